@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::paths;
+
+const ALIASES_FILE: &str = "aliases.tsv";
+
+/// Short names for frequently visited URLs, expanded by `go <name>`.
+///
+/// Backed by a TSV file with one `name<TAB>url` entry per line, written back
+/// on every change. When no path is available (no home directory), aliases
+/// are kept in memory only.
+#[derive(Debug)]
+pub struct Aliases {
+    path: Option<PathBuf>,
+    entries: BTreeMap<String, String>,
+}
+
+impl Aliases {
+    /// Load the aliases file from the config directory.
+    pub fn load_default() -> Self {
+        match paths::config_dir() {
+            Some(dir) => Self::load(dir.join(ALIASES_FILE)),
+            None => Self {
+                path: None,
+                entries: BTreeMap::new(),
+            },
+        }
+    }
+
+    /// Load aliases from `path`. A missing or unreadable file yields no
+    /// aliases.
+    pub fn load(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .map(|contents| parse_aliases(&contents))
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            entries,
+        }
+    }
+
+    /// Define `name` as `url`, replacing any previous definition, and
+    /// persist the file.
+    pub fn add(&mut self, name: &str, url: &str) -> io::Result<()> {
+        self.entries.insert(name.to_string(), url.to_string());
+        self.save()
+    }
+
+    /// Remove `name` and persist the file. Returns false if it wasn't
+    /// defined.
+    pub fn remove(&mut self, name: &str) -> io::Result<bool> {
+        if self.entries.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// `(name, url)` pairs in name order.
+    pub fn list(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(name, url)| (name.as_str(), url.as_str()))
+    }
+
+    /// The URL for `arg` if it is exactly a defined alias. Anything with a
+    /// scheme is a URL, never an alias.
+    pub fn expand(&self, arg: &str) -> Option<&str> {
+        if arg.contains("://") {
+            return None;
+        }
+        self.entries.get(arg).map(String::as_str)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, format_aliases(&self.entries))
+    }
+}
+
+/// Parse aliases file contents. Blank lines and lines without a tab are
+/// skipped.
+fn parse_aliases(contents: &str) -> BTreeMap<String, String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (name, url) = line.split_once('\t')?;
+            let (name, url) = (name.trim(), url.trim());
+            (!name.is_empty() && !url.is_empty()).then(|| (name.to_string(), url.to_string()))
+        })
+        .collect()
+}
+
+fn format_aliases(entries: &BTreeMap<String, String>) -> String {
+    entries
+        .iter()
+        .map(|(name, url)| format!("{name}\t{url}\n"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_memory() -> Aliases {
+        Aliases {
+            path: None,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_expand_only_exact_names() {
+        let mut aliases = in_memory();
+        aliases.add("home", "gemini://example.com/").unwrap();
+        assert_eq!(aliases.expand("home"), Some("gemini://example.com/"));
+        assert_eq!(aliases.expand("hom"), None);
+        assert_eq!(aliases.expand("home/page"), None);
+        assert_eq!(aliases.expand("gemini://home"), None);
+    }
+
+    #[test]
+    fn test_url_like_names_never_expand() {
+        let mut aliases = in_memory();
+        aliases.add("gemini://a", "gemini://b/").unwrap();
+        assert_eq!(aliases.expand("gemini://a"), None);
+    }
+
+    #[test]
+    fn test_add_replace_and_remove() {
+        let mut aliases = in_memory();
+        aliases.add("b", "gemini://b.example/").unwrap();
+        aliases.add("a", "gemini://a.example/").unwrap();
+        aliases.add("b", "gemini://b2.example/").unwrap();
+        assert_eq!(
+            aliases.list().collect::<Vec<_>>(),
+            vec![("a", "gemini://a.example/"), ("b", "gemini://b2.example/")]
+        );
+        assert!(aliases.remove("a").unwrap());
+        assert!(!aliases.remove("a").unwrap());
+        assert_eq!(aliases.list().count(), 1);
+    }
+
+    #[test]
+    fn test_aliases_roundtrip() {
+        let path =
+            std::env::temp_dir().join(format!("gemini-browser-aliases-{}", std::process::id()));
+        let mut aliases = Aliases::load(path.clone());
+        aliases.add("cap", "gemini://capsule.example/").unwrap();
+        aliases.add("news", "gemini://news.example/").unwrap();
+        aliases.remove("news").unwrap();
+
+        let reloaded = Aliases::load(path.clone());
+        assert_eq!(reloaded.expand("cap"), Some("gemini://capsule.example/"));
+        assert_eq!(reloaded.expand("news"), None);
+        let _ = fs::remove_file(path);
+    }
+}
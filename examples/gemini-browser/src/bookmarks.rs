@@ -0,0 +1,311 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::parser::{self, GeminiLine};
+use crate::paths;
+use crate::url_utils;
+
+const BOOKMARKS_FILE: &str = "bookmarks.tsv";
+
+/// A saved page: its absolute URL and a human-readable title.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub url: String,
+    pub title: String,
+}
+
+/// The user's bookmarks, in the order they were added.
+///
+/// Backed by a TSV file with one `url<TAB>title` entry per line. Every
+/// mutation is written straight back to disk. When no path is available (no
+/// home directory), bookmarks are kept in memory only.
+#[derive(Debug)]
+pub struct Bookmarks {
+    path: Option<PathBuf>,
+    entries: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    /// Load the bookmarks file from the config directory.
+    pub fn load_default() -> Self {
+        match paths::config_dir() {
+            Some(dir) => Self::load(dir.join(BOOKMARKS_FILE)),
+            None => Self {
+                path: None,
+                entries: Vec::new(),
+            },
+        }
+    }
+
+    /// Load bookmarks from `path`. A missing or unreadable file yields an
+    /// empty list.
+    pub fn load(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .map(|contents| parse_bookmarks(&contents))
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            entries,
+        }
+    }
+
+    /// Append a bookmark and persist the file.
+    pub fn add(&mut self, url: &str, title: &str) -> io::Result<()> {
+        self.entries.push(Bookmark {
+            url: url.to_string(),
+            title: sanitize(title),
+        });
+        self.save()
+    }
+
+    /// Remove the bookmark at `index` (zero-based) and persist the file.
+    /// Returns None if there is no such bookmark.
+    pub fn remove(&mut self, index: usize) -> io::Result<Option<Bookmark>> {
+        if index >= self.entries.len() {
+            return Ok(None);
+        }
+        let removed = self.entries.remove(index);
+        self.save()?;
+        Ok(Some(removed))
+    }
+
+    /// Point bookmarks for `from` at `to` instead, persisting the file if
+    /// any changed. Returns how many did.
+    pub fn replace_url(&mut self, from: &str, to: &str) -> io::Result<usize> {
+        let mut replaced = 0;
+        for entry in self.entries.iter_mut().filter(|entry| entry.url == from) {
+            entry.url = to.to_string();
+            replaced += 1;
+        }
+        if replaced > 0 {
+            self.save()?;
+        }
+        Ok(replaced)
+    }
+
+    pub fn list(&self) -> &[Bookmark] {
+        &self.entries
+    }
+
+    /// The bookmarks as a gemtext page, one `=> url title` link each.
+    pub fn to_gemtext(&self) -> String {
+        let mut page = String::from("# Bookmarks\n\n");
+        for b in &self.entries {
+            if b.title.is_empty() {
+                page.push_str(&format!("=> {}\n", b.url));
+            } else {
+                page.push_str(&format!("=> {} {}\n", b.url, b.title));
+            }
+        }
+        page
+    }
+
+    /// Bookmark every link in the gemtext page `gemtext`, titled by its
+    /// label, and persist the file. Only absolute gemini:// links are
+    /// taken, and URLs already bookmarked are skipped. Returns how many
+    /// were added.
+    pub fn import(&mut self, gemtext: &str) -> io::Result<usize> {
+        let before = self.entries.len();
+        for (_, line) in parser::parse_gemini(gemtext) {
+            let GeminiLine::Link { url, label } = line else {
+                continue;
+            };
+            // A relative link would otherwise be read as a bare host name
+            if !url.contains("://") {
+                continue;
+            }
+            let Ok(parsed) = url_utils::parse_gemini_url(&url) else {
+                continue;
+            };
+            if self.entries.iter().any(|b| b.url == parsed.as_str()) {
+                continue;
+            }
+            // An unlabelled link's label is its URL
+            let title = if label == url { "" } else { &label };
+            self.entries.push(Bookmark {
+                url: parsed.to_string(),
+                title: sanitize(title),
+            });
+        }
+        let added = self.entries.len() - before;
+        if added > 0 {
+            self.save()?;
+        }
+        Ok(added)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, format_bookmarks(&self.entries))
+    }
+}
+
+/// Titles share the line with the URL, so tabs and newlines become spaces.
+fn sanitize(title: &str) -> String {
+    title
+        .chars()
+        .map(|c| {
+            if c == '\t' || c == '\n' || c == '\r' {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Parse bookmarks file contents. Blank lines are skipped; a line without a
+/// tab is a bookmark with an empty title.
+fn parse_bookmarks(contents: &str) -> Vec<Bookmark> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (url, title) = line.split_once('\t').unwrap_or((line, ""));
+            Bookmark {
+                url: url.trim().to_string(),
+                title: title.trim().to_string(),
+            }
+        })
+        .collect()
+}
+
+fn format_bookmarks(entries: &[Bookmark]) -> String {
+    entries
+        .iter()
+        .map(|b| format!("{}\t{}\n", b.url, b.title))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_memory() -> Bookmarks {
+        Bookmarks {
+            path: None,
+            entries: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let mut bookmarks = in_memory();
+        bookmarks.add("gemini://a.example/", "A").unwrap();
+        bookmarks.add("gemini://b.example/", "B").unwrap();
+        assert_eq!(bookmarks.list().len(), 2);
+
+        let removed = bookmarks.remove(0).unwrap().unwrap();
+        assert_eq!(removed.url, "gemini://a.example/");
+        assert_eq!(bookmarks.list()[0].title, "B");
+        assert!(bookmarks.remove(5).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_title_tabs_are_replaced() {
+        let mut bookmarks = in_memory();
+        bookmarks.add("gemini://a.example/", "one\ttwo\n").unwrap();
+        assert_eq!(bookmarks.list()[0].title, "one two");
+    }
+
+    #[test]
+    fn test_replace_url() {
+        let mut bookmarks = in_memory();
+        bookmarks.add("gemini://a.example/old", "A").unwrap();
+        bookmarks.add("gemini://b.example/", "B").unwrap();
+        bookmarks.add("gemini://a.example/old", "A again").unwrap();
+        let replaced = bookmarks
+            .replace_url("gemini://a.example/old", "gemini://a.example/new")
+            .unwrap();
+        assert_eq!(replaced, 2);
+        let urls: Vec<&str> = bookmarks.list().iter().map(|b| b.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "gemini://a.example/new",
+                "gemini://b.example/",
+                "gemini://a.example/new"
+            ]
+        );
+        assert_eq!(
+            bookmarks.replace_url("gemini://c.example/", "x").unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_gemtext_export_and_import() {
+        let mut bookmarks = in_memory();
+        bookmarks.add("gemini://a.example/", "A page").unwrap();
+        bookmarks.add("gemini://b.example/", "").unwrap();
+        let page = bookmarks.to_gemtext();
+        assert_eq!(
+            page,
+            "# Bookmarks\n\n=> gemini://a.example/ A page\n=> gemini://b.example/\n"
+        );
+
+        let mut imported = in_memory();
+        imported.add("gemini://b.example/", "Mine").unwrap();
+        let added = imported
+            .import(&format!(
+                "{page}=> /relative Skipped\n=> mailto:a@b.example Mail\n\
+                 => https://c.example/ Web\n=> gemini://a.example/ Dup\n"
+            ))
+            .unwrap();
+        assert_eq!(added, 1);
+        assert_eq!(
+            imported.list(),
+            &[
+                Bookmark {
+                    url: "gemini://b.example/".to_string(),
+                    title: "Mine".to_string(),
+                },
+                Bookmark {
+                    url: "gemini://a.example/".to_string(),
+                    title: "A page".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_bookmarks() {
+        let entries = parse_bookmarks("gemini://a.example/\tA page\n\ngemini://b.example/\n");
+        assert_eq!(
+            entries,
+            vec![
+                Bookmark {
+                    url: "gemini://a.example/".to_string(),
+                    title: "A page".to_string(),
+                },
+                Bookmark {
+                    url: "gemini://b.example/".to_string(),
+                    title: String::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bookmarks_roundtrip() {
+        let path =
+            std::env::temp_dir().join(format!("gemini-browser-bookmarks-{}", std::process::id()));
+        let mut bookmarks = Bookmarks::load(path.clone());
+        bookmarks.add("gemini://a.example/", "A").unwrap();
+        bookmarks.add("gemini://b.example/", "B").unwrap();
+        bookmarks.remove(0).unwrap();
+
+        let reloaded = Bookmarks::load(path.clone());
+        assert_eq!(reloaded.list().len(), 1);
+        assert_eq!(reloaded.list()[0].url, "gemini://b.example/");
+        let _ = fs::remove_file(path);
+    }
+}
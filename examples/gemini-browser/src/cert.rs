@@ -0,0 +1,189 @@
+use time::OffsetDateTime;
+use x509_parser::certificate::X509Certificate;
+
+use crate::tofu;
+
+/// How close to expiry a certificate must be before `info` warns about it.
+const EXPIRY_WARNING_DAYS: i64 = 7;
+
+/// Details of a server certificate, captured after the TLS handshake.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertificateInfo {
+    /// SHA-256 fingerprint as lowercase hex, as pinned in `known_hosts`.
+    pub fingerprint: String,
+    /// Subject distinguished name, e.g. `CN=example.com, O=Example`.
+    pub subject: Option<String>,
+    /// Start of the validity period.
+    pub not_before: Option<OffsetDateTime>,
+    /// End of the validity period.
+    pub not_after: Option<OffsetDateTime>,
+}
+
+impl CertificateInfo {
+    /// Read the fingerprint, subject, and validity period of a DER-encoded
+    /// X.509 certificate. When it cannot be parsed, only the fingerprint is
+    /// filled in.
+    pub fn from_der(der: &[u8]) -> Self {
+        let fingerprint = tofu::fingerprint(der);
+        match x509_parser::parse_x509_certificate(der) {
+            Ok((_, cert)) => Self::from_parsed(fingerprint, &cert),
+            Err(_) => Self {
+                fingerprint,
+                subject: None,
+                not_before: None,
+                not_after: None,
+            },
+        }
+    }
+
+    fn from_parsed(fingerprint: String, cert: &X509Certificate<'_>) -> Self {
+        let subject = cert.subject().to_string();
+        let validity = cert.validity();
+        Self {
+            fingerprint,
+            subject: (!subject.is_empty()).then_some(subject),
+            not_before: Some(validity.not_before.to_datetime()),
+            not_after: Some(validity.not_after.to_datetime()),
+        }
+    }
+
+    /// What is wrong if `now` falls outside the validity period, e.g.
+    /// `expired on 2024-01-01 00:00:00 UTC (valid from ... until ...)`.
+    /// Dates that could not be parsed are not checked.
+    pub fn validity_error(&self, now: OffsetDateTime) -> Option<String> {
+        let problem = match (self.not_before, self.not_after) {
+            (_, Some(not_after)) if now > not_after => {
+                format!("expired on {}", format_date(not_after))
+            }
+            (Some(not_before), _) if now < not_before => {
+                format!("is not valid until {}", format_date(not_before))
+            }
+            _ => return None,
+        };
+        Some(match (self.not_before, self.not_after) {
+            (Some(not_before), Some(not_after)) => format!(
+                "{problem} (valid from {} until {})",
+                format_date(not_before),
+                format_date(not_after)
+            ),
+            _ => problem,
+        })
+    }
+
+    /// A warning if the certificate has expired or expires within a week
+    /// of `now`.
+    pub fn expiry_warning(&self, now: OffsetDateTime) -> Option<String> {
+        let not_after = self.not_after?;
+        let date = format_date(not_after);
+        if not_after <= now {
+            return Some(format!("certificate expired on {date}"));
+        }
+        let days = (not_after - now).whole_days();
+        if days < EXPIRY_WARNING_DAYS {
+            let plural = if days == 1 { "" } else { "s" };
+            Some(format!(
+                "certificate expires in {days} day{plural}, on {date}"
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// Format a timestamp as `YYYY-MM-DD HH:MM:SS UTC`.
+pub fn format_date(at: OffsetDateTime) -> String {
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        at.year(),
+        u8::from(at.month()),
+        at.day(),
+        at.hour(),
+        at.minute(),
+        at.second()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{date_time_ymd, CertificateParams, DistinguishedName, DnType, KeyPair};
+
+    fn cert_der(common_name: &str, not_after: OffsetDateTime) -> Vec<u8> {
+        let key_pair = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(vec![common_name.to_string()]).unwrap();
+        let mut name = DistinguishedName::new();
+        name.push(DnType::CommonName, common_name);
+        name.push(DnType::OrganizationName, "Example");
+        params.distinguished_name = name;
+        params.not_before = not_after - time::Duration::days(365);
+        params.not_after = not_after;
+        params.self_signed(&key_pair).unwrap().der().to_vec()
+    }
+
+    #[test]
+    fn test_from_der_reads_subject_and_expiry() {
+        let der = cert_der("example.com", date_time_ymd(2030, 1, 2));
+        let info = CertificateInfo::from_der(&der);
+        assert_eq!(info.fingerprint, tofu::fingerprint(&der));
+        assert_eq!(info.subject.as_deref(), Some("CN=example.com, O=Example"));
+        assert_eq!(info.not_before, Some(date_time_ymd(2029, 1, 2)));
+        assert_eq!(info.not_after, Some(date_time_ymd(2030, 1, 2)));
+    }
+
+    #[test]
+    fn test_from_der_reads_generalized_time() {
+        // Dates from 2050 on are encoded as GeneralizedTime
+        let der = cert_der("example.com", date_time_ymd(2077, 6, 30));
+        let info = CertificateInfo::from_der(&der);
+        assert_eq!(info.not_after, Some(date_time_ymd(2077, 6, 30)));
+    }
+
+    #[test]
+    fn test_from_der_garbage() {
+        let info = CertificateInfo::from_der(b"not a certificate");
+        assert_eq!(info.subject, None);
+        assert_eq!(info.not_after, None);
+        assert_eq!(info.fingerprint.len(), 64);
+    }
+
+    #[test]
+    fn test_expiry_warning() {
+        let now = date_time_ymd(2030, 1, 1);
+        let info = |not_after| CertificateInfo {
+            fingerprint: String::new(),
+            subject: None,
+            not_before: None,
+            not_after: Some(not_after),
+        };
+        assert_eq!(
+            info(date_time_ymd(2029, 12, 31)).expiry_warning(now),
+            Some("certificate expired on 2029-12-31 00:00:00 UTC".to_string())
+        );
+        assert_eq!(
+            info(date_time_ymd(2030, 1, 4)).expiry_warning(now),
+            Some("certificate expires in 3 days, on 2030-01-04 00:00:00 UTC".to_string())
+        );
+        assert_eq!(info(date_time_ymd(2030, 2, 1)).expiry_warning(now), None);
+    }
+
+    #[test]
+    fn test_validity_error() {
+        let info = CertificateInfo::from_der(&cert_der("example.com", date_time_ymd(2030, 1, 2)));
+        assert_eq!(info.validity_error(date_time_ymd(2029, 6, 1)), None);
+        assert_eq!(
+            info.validity_error(date_time_ymd(2030, 1, 3)),
+            Some(
+                "expired on 2030-01-02 00:00:00 UTC \
+                 (valid from 2029-01-02 00:00:00 UTC until 2030-01-02 00:00:00 UTC)"
+                    .to_string()
+            )
+        );
+        assert!(info
+            .validity_error(date_time_ymd(2028, 1, 1))
+            .unwrap()
+            .starts_with("is not valid until 2029-01-02 00:00:00 UTC"));
+
+        let garbage = CertificateInfo::from_der(b"not a certificate");
+        assert_eq!(garbage.validity_error(date_time_ymd(2100, 1, 1)), None);
+    }
+}
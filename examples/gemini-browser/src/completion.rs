@@ -0,0 +1,171 @@
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+/// Command words offered at the start of a line. Single-letter aliases are
+/// left out since they're already as short as they get.
+const COMMANDS: &[&str] = &[
+    "alias",
+    "back",
+    "bookmark",
+    "bookmarks",
+    "bottom",
+    "cert",
+    "export",
+    "follow",
+    "go",
+    "help",
+    "history",
+    "home",
+    "info",
+    "last",
+    "links",
+    "next",
+    "page",
+    "peek",
+    "prev",
+    "quit",
+    "reload",
+    "save",
+    "source",
+    "subscribe",
+    "toc",
+    "top",
+    "updates",
+    "upload",
+    "verbose",
+];
+
+/// Tab completions for the REPL, from a snapshot of the browser state
+/// taken before each prompt.
+pub struct Completions {
+    /// Defined alias names, completed after `go ` and `alias rm `.
+    pub aliases: Vec<String>,
+    /// Bookmarked URLs, also completed after `go `.
+    pub bookmarks: Vec<String>,
+    /// Links on the current page, whose numbers are completed after `peek `.
+    pub link_count: usize,
+}
+
+impl Completions {
+    /// The byte offset in `line` where the word being completed starts, and
+    /// the words it could become.
+    fn candidates(&self, line: &str) -> (usize, Vec<String>) {
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let (command, word) = line.split_at(start);
+        let options: Vec<String> = match command.split_whitespace().collect::<Vec<_>>()[..] {
+            [] => COMMANDS.iter().map(|c| c.to_string()).collect(),
+            ["go"] => self
+                .aliases
+                .iter()
+                .chain(&self.bookmarks)
+                .cloned()
+                .collect(),
+            ["alias", "rm"] => self.aliases.clone(),
+            ["peek"] => (1..=self.link_count).map(|n| n.to_string()).collect(),
+            _ => Vec::new(),
+        };
+        let candidates = options
+            .into_iter()
+            .filter(|option| option.starts_with(word))
+            .collect();
+        (start, candidates)
+    }
+}
+
+impl Completer for Completions {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(&line[..pos]))
+    }
+}
+
+impl Hinter for Completions {
+    type Hint = String;
+}
+
+impl Highlighter for Completions {}
+
+impl Validator for Completions {}
+
+impl Helper for Completions {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completions() -> Completions {
+        Completions {
+            aliases: vec!["cap".to_string(), "news".to_string()],
+            bookmarks: vec![
+                "gemini://example.com/".to_string(),
+                "gemini://geminiprotocol.net/".to_string(),
+            ],
+            link_count: 12,
+        }
+    }
+
+    #[test]
+    fn test_complete_command_names() {
+        let c = completions();
+        assert_eq!(
+            c.candidates("bo"),
+            (
+                0,
+                vec!["bookmark".into(), "bookmarks".into(), "bottom".into()]
+            )
+        );
+        assert_eq!(c.candidates("he"), (0, vec!["help".to_string()]));
+        assert_eq!(c.candidates("xyz"), (0, Vec::new()));
+        assert_eq!(c.candidates("").1.len(), COMMANDS.len());
+    }
+
+    #[test]
+    fn test_complete_aliases_after_go() {
+        let c = completions();
+        assert_eq!(c.candidates("go n"), (3, vec!["news".to_string()]));
+        assert_eq!(c.candidates("go ").1.len(), 4);
+        assert_eq!(c.candidates("alias rm c"), (9, vec!["cap".to_string()]));
+        // Only the first argument of go is an alias
+        assert_eq!(c.candidates("go cap n"), (7, Vec::new()));
+    }
+
+    #[test]
+    fn test_complete_bookmarks_after_go() {
+        let c = completions();
+        assert_eq!(
+            c.candidates("go gemini://ex"),
+            (3, vec!["gemini://example.com/".to_string()])
+        );
+        assert_eq!(c.candidates("go gemini://").1.len(), 2);
+        assert_eq!(c.candidates("alias rm gemini://").1, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_completer_uses_text_before_cursor() {
+        let c = completions();
+        let history = rustyline::history::DefaultHistory::new();
+        let ctx = Context::new(&history);
+        let (start, candidates) = c.complete("go n and more", 4, &ctx).unwrap();
+        assert_eq!((start, candidates), (3, vec!["news".to_string()]));
+    }
+
+    #[test]
+    fn test_complete_link_numbers_after_peek() {
+        let c = completions();
+        assert_eq!(
+            c.candidates("peek 1"),
+            (5, vec!["1".into(), "10".into(), "11".into(), "12".into()])
+        );
+        assert_eq!(c.candidates("peek 5").1, vec!["5".to_string()]);
+        assert_eq!(c.candidates("peek 13").1, Vec::<String>::new());
+    }
+}
@@ -0,0 +1,222 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::gemini::TlsVersion;
+use crate::paths;
+use crate::render::ColorMode;
+use crate::verify::VerifyMode;
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// Startup defaults from `config.toml`. Every setting is optional; anything
+/// left unset falls back to the built-in default, and command-line options
+/// take precedence over the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// Page opened when no URL is given on the command line.
+    pub home: Option<String>,
+    pub color: Option<ColorMode>,
+    /// Largest response body accepted, in bytes.
+    pub max_body_size: Option<usize>,
+    pub connect_timeout: Option<Duration>,
+    pub io_timeout: Option<Duration>,
+    /// Theme file to use instead of `theme.toml` in the config directory.
+    pub theme: Option<PathBuf>,
+    pub verify: Option<VerifyMode>,
+    /// Oldest TLS version to negotiate.
+    pub min_tls: Option<TlsVersion>,
+    /// Accept response headers with no space after the status code.
+    pub lenient_headers: Option<bool>,
+    /// Least time between requests to one host.
+    pub request_interval: Option<Duration>,
+    /// How many times a temporary failure is retried.
+    pub max_retries: Option<u32>,
+    /// Wrap text at this many columns instead of the terminal width.
+    pub wrap_width: Option<usize>,
+    /// Command that opens images and other media, instead of the system
+    /// opener.
+    pub viewer: Option<String>,
+    /// Whether media is opened in the viewer as soon as it is received.
+    pub open_media: Option<bool>,
+    /// Whether non-gemini links may be offered to the system handler.
+    pub open_external: Option<bool>,
+    /// Whether the terminal title follows the page title.
+    pub terminal_title: Option<bool>,
+}
+
+/// `config.toml` as written by the user, e.g.
+///
+/// ```toml
+/// home = "gemini://geminiprotocol.net/"
+/// color = "auto"
+/// connect_timeout = 10  # seconds
+/// verify = "tofu"
+/// min_tls = "1.3"
+/// lenient_headers = true
+/// request_interval = 1  # seconds
+/// max_retries = 2
+/// wrap_width = 72
+/// viewer = "feh --scale-down"
+/// open_media = true
+/// open_external = false
+/// terminal_title = false
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    home: Option<String>,
+    color: Option<String>,
+    max_body_size: Option<usize>,
+    connect_timeout: Option<u64>,
+    io_timeout: Option<u64>,
+    theme: Option<PathBuf>,
+    verify: Option<String>,
+    min_tls: Option<String>,
+    lenient_headers: Option<bool>,
+    request_interval: Option<u64>,
+    max_retries: Option<u32>,
+    wrap_width: Option<usize>,
+    viewer: Option<String>,
+    open_media: Option<bool>,
+    open_external: Option<bool>,
+    terminal_title: Option<bool>,
+}
+
+impl Config {
+    /// Load `config.toml` from the config directory. A missing file gives
+    /// the defaults; an invalid one prints a warning and does the same.
+    pub fn load_default() -> Self {
+        let Some(dir) = paths::config_dir() else {
+            return Self::default();
+        };
+        let path = dir.join(CONFIG_FILE);
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        match Self::from_toml(&contents, &dir) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Warning: ignoring invalid config {}: {e}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    /// Parse config file contents. A relative `theme` path is taken
+    /// relative to `dir`, the directory holding the file.
+    pub fn from_toml(contents: &str, dir: &Path) -> Result<Self, String> {
+        let file: ConfigFile = toml::from_str(contents).map_err(|e| e.message().to_string())?;
+        let color = file
+            .color
+            .map(|mode| ColorMode::parse(&mode).ok_or(format!("invalid color: {mode:?}")))
+            .transpose()?;
+        let verify = file
+            .verify
+            .map(|mode| VerifyMode::parse(&mode).ok_or(format!("invalid verify: {mode:?}")))
+            .transpose()?;
+        let min_tls = file
+            .min_tls
+            .map(|v| TlsVersion::parse(&v).ok_or(format!("invalid min_tls: {v:?}")))
+            .transpose()?;
+        if file.wrap_width == Some(0) {
+            return Err("wrap_width must be at least 1".to_string());
+        }
+        if file.viewer.as_deref().is_some_and(|v| v.trim().is_empty()) {
+            return Err("viewer must not be empty".to_string());
+        }
+        Ok(Self {
+            home: file.home,
+            color,
+            max_body_size: file.max_body_size,
+            connect_timeout: file.connect_timeout.map(Duration::from_secs),
+            io_timeout: file.io_timeout.map(Duration::from_secs),
+            theme: file.theme.map(|theme| dir.join(theme)),
+            verify,
+            min_tls,
+            lenient_headers: file.lenient_headers,
+            request_interval: file.request_interval.map(Duration::from_secs),
+            max_retries: file.max_retries,
+            wrap_width: file.wrap_width,
+            viewer: file.viewer,
+            open_media: file.open_media,
+            open_external: file.open_external,
+            terminal_title: file.terminal_title,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml_sample() {
+        let sample = "home = \"gemini://example.com/\"\n\
+                      color = \"never\"\n\
+                      max_body_size = 1048576\n\
+                      connect_timeout = 10\n\
+                      io_timeout = 30\n\
+                      theme = \"dark.toml\"\n\
+                      verify = \"ca\"\n\
+                      min_tls = \"1.3\"\n\
+                      lenient_headers = true\n\
+                      request_interval = 2\n\
+                      max_retries = 3\n\
+                      wrap_width = 72\n\
+                      viewer = \"feh -F\"\n\
+                      open_media = false\n\
+                      open_external = false\n\
+                      terminal_title = false\n";
+        let config = Config::from_toml(sample, Path::new("/etc/gb")).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                home: Some("gemini://example.com/".to_string()),
+                color: Some(ColorMode::Never),
+                max_body_size: Some(1_048_576),
+                connect_timeout: Some(Duration::from_secs(10)),
+                io_timeout: Some(Duration::from_secs(30)),
+                theme: Some(PathBuf::from("/etc/gb/dark.toml")),
+                verify: Some(VerifyMode::Ca),
+                min_tls: Some(TlsVersion::Tls13),
+                lenient_headers: Some(true),
+                request_interval: Some(Duration::from_secs(2)),
+                max_retries: Some(3),
+                wrap_width: Some(72),
+                viewer: Some("feh -F".to_string()),
+                open_media: Some(false),
+                open_external: Some(false),
+                terminal_title: Some(false),
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_toml_partial_and_empty() {
+        assert_eq!(
+            Config::from_toml("", Path::new("/")).unwrap(),
+            Config::default()
+        );
+        let config = Config::from_toml("theme = \"/abs/theme.toml\"", Path::new("/etc")).unwrap();
+        assert_eq!(config.theme, Some(PathBuf::from("/abs/theme.toml")));
+        assert_eq!(config.color, None);
+    }
+
+    #[test]
+    fn test_from_toml_rejects_bad_input() {
+        let dir = Path::new("/");
+        assert!(Config::from_toml("color = \"sometimes\"", dir).is_err());
+        assert!(Config::from_toml("verify = \"off\"", dir).is_err());
+        assert!(Config::from_toml("min_tls = \"1.1\"", dir).is_err());
+        assert!(Config::from_toml("wrap_width = 0", dir).is_err());
+        assert!(Config::from_toml("viewer = \" \"", dir).is_err());
+        assert!(Config::from_toml("open_external = \"no\"", dir).is_err());
+        assert!(Config::from_toml("terminal_title = 1", dir).is_err());
+        assert!(Config::from_toml("connect_timeout = \"5s\"", dir).is_err());
+        assert!(Config::from_toml("homepage = \"x\"", dir).is_err());
+        assert!(Config::from_toml("not toml", dir).is_err());
+    }
+}
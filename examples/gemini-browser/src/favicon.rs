@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use url::Url;
+
+use crate::gemini::GeminiClient;
+use crate::mime;
+
+/// Per-host favicons from the `/favicon.txt` convention, where a capsule
+/// publishes a single emoji to represent itself.
+///
+/// Favicons are fetched on background threads; finished fetches are picked
+/// up by `collect`. Hosts without a usable favicon are remembered too, so
+/// each host is only asked once per session.
+pub struct Favicons {
+    /// Keyed by `host:port`. None means pending, missing, or malformed.
+    cache: HashMap<String, Option<String>>,
+    tx: Sender<(String, Option<String>)>,
+    rx: Receiver<(String, Option<String>)>,
+}
+
+impl Default for Favicons {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            cache: HashMap::new(),
+            tx,
+            rx,
+        }
+    }
+}
+
+impl Favicons {
+    /// Start fetching the favicon for `url`'s host unless it has already
+    /// been requested.
+    pub fn request(&mut self, client: &GeminiClient, url: &Url) {
+        let Some(key) = host_key(url) else {
+            return;
+        };
+        if self.cache.contains_key(&key) {
+            return;
+        }
+        self.cache.insert(key.clone(), None);
+
+        let mut favicon_url = url.clone();
+        favicon_url.set_path("/favicon.txt");
+        favicon_url.set_query(None);
+        favicon_url.set_fragment(None);
+        // Diagnostics from the background fetch would interleave with the page
+        let mut client = client.clone();
+        client.set_verbose(false);
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            let favicon = client
+                .fetch(&favicon_url)
+                .ok()
+                .filter(|response| response.status == 20)
+                .and_then(|response| {
+                    let body = mime::parse(&response.meta).decode(&response.body?);
+                    parse_favicon(&body)
+                });
+            let _ = tx.send((key, favicon));
+        });
+    }
+
+    /// Store the results of any background fetches that have finished.
+    pub fn collect(&mut self) {
+        while let Ok((key, favicon)) = self.rx.try_recv() {
+            self.cache.insert(key, favicon);
+        }
+    }
+
+    /// The favicon for `url`'s host, if one has been fetched.
+    pub fn get(&self, url: &Url) -> Option<&str> {
+        self.cache.get(&host_key(url)?)?.as_deref()
+    }
+}
+
+fn host_key(url: &Url) -> Option<String> {
+    Some(format!(
+        "{}:{}",
+        url.host_str()?,
+        url.port().unwrap_or(1965)
+    ))
+}
+
+/// Accept `body` as a favicon only if, once trimmed, it is a single emoji
+/// (including flag pairs, skin tones, and ZWJ sequences).
+fn parse_favicon(body: &str) -> Option<String> {
+    let emoji = body.trim();
+    let mut bases = 0;
+    let mut regional_indicators = 0;
+    let mut after_joiner = false;
+    for c in emoji.chars() {
+        match c {
+            '\u{200d}' => {
+                after_joiner = true;
+                continue;
+            }
+            // Variation selector, keycap, skin tones, and tag characters
+            '\u{fe0f}' | '\u{20e3}' | '\u{1f3fb}'..='\u{1f3ff}' | '\u{e0020}'..='\u{e007f}' => {}
+            '\u{1f1e6}'..='\u{1f1ff}' => regional_indicators += 1,
+            c if is_emoji_base(c) => {
+                if !after_joiner {
+                    bases += 1;
+                }
+            }
+            _ => return None,
+        }
+        after_joiner = false;
+    }
+    let single = match regional_indicators {
+        0 => bases == 1,
+        2 => bases == 0,
+        _ => false,
+    };
+    single.then(|| emoji.to_string())
+}
+
+/// Characters that can start an emoji presentation.
+fn is_emoji_base(c: char) -> bool {
+    matches!(c,
+        '\u{2300}'..='\u{23ff}'
+        | '\u{2600}'..='\u{27bf}'
+        | '\u{2b00}'..='\u{2bff}'
+        | '\u{1f000}'..='\u{1faff}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_favicon_accepts_single_emoji() {
+        assert_eq!(parse_favicon("🐱\n").as_deref(), Some("🐱"));
+        assert_eq!(parse_favicon("☕\u{fe0f}").as_deref(), Some("☕\u{fe0f}"));
+        assert_eq!(parse_favicon("👋🏽").as_deref(), Some("👋🏽"));
+        assert_eq!(parse_favicon("🇳🇿").as_deref(), Some("🇳🇿"));
+        assert_eq!(
+            parse_favicon("👩\u{200d}💻").as_deref(),
+            Some("👩\u{200d}💻")
+        );
+    }
+
+    #[test]
+    fn test_parse_favicon_rejects_other_content() {
+        assert_eq!(parse_favicon(""), None);
+        assert_eq!(parse_favicon("x"), None);
+        assert_eq!(parse_favicon("🐱🐶"), None);
+        assert_eq!(parse_favicon("🐱 cat"), None);
+        assert_eq!(parse_favicon("<html>"), None);
+        assert_eq!(parse_favicon("🇳"), None);
+    }
+
+    #[test]
+    fn test_cache_lookup_by_host() {
+        let mut favicons = Favicons::default();
+        let url = Url::parse("gemini://example.com/page").unwrap();
+        favicons
+            .tx
+            .send(("example.com:1965".to_string(), Some("🐱".to_string())))
+            .unwrap();
+        assert_eq!(favicons.get(&url), None);
+        favicons.collect();
+        assert_eq!(favicons.get(&url), Some("🐱"));
+        let other_port = Url::parse("gemini://example.com:1966/").unwrap();
+        assert_eq!(favicons.get(&other_port), None);
+    }
+}
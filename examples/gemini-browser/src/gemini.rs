@@ -1,19 +1,209 @@
-use std::io::{self, Read, Write};
-use std::net::TcpStream;
-use std::sync::Arc;
-use std::time::Duration;
-
-use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
-use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
-use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme, StreamOwned};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rustls::client::danger::ServerCertVerifier;
+use rustls::pki_types::ServerName;
+use rustls::{
+    AlertDescription, CertificateError, ClientConfig, ClientConnection, HandshakeKind,
+    PeerIncompatible, StreamOwned, SupportedProtocolVersion,
+};
 use url::Url;
 
+use crate::cert::CertificateInfo;
+use crate::identity::{self, ClientIdentity};
+use crate::mime;
+use crate::tofu::{CertificateMismatch, CertificateOutOfDate, KnownHosts, TofuVerifier};
 use crate::url_utils;
 
-const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
-const IO_TIMEOUT: Duration = Duration::from_secs(5);
-const MAX_HEADER_LEN: usize = 1026; // 1024 META + 2 for status digits (+ space, but bounded)
-const MAX_BODY_SIZE: usize = 5_242_880; // 5 MB
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_IO_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_META_LEN: usize = 1024;
+const MAX_HEADER_LEN: usize = MAX_META_LEN + 3; // 2 status digits + space + META
+const DEFAULT_MAX_BODY_SIZE: usize = 5_242_880; // 5 MB
+const DEFAULT_MAX_REDIRECTS: usize = 5;
+const DEFAULT_MAX_RETRIES: u32 = 0;
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_SLOW_DOWN_WAIT: Duration = Duration::from_secs(120);
+/// How often a retry wait checks whether it has been cancelled.
+const CANCEL_POLL: Duration = Duration::from_millis(50);
+
+/// TLS configs built so far, keyed by host and port: the client identity
+/// depends on the host, and TOFU pins on both.
+type TlsConfigCache = Arc<Mutex<HashMap<(String, u16), Arc<ClientConfig>>>>;
+
+/// Oldest TLS version the client will negotiate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TlsVersion {
+    /// TLS 1.2 or 1.3, whichever the server prefers.
+    #[default]
+    Tls12,
+    /// TLS 1.3 only.
+    Tls13,
+}
+
+impl TlsVersion {
+    /// Parse a version as written in the config file or on the command
+    /// line: `1.2` or `1.3`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "1.2" => Some(Self::Tls12),
+            "1.3" => Some(Self::Tls13),
+            _ => None,
+        }
+    }
+
+    /// The rustls protocol versions allowed from this minimum up.
+    fn protocol_versions(self) -> &'static [&'static SupportedProtocolVersion] {
+        match self {
+            Self::Tls12 => rustls::ALL_VERSIONS,
+            Self::Tls13 => {
+                const TLS13_ONLY: &[&SupportedProtocolVersion] = &[&rustls::version::TLS13];
+                TLS13_ONLY
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tls12 => f.write_str("TLS 1.2"),
+            Self::Tls13 => f.write_str("TLS 1.3"),
+        }
+    }
+}
+
+/// Permanent redirects followed while fetching, as `(from, to)`.
+pub type Moves = Vec<(Url, Url)>;
+
+/// Something the client has to report while fetching, passed to the handler
+/// given to `GeminiClientBuilder::on_event`. The client never prints; the
+/// caller decides what the user sees.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientEvent {
+    /// A diagnostic line, sent only in verbose mode.
+    Verbose(String),
+    /// Something went wrong that doesn't stop the request, such as failing
+    /// to save `known_hosts` or an unusable client identity.
+    Warning(String),
+    /// The server answered 44 SLOW DOWN; the same URL is requested again
+    /// after `delay`.
+    SlowDown { url: Url, delay: Duration },
+    /// The server answered with a 4x temporary failure; the URL is requested
+    /// again after `delay`, as retry `attempt` of `max_retries`.
+    Retry {
+        url: Url,
+        status: u8,
+        attempt: u32,
+        max_retries: u32,
+        delay: Duration,
+    },
+}
+
+impl std::fmt::Display for ClientEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Verbose(message) => write!(f, "[verbose] {message}"),
+            Self::Warning(message) => write!(f, "Warning: {message}"),
+            Self::SlowDown { delay, .. } => write!(
+                f,
+                "Server asked to slow down; retrying in {} seconds...",
+                delay.as_secs()
+            ),
+            Self::Retry {
+                status,
+                attempt,
+                max_retries,
+                delay,
+                ..
+            } => write!(
+                f,
+                "Temporary failure ({status}); retrying in {:.1} seconds ({attempt}/{max_retries})...",
+                delay.as_secs_f64()
+            ),
+        }
+    }
+}
+
+/// The caller's `ClientEvent` handler.
+#[derive(Clone)]
+pub(crate) struct EventHandler(Arc<dyn Fn(&ClientEvent) + Send + Sync>);
+
+impl EventHandler {
+    pub(crate) fn call(&self, event: &ClientEvent) {
+        (self.0)(event)
+    }
+}
+
+impl std::fmt::Debug for EventHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventHandler")
+    }
+}
+
+/// Decides whether a redirect from the first URL to the second, on another
+/// host, should be followed.
+type ConfirmFn = dyn Fn(&Url, &Url) -> bool + Send + Sync;
+
+/// The caller's `ConfirmFn` for cross-host redirects.
+#[derive(Clone)]
+struct RedirectConfirm(Arc<ConfirmFn>);
+
+impl std::fmt::Debug for RedirectConfirm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RedirectConfirm")
+    }
+}
+
+/// A response stream, buffered so the header can be read in chunks. Body
+/// bytes that arrive with the header stay in the buffer for `read_body`.
+type GeminiStream = BufReader<Box<dyn Read + Send>>;
+
+/// Carries requests to a server and responses back. `GeminiClient` uses
+/// `Network` unless given another transport, such as a recorded session.
+pub trait Transport: std::fmt::Debug + Send + Sync {
+    /// Send `request` (a complete request line) followed by `payload` to
+    /// `url`'s server, returning the raw response, header first.
+    fn send(
+        &self,
+        client: &GeminiClient,
+        url: &Url,
+        request: &str,
+        payload: &[u8],
+    ) -> Result<Connection, GeminiError>;
+}
+
+/// A request sent by a `Transport`, whose response is waiting to be read.
+pub struct Connection {
+    pub stream: Box<dyn Read + Send>,
+    /// The certificate the server presented, when it sent one.
+    pub certificate: Option<CertificateInfo>,
+    /// Connect and handshake timings; the client fills in the rest.
+    pub metrics: FetchMetrics,
+}
+
+/// The default transport: TCP and TLS to the URL's host.
+#[derive(Debug)]
+pub struct Network;
+
+impl Transport for Network {
+    fn send(
+        &self,
+        client: &GeminiClient,
+        url: &Url,
+        request: &str,
+        payload: &[u8],
+    ) -> Result<Connection, GeminiError> {
+        client.send_over_network(url, request, payload)
+    }
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum GeminiError {
@@ -26,14 +216,27 @@ pub enum GeminiError {
     #[error("TLS error: {0}")]
     TlsError(String),
 
+    #[error("certificate for {host} has changed (pinned {pinned}, presented {presented})")]
+    CertificateChanged {
+        host: String,
+        pinned: String,
+        presented: String,
+    },
+
     #[error("timeout connecting to server")]
     Timeout,
 
+    #[error("server stopped responding")]
+    ReadTimeout,
+
+    #[error("request cancelled")]
+    Cancelled,
+
     #[error("invalid response header: {0}")]
     InvalidResponse(String),
 
-    #[error("response body too large (exceeds 5 MB)")]
-    BodyTooLarge,
+    #[error("response body too large (exceeds {0} bytes)")]
+    BodyTooLarge(usize),
 
     #[error("too many redirects")]
     TooManyRedirects,
@@ -41,6 +244,12 @@ pub enum GeminiError {
     #[error("redirect loop detected")]
     RedirectLoop,
 
+    #[error("refusing redirect to non-gemini URL: {0}")]
+    CrossSchemeRedirect(String),
+
+    #[error("redirect from {from} leads to another host: {to}")]
+    CrossHostRedirect { from: String, to: String },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -50,59 +259,545 @@ pub struct GeminiResponse {
     pub status: u8,
     pub meta: String,
     pub body: Option<Vec<u8>>,
+    /// The certificate the server presented, when it sent one.
+    pub certificate: Option<CertificateInfo>,
+    /// How long each stage of the request took.
+    pub metrics: FetchMetrics,
 }
 
-/// A ServerCertVerifier that accepts all certificates (skip verification).
-#[derive(Debug)]
-struct NoVerifier;
+impl GeminiResponse {
+    /// The kind of response, or None for a status outside 10–69.
+    pub fn class(&self) -> Option<StatusClass> {
+        StatusClass::of(self.status)
+    }
 
-impl ServerCertVerifier for NoVerifier {
-    fn verify_server_cert(
-        &self,
-        _end_entity: &CertificateDer<'_>,
-        _intermediates: &[CertificateDer<'_>],
-        _server_name: &ServerName<'_>,
-        _ocsp_response: &[u8],
-        _now: UnixTime,
-    ) -> Result<ServerCertVerified, rustls::Error> {
-        Ok(ServerCertVerified::assertion())
+    /// 11 SENSITIVE INPUT: the answer shouldn't be echoed.
+    pub fn is_sensitive_input(&self) -> bool {
+        self.status == 11
     }
 
-    fn verify_tls12_signature(
-        &self,
-        _message: &[u8],
-        _cert: &CertificateDer<'_>,
-        _dss: &DigitallySignedStruct,
-    ) -> Result<HandshakeSignatureValid, rustls::Error> {
-        Ok(HandshakeSignatureValid::assertion())
+    /// 30 TEMPORARY REDIRECT, as opposed to 31 PERMANENT REDIRECT.
+    pub fn is_temporary_redirect(&self) -> bool {
+        self.status == 30
     }
 
-    fn verify_tls13_signature(
-        &self,
-        _message: &[u8],
-        _cert: &CertificateDer<'_>,
-        _dss: &DigitallySignedStruct,
-    ) -> Result<HandshakeSignatureValid, rustls::Error> {
-        Ok(HandshakeSignatureValid::assertion())
+    /// 31 PERMANENT REDIRECT: links to this URL should be updated.
+    pub fn is_permanent_redirect(&self) -> bool {
+        self.status == 31
+    }
+
+    /// 44 SLOW DOWN.
+    pub fn is_slow_down(&self) -> bool {
+        self.status == 44
+    }
+}
+
+/// The kind of response a status belongs to, given by its first digit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusClass {
+    /// 1x: the server wants a line of input.
+    Input,
+    /// 2x: the body follows.
+    Success,
+    /// 3x: the resource is at the URL in the meta.
+    Redirect,
+    /// 4x: failed, but may succeed later.
+    TemporaryFailure,
+    /// 5x: failed, and will keep failing.
+    PermanentFailure,
+    /// 6x: the server wants a client certificate.
+    ClientCertRequired,
+}
+
+impl StatusClass {
+    /// The class of `status`, or None for a status outside 10–69.
+    pub fn of(status: u8) -> Option<Self> {
+        match status {
+            10..=19 => Some(Self::Input),
+            20..=29 => Some(Self::Success),
+            30..=39 => Some(Self::Redirect),
+            40..=49 => Some(Self::TemporaryFailure),
+            50..=59 => Some(Self::PermanentFailure),
+            60..=69 => Some(Self::ClientCertRequired),
+            _ => None,
+        }
     }
+}
+
+/// Timings and size of a single request, in milliseconds and bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FetchMetrics {
+    /// DNS resolution and TCP connect.
+    pub connect_ms: u64,
+    /// TLS handshake.
+    pub tls_ms: u64,
+    /// Whether the handshake resumed an earlier session with the server.
+    pub resumed: bool,
+    /// From sending the request to receiving the response header.
+    pub ttfb_ms: u64,
+    /// The whole request, including reading the body.
+    pub total_ms: u64,
+    pub body_bytes: u64,
+}
+
+fn elapsed_ms(since: Instant) -> u64 {
+    since.elapsed().as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+/// A Gemini client holding connection timeouts and response limits.
+///
+/// Build one with `GeminiClient::builder()`; `GeminiClient::default()` uses
+/// 5-second timeouts, a 5 MB body limit, at most 5 redirect hops, and no
+/// retries of temporary failures.
+#[derive(Debug, Clone)]
+pub struct GeminiClient {
+    connect_timeout: Duration,
+    io_timeout: Duration,
+    max_body_size: usize,
+    max_redirects: usize,
+    max_retries: u32,
+    retry_delay: Duration,
+    confirm_cross_host_redirects: Option<RedirectConfirm>,
+    verbose: bool,
+    on_event: Option<EventHandler>,
+    min_tls_version: TlsVersion,
+    lenient_headers: bool,
+    /// Overrides the default trust-on-first-use verification when set.
+    verifier: Option<Arc<dyn ServerCertVerifier>>,
+    /// Certificates pinned by trust-on-first-use, shared by clones of the
+    /// client.
+    known_hosts: Arc<Mutex<KnownHosts>>,
+    /// Where client identities are looked up; None presents none.
+    identity_dir: Option<PathBuf>,
+    /// A `host:port` and the fingerprint its changed certificate may have
+    /// this time, set by `accepting_once`.
+    accept_once: Option<(String, String)>,
+    transport: Arc<dyn Transport>,
+    /// Least time between the start of two requests to one host.
+    request_interval: Duration,
+    /// When each host's latest request was allowed to start, shared by
+    /// clones of the client.
+    request_slots: Arc<Mutex<HashMap<String, Instant>>>,
+    /// Set (e.g. from a SIGINT handler) to abandon requests in progress.
+    cancel: Arc<AtomicBool>,
+    /// Shared by clones of the client. Reusing a config skips rebuilding it
+    /// and keeps its session cache, so later requests to a host resume the
+    /// first one's TLS session rather than doing a full handshake.
+    tls_configs: TlsConfigCache,
+}
+
+/// Builder for `GeminiClient`.
+#[derive(Debug, Clone)]
+pub struct GeminiClientBuilder {
+    client: GeminiClient,
+}
 
-    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
-        rustls::crypto::ring::default_provider()
-            .signature_verification_algorithms
-            .supported_schemes()
+impl Default for GeminiClient {
+    fn default() -> Self {
+        Self::builder().build()
     }
 }
 
-fn build_tls_config() -> Arc<ClientConfig> {
-    let config = ClientConfig::builder()
+impl GeminiClient {
+    pub fn builder() -> GeminiClientBuilder {
+        GeminiClientBuilder {
+            client: GeminiClient {
+                connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+                io_timeout: DEFAULT_IO_TIMEOUT,
+                max_body_size: DEFAULT_MAX_BODY_SIZE,
+                max_redirects: DEFAULT_MAX_REDIRECTS,
+                max_retries: DEFAULT_MAX_RETRIES,
+                retry_delay: DEFAULT_RETRY_DELAY,
+                confirm_cross_host_redirects: None,
+                verbose: false,
+                on_event: None,
+                min_tls_version: TlsVersion::default(),
+                lenient_headers: false,
+                verifier: None,
+                known_hosts: Arc::default(),
+                identity_dir: None,
+                accept_once: None,
+                transport: Arc::new(Network),
+                request_interval: Duration::ZERO,
+                request_slots: Arc::default(),
+                cancel: Arc::new(AtomicBool::new(false)),
+                tls_configs: TlsConfigCache::default(),
+            },
+        }
+    }
+
+    pub fn verbose(&self) -> bool {
+        self.verbose
+    }
+
+    /// Turn request/response logging on or off.
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    /// Forget the TLS configs built so far, so the next connection to each
+    /// host reloads its client certificate.
+    pub fn clear_tls_configs(&self) {
+        self.tls_configs().clear();
+    }
+
+    /// Pin `fingerprint` for `host` (given as `host:port`), replacing any
+    /// certificate pinned before, and save `known_hosts` if it is backed by
+    /// a file.
+    pub fn pin_certificate(&self, host: &str, fingerprint: &str) -> io::Result<()> {
+        self.known_hosts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pin(host, fingerprint)
+    }
+
+    /// A copy of the client that connects to `host` (given as `host:port`)
+    /// even though its certificate no longer matches the pin, as long as it
+    /// presents `fingerprint`. Nothing is saved to `known_hosts`, and the
+    /// copy builds its own TLS configs so the exception stays with it.
+    pub fn accepting_once(&self, host: &str, fingerprint: &str) -> Self {
+        Self {
+            accept_once: Some((host.to_string(), fingerprint.to_string())),
+            tls_configs: TlsConfigCache::default(),
+            ..self.clone()
+        }
+    }
+
+    fn tls_configs(&self) -> MutexGuard<'_, HashMap<(String, u16), Arc<ClientConfig>>> {
+        self.tls_configs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The TLS config for `host:port`, built on first use and then reused.
+    fn tls_config(&self, host: &str, port: u16) -> Result<Arc<ClientConfig>, GeminiError> {
+        let key = (host.to_string(), port);
+        if let Some(config) = self.tls_configs().get(&key) {
+            return Ok(config.clone());
+        }
+        let verifier = self.verifier.clone().unwrap_or_else(|| {
+            let on_event = self.on_event.clone();
+            let tofu = TofuVerifier::new(self.known_hosts.clone(), port).on_save_error(Arc::new(
+                move |_, e| {
+                    if let Some(handler) = &on_event {
+                        handler.call(&ClientEvent::Warning(format!(
+                            "could not save known_hosts: {e}"
+                        )));
+                    }
+                },
+            ));
+            Arc::new(match &self.accept_once {
+                Some((key, fingerprint)) if *key == format!("{host}:{port}") => {
+                    tofu.accepting(fingerprint)
+                }
+                _ => tofu,
+            })
+        });
+        let identity = match &self.identity_dir {
+            Some(dir) => identity::load(dir, host).unwrap_or_else(|e| {
+                self.emit(ClientEvent::Warning(format!(
+                    "ignoring identity for {host}: {e}"
+                )));
+                None
+            }),
+            None => None,
+        };
+        let config = build_tls_config(verifier, identity, self.min_tls_version)?;
+        self.tls_configs().insert(key, config.clone());
+        Ok(config)
+    }
+
+    /// Claim the next request slot for `host`, at least `request_interval`
+    /// after the last one, and return how long it is until then.
+    fn reserve_request_slot(&self, host: &str) -> Duration {
+        if self.request_interval.is_zero() {
+            return Duration::ZERO;
+        }
+        let now = Instant::now();
+        let mut slots = self
+            .request_slots
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let slot = match slots.get(host) {
+            Some(&last) => (last + self.request_interval).max(now),
+            None => now,
+        };
+        slots.insert(host.to_string(), slot);
+        slot - now
+    }
+
+    /// Fail with `Cancelled` if the cancel flag has been raised.
+    fn check_cancelled(&self) -> Result<(), GeminiError> {
+        check_cancelled(&self.cancel)
+    }
+
+    /// Wait for `delay`, returning early with `Cancelled` if the cancel flag
+    /// is raised meanwhile.
+    fn wait(&self, delay: Duration) -> Result<(), GeminiError> {
+        let until = Instant::now() + delay;
+        loop {
+            self.check_cancelled()?;
+            let left = until.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Ok(());
+            }
+            thread::sleep(left.min(CANCEL_POLL));
+        }
+    }
+
+    /// Pass `event` to the caller's handler, if there is one.
+    fn emit(&self, event: ClientEvent) {
+        if let Some(handler) = &self.on_event {
+            handler.call(&event);
+        }
+    }
+
+    /// The caller's event handler, for parts of the crate that report
+    /// events after the client call has returned.
+    pub(crate) fn event_handler(&self) -> Option<EventHandler> {
+        self.on_event.clone()
+    }
+
+    /// Report a diagnostic line as `ClientEvent::Verbose` in verbose mode.
+    fn log(&self, message: impl std::fmt::Display) {
+        if self.verbose {
+            self.emit(ClientEvent::Verbose(message.to_string()));
+        }
+    }
+}
+
+impl GeminiClientBuilder {
+    /// Maximum time to wait for the TCP connection to be established.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.client.connect_timeout = timeout;
+        self
+    }
+
+    /// Read/write timeout applied to the socket once connected.
+    pub fn io_timeout(mut self, timeout: Duration) -> Self {
+        self.client.io_timeout = timeout;
+        self
+    }
+
+    /// Largest response body accepted by `fetch`, in bytes.
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.client.max_body_size = bytes;
+        self
+    }
+
+    /// Maximum number of URLs visited while following a redirect chain.
+    pub fn max_redirects(mut self, hops: usize) -> Self {
+        self.client.max_redirects = hops;
+        self
+    }
+
+    /// How many times a 4x temporary failure (other than 44, which has its
+    /// own delay) is retried before the response is returned. None by
+    /// default.
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.client.max_retries = retries;
+        self
+    }
+
+    /// Wait before the first retry; each later retry waits twice as long.
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.client.retry_delay = delay;
+        self
+    }
+
+    /// Ask `confirm` before following a redirect to a different host,
+    /// instead of following it silently. It is given the redirecting URL
+    /// and the target; when it returns false the fetch stops with
+    /// `GeminiError::CrossHostRedirect`.
+    pub fn confirm_cross_host_redirects(
+        mut self,
+        confirm: impl Fn(&Url, &Url) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.client.confirm_cross_host_redirects = Some(RedirectConfirm(Arc::new(confirm)));
+        self
+    }
+
+    /// Call `handler` with each `ClientEvent`, such as a retry about to
+    /// wait. Without one, events pass silently.
+    pub fn on_event(mut self, handler: impl Fn(&ClientEvent) + Send + Sync + 'static) -> Self {
+        self.client.on_event = Some(EventHandler(Arc::new(handler)));
+        self
+    }
+
+    /// Log each request line, response header, and body size, as
+    /// `ClientEvent::Verbose` events.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.client.verbose = verbose;
+        self
+    }
+
+    /// Refuse to negotiate TLS versions older than `version`. Servers that
+    /// only speak older versions fail with `GeminiError::TlsError`.
+    pub fn min_tls_version(mut self, version: TlsVersion) -> Self {
+        self.client.min_tls_version = version;
+        self
+    }
+
+    /// Accept response headers missing the space after the status, such
+    /// as `20text/gemini`, which some buggy servers send.
+    pub fn lenient_headers(mut self, lenient: bool) -> Self {
+        self.client.lenient_headers = lenient;
+        self
+    }
+
+    /// Verify server certificates with `verifier` instead of pinning them
+    /// in `known_hosts`.
+    ///
+    /// Without one, pins live in memory for the client's lifetime unless
+    /// `known_hosts` gives a set backed by a file.
+    pub fn cert_verifier(mut self, verifier: Arc<dyn ServerCertVerifier>) -> Self {
+        self.client.verifier = Some(verifier);
+        self
+    }
+
+    /// Wait so that requests to the same host start at least `interval`
+    /// apart. Zero, the default, sends them right away.
+    pub fn request_interval(mut self, interval: Duration) -> Self {
+        self.client.request_interval = interval;
+        self
+    }
+
+    /// Pin certificates in `known_hosts`, such as
+    /// `KnownHosts::load_default()`, so they are kept between runs.
+    pub fn known_hosts(mut self, known_hosts: KnownHosts) -> Self {
+        self.client.known_hosts = Arc::new(Mutex::new(known_hosts));
+        self
+    }
+
+    /// Present the identity installed in `dir` (see `identity::default_dir`)
+    /// for each host that has one. Without it no client certificate is
+    /// sent.
+    pub fn identity_dir(mut self, dir: PathBuf) -> Self {
+        self.client.identity_dir = Some(dir);
+        self
+    }
+
+    /// Send requests through `transport` instead of the network.
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.client.transport = transport;
+        self
+    }
+
+    /// Abandon requests with `GeminiError::Cancelled` once `flag` is set.
+    /// It is checked between reads, so a request stuck in a single read
+    /// still waits out the I/O timeout; the caller clears the flag.
+    pub fn cancel_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.client.cancel = flag;
+        self
+    }
+
+    pub fn build(self) -> GeminiClient {
+        self.client
+    }
+}
+
+/// Build a TLS config that checks server certificates with `verifier`,
+/// presenting `identity` as the client certificate when one is given and
+/// negotiating `min_version` or newer.
+fn build_tls_config(
+    verifier: Arc<dyn ServerCertVerifier>,
+    identity: Option<ClientIdentity>,
+    min_version: TlsVersion,
+) -> Result<Arc<ClientConfig>, GeminiError> {
+    let builder = ClientConfig::builder_with_protocol_versions(min_version.protocol_versions())
         .dangerous()
-        .with_custom_certificate_verifier(Arc::new(NoVerifier))
-        .with_no_client_auth();
-    Arc::new(config)
+        .with_custom_certificate_verifier(verifier);
+    let config = match identity {
+        Some(identity) => builder
+            .with_client_auth_cert(vec![identity.cert], identity.key)
+            .map_err(|e| GeminiError::TlsError(e.to_string()))?,
+        None => builder.with_no_client_auth(),
+    };
+    Ok(Arc::new(config))
+}
+
+/// Map a failed TLS handshake to a GeminiError, recovering TOFU mismatches
+/// and giving out-of-date certificates and version mismatches a readable
+/// message.
+fn handshake_error(e: io::Error, min_version: TlsVersion) -> GeminiError {
+    let tls_error = e
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<rustls::Error>());
+    if let Some(
+        rustls::Error::PeerIncompatible(
+            PeerIncompatible::ServerDoesNotSupportTls12Or13
+            | PeerIncompatible::ServerTlsVersionIsDisabledByOurConfig
+            | PeerIncompatible::SupportedVersionsExtensionRequired
+            | PeerIncompatible::Tls12NotOffered
+            | PeerIncompatible::Tls12NotOfferedOrEnabled,
+        )
+        | rustls::Error::AlertReceived(AlertDescription::ProtocolVersion),
+    ) = tls_error
+    {
+        return GeminiError::TlsError(format!("server does not support {min_version} or newer"));
+    }
+    if let Some(rustls::Error::InvalidCertificate(CertificateError::Other(other))) = tls_error {
+        if let Some(mismatch) = other.0.downcast_ref::<CertificateMismatch>() {
+            return GeminiError::CertificateChanged {
+                host: mismatch.host.clone(),
+                pinned: mismatch.pinned.clone(),
+                presented: mismatch.presented.clone(),
+            };
+        }
+        if let Some(out_of_date) = other.0.downcast_ref::<CertificateOutOfDate>() {
+            return GeminiError::TlsError(out_of_date.to_string());
+        }
+    }
+    if is_timeout(&e) {
+        return GeminiError::ReadTimeout;
+    }
+    GeminiError::TlsError(e.to_string())
+}
+
+/// Whether a socket error means the read/write timeout expired. Depending
+/// on the platform this shows up as either `WouldBlock` or `TimedOut`.
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// Whether a read error means the server has gone away. Gemini ends a body
+/// by closing the connection, and servers that do so abruptly show up as
+/// an abort, a reset, or a TLS stream cut off without `close_notify`, so
+/// what was read before it is the whole body.
+fn is_closed(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::UnexpectedEof
+    )
+}
+
+/// Map an error reading from or writing to the server, reporting an
+/// expired timeout as `ReadTimeout`.
+fn stream_error(e: io::Error) -> GeminiError {
+    if is_timeout(&e) {
+        GeminiError::ReadTimeout
+    } else {
+        GeminiError::Io(e)
+    }
+}
+
+fn check_cancelled(cancel: &AtomicBool) -> Result<(), GeminiError> {
+    if cancel.load(Ordering::Relaxed) {
+        Err(GeminiError::Cancelled)
+    } else {
+        Ok(())
+    }
 }
 
 /// Parse a response header line (without the trailing \r\n) into (status, meta).
-pub fn parse_response_header(line: &str) -> Result<(u8, String), GeminiError> {
+/// When `lenient` is set, the text right after the status digits is taken
+/// as the meta even without a separating space, as in `20text/gemini`.
+pub fn parse_response_header(line: &str, lenient: bool) -> Result<(u8, String), GeminiError> {
     let line = line.trim_end_matches('\n').trim_end_matches('\r');
 
     if line.len() < 2 {
@@ -134,6 +829,8 @@ pub fn parse_response_header(line: &str) -> Result<(u8, String), GeminiError> {
         let rest = &line[2..];
         if let Some(stripped) = rest.strip_prefix(' ') {
             stripped.to_string()
+        } else if lenient {
+            rest.to_string()
         } else {
             // Characters after status but no space — treat as no meta
             return Err(GeminiError::InvalidResponse(
@@ -144,150 +841,523 @@ pub fn parse_response_header(line: &str) -> Result<(u8, String), GeminiError> {
         String::new()
     };
 
+    if meta.len() > MAX_META_LEN {
+        return Err(GeminiError::InvalidResponse("meta too long".to_string()));
+    }
+
     Ok((status, meta))
 }
 
-/// Read the response header from a TLS stream, returning the raw header line.
-fn read_header(
-    stream: &mut StreamOwned<ClientConnection, TcpStream>,
-) -> Result<String, GeminiError> {
+/// Read the response header, returning the raw header line without its
+/// CRLF. Reads whole buffered chunks and consumes only up to the line end,
+/// leaving any body bytes in `stream`. Stops with `Cancelled` once
+/// `cancel` is set.
+fn read_header(stream: &mut impl BufRead, cancel: &AtomicBool) -> Result<String, GeminiError> {
     let mut buf = Vec::with_capacity(MAX_HEADER_LEN);
-    let mut byte = [0u8; 1];
 
     loop {
-        match stream.read(&mut byte) {
-            Ok(0) => {
-                if buf.is_empty() {
-                    return Err(GeminiError::InvalidResponse("empty response".to_string()));
-                }
-                // EOF before \r\n — return what we have
-                break;
-            }
-            Ok(_) => {
-                buf.push(byte[0]);
-                if buf.len() >= 2 && buf[buf.len() - 2] == b'\r' && buf[buf.len() - 1] == b'\n' {
-                    // Found \r\n, remove them
-                    buf.truncate(buf.len() - 2);
-                    break;
-                }
-                if buf.len() > MAX_HEADER_LEN {
-                    return Err(GeminiError::InvalidResponse(
-                        "header line too long".to_string(),
-                    ));
-                }
+        check_cancelled(cancel)?;
+        let chunk = stream.fill_buf().map_err(stream_error)?;
+        if chunk.is_empty() {
+            if buf.is_empty() {
+                return Err(GeminiError::InvalidResponse("empty response".to_string()));
             }
-            Err(e) => return Err(GeminiError::Io(e)),
+            // EOF before \r\n — return what we have
+            break;
+        }
+
+        let (take, found_newline) = match chunk.iter().position(|&b| b == b'\n') {
+            Some(i) => (i + 1, true),
+            None => (chunk.len(), false),
+        };
+        buf.extend_from_slice(&chunk[..take]);
+        stream.consume(take);
+
+        if found_newline && buf.ends_with(b"\r\n") {
+            // Found \r\n, remove them
+            buf.truncate(buf.len() - 2);
+            break;
+        }
+        // Allow for a trailing \r whose \n is still to come
+        if buf.len() > MAX_HEADER_LEN + 1 {
+            return Err(GeminiError::InvalidResponse(
+                "header line too long".to_string(),
+            ));
         }
     }
 
+    if buf.len() > MAX_HEADER_LEN {
+        return Err(GeminiError::InvalidResponse(
+            "header line too long".to_string(),
+        ));
+    }
+
     String::from_utf8(buf)
         .map_err(|_| GeminiError::InvalidResponse("header is not valid UTF-8".to_string()))
 }
 
 /// Read the response body with a size limit.
 fn read_body(
-    stream: &mut StreamOwned<ClientConnection, TcpStream>,
+    stream: &mut impl Read,
+    max_body_size: usize,
+    cancel: &AtomicBool,
 ) -> Result<Vec<u8>, GeminiError> {
     let mut body = Vec::new();
     let mut total = 0usize;
     let mut buf = [0u8; 8192];
 
     loop {
+        check_cancelled(cancel)?;
         match stream.read(&mut buf) {
             Ok(0) => break,
             Ok(n) => {
                 total += n;
-                if total > MAX_BODY_SIZE {
-                    return Err(GeminiError::BodyTooLarge);
+                if total > max_body_size {
+                    return Err(GeminiError::BodyTooLarge(max_body_size));
                 }
                 body.extend_from_slice(&buf[..n]);
             }
-            Err(e) if e.kind() == io::ErrorKind::ConnectionAborted => break,
-            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
-            Err(e) => return Err(GeminiError::Io(e)),
+            Err(e) if is_closed(&e) => break,
+            Err(e) => return Err(stream_error(e)),
         }
     }
 
     Ok(body)
 }
 
-/// Fetch a Gemini page. Handles the full request/response cycle for a single URL.
-/// Does NOT follow redirects — the caller handles redirect logic.
-pub fn fetch(url: &Url) -> Result<GeminiResponse, GeminiError> {
-    let host = url
-        .host_str()
-        .ok_or_else(|| GeminiError::InvalidUrl("missing host".to_string()))?;
-    let port = url.port().unwrap_or(1965);
+/// Copy the response body into `writer` without a size limit.
+/// Returns the number of bytes written.
+fn copy_body(
+    stream: &mut impl Read,
+    writer: &mut dyn Write,
+    cancel: &AtomicBool,
+) -> Result<u64, GeminiError> {
+    let mut total = 0u64;
+    let mut buf = [0u8; 8192];
 
-    let addr = format!("{host}:{port}");
+    loop {
+        check_cancelled(cancel)?;
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                writer.write_all(&buf[..n])?;
+                total += n as u64;
+            }
+            Err(e) if is_closed(&e) => break,
+            Err(e) => return Err(stream_error(e)),
+        }
+    }
 
-    // DNS resolve and connect with timeout
-    let sock_addrs: Vec<std::net::SocketAddr> = addr
-        .to_socket_addrs()
-        .map_err(|e| GeminiError::ConnectionFailed(e.to_string()))?
-        .collect();
+    writer.flush()?;
+    Ok(total)
+}
 
-    if sock_addrs.is_empty() {
-        return Err(GeminiError::ConnectionFailed(
-            "could not resolve host".to_string(),
+impl GeminiClient {
+    /// Connect to `url`'s host over TLS and send `request` followed by
+    /// `payload`.
+    fn send_over_network(
+        &self,
+        url: &Url,
+        request: &str,
+        payload: &[u8],
+    ) -> Result<Connection, GeminiError> {
+        let started = Instant::now();
+        let mut metrics = FetchMetrics::default();
+        let host = url
+            .host_str()
+            .ok_or_else(|| GeminiError::InvalidUrl("missing host".to_string()))?;
+        let port = url.port().unwrap_or(1965);
+
+        let addr = format!("{host}:{port}");
+
+        // DNS resolve and connect with timeout
+        let sock_addrs: Vec<SocketAddr> = addr
+            .to_socket_addrs()
+            .map_err(|e| GeminiError::ConnectionFailed(e.to_string()))?
+            .collect();
+
+        if sock_addrs.is_empty() {
+            return Err(GeminiError::ConnectionFailed(
+                "could not resolve host".to_string(),
+            ));
+        }
+
+        let mut tcp = connect_with_timeout(&sock_addrs, self.connect_timeout)?;
+        tcp.set_read_timeout(Some(self.io_timeout))?;
+        tcp.set_write_timeout(Some(self.io_timeout))?;
+        metrics.connect_ms = elapsed_ms(started);
+
+        // TLS handshake, driven explicitly so certificate errors surface here
+        let handshake_started = Instant::now();
+        let tls_config = self.tls_config(host, port)?;
+        let server_name = ServerName::try_from(host.to_string())
+            .map_err(|e| GeminiError::TlsError(e.to_string()))?;
+        let mut conn = ClientConnection::new(tls_config, server_name)
+            .map_err(|e| GeminiError::TlsError(e.to_string()))?;
+        while conn.is_handshaking() {
+            self.check_cancelled()?;
+            if let Err(e) = conn.complete_io(&mut tcp) {
+                // Rebuild next time rather than reuse a config that failed
+                self.tls_configs().remove(&(host.to_string(), port));
+                return Err(handshake_error(e, self.min_tls_version));
+            }
+        }
+        metrics.resumed = conn.handshake_kind() == Some(HandshakeKind::Resumed);
+        let certificate = conn
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .map(|cert| CertificateInfo::from_der(cert.as_ref()));
+        let mut tls_stream = StreamOwned::new(conn, tcp);
+        metrics.tls_ms = elapsed_ms(handshake_started);
+
+        tls_stream
+            .write_all(request.as_bytes())
+            .and_then(|()| tls_stream.write_all(payload))
+            .map_err(|e| {
+                if is_timeout(&e) {
+                    GeminiError::ReadTimeout
+                } else {
+                    GeminiError::ConnectionFailed(e.to_string())
+                }
+            })?;
+        Ok(Connection {
+            stream: Box::new(tls_stream),
+            certificate,
+            metrics,
+        })
+    }
+
+    /// Send `request` (a complete request line) followed by `payload` for
+    /// `url` through the transport, and read the response header. Returns
+    /// the stream positioned at the start of the body, the response without
+    /// its body, and when the request started (for `FetchMetrics::total_ms`).
+    fn open(
+        &self,
+        url: &Url,
+        request: &str,
+        payload: &[u8],
+    ) -> Result<(GeminiStream, GeminiResponse, Instant), GeminiError> {
+        if let Some(host) = url.host_str() {
+            let delay = self.reserve_request_slot(host);
+            if !delay.is_zero() {
+                self.log(format_args!(
+                    "waiting {}ms before requesting from {host}",
+                    delay.as_millis()
+                ));
+                self.wait(delay)?;
+            }
+        }
+        let started = Instant::now();
+        self.log(format_args!("> {request:?}"));
+        if !payload.is_empty() {
+            self.log(format_args!("> {} payload bytes", payload.len()));
+        }
+        let Connection {
+            stream,
+            certificate,
+            mut metrics,
+        } = self.transport.send(self, url, request, payload)?;
+        let mut stream = BufReader::new(stream);
+
+        // Read and parse header
+        let header_line = read_header(&mut stream, &self.cancel)?;
+        // Everything after the handshake: sending the request and waiting
+        metrics.ttfb_ms = elapsed_ms(started).saturating_sub(metrics.connect_ms + metrics.tls_ms);
+        self.log(format_args!("< {header_line:?}"));
+        let (status, meta) = parse_response_header(&header_line, self.lenient_headers)?;
+        self.log(format_args!("status {status}, meta {meta:?}"));
+        let class = StatusClass::of(status);
+        if class == Some(StatusClass::Success) {
+            self.log(format_args!("mime {}", mime::parse(&meta).essence));
+        }
+
+        // Validate that 1x and 3x have non-empty meta
+        if matches!(class, Some(StatusClass::Input | StatusClass::Redirect)) && meta.is_empty() {
+            return Err(GeminiError::InvalidResponse("missing meta".to_string()));
+        }
+
+        let response = GeminiResponse {
+            status,
+            meta,
+            body: None,
+            certificate,
+            metrics,
+        };
+        Ok((stream, response, started))
+    }
+
+    /// Fill in the total time and body size once the body has been read.
+    fn finish_metrics(&self, metrics: &mut FetchMetrics, started: Instant, body_bytes: u64) {
+        metrics.total_ms = elapsed_ms(started);
+        metrics.body_bytes = body_bytes;
+        self.log(format_args!(
+            "timing: connect {}ms, tls {}ms{}, ttfb {}ms, total {}ms, body {} bytes",
+            metrics.connect_ms,
+            metrics.tls_ms,
+            if metrics.resumed { " (resumed)" } else { "" },
+            metrics.ttfb_ms,
+            metrics.total_ms,
+            body_bytes
         ));
     }
 
-    let tcp = connect_with_timeout(&sock_addrs, CONNECT_TIMEOUT)?;
-    tcp.set_read_timeout(Some(IO_TIMEOUT))?;
-    tcp.set_write_timeout(Some(IO_TIMEOUT))?;
+    /// Fetch a Gemini page. Handles the full request/response cycle for a single URL.
+    /// Does NOT follow redirects — the caller handles redirect logic.
+    pub fn fetch(&self, url: &Url) -> Result<GeminiResponse, GeminiError> {
+        self.exchange(url, &request_line(url), &[])
+    }
 
-    // TLS handshake
-    let tls_config = build_tls_config();
-    let server_name =
-        ServerName::try_from(host.to_string()).map_err(|e| GeminiError::TlsError(e.to_string()))?;
-    let conn = ClientConnection::new(tls_config, server_name)
-        .map_err(|e| GeminiError::TlsError(e.to_string()))?;
-    let mut tls_stream = StreamOwned::new(conn, tcp);
+    /// Send `request` and `payload` to `url`'s host and read the response,
+    /// including the body of a 2x response. Shared by `fetch` and Titan
+    /// uploads.
+    pub(crate) fn exchange(
+        &self,
+        url: &Url,
+        request: &str,
+        payload: &[u8],
+    ) -> Result<GeminiResponse, GeminiError> {
+        let (mut stream, mut response, started) = self.open(url, request, payload)?;
+
+        // Read body for 2x responses only
+        if response.class() == Some(StatusClass::Success) {
+            response.body = Some(read_body(&mut stream, self.max_body_size, &self.cancel)?);
+        }
+        let body_bytes = response.body.as_ref().map_or(0, |body| body.len() as u64);
+        self.finish_metrics(&mut response.metrics, started, body_bytes);
+        Ok(response)
+    }
 
-    // Send request
-    let request = format!("{url}\r\n");
-    tls_stream
-        .write_all(request.as_bytes())
-        .map_err(|e| GeminiError::ConnectionFailed(e.to_string()))?;
+    /// Navigate to a URL, following redirects. Returns the final response and the final URL.
+    pub fn fetch_with_redirects(
+        &self,
+        start_url: &Url,
+    ) -> Result<(GeminiResponse, Url), GeminiError> {
+        let (response, final_url, _) = self.fetch_with_moves(start_url)?;
+        Ok((response, final_url))
+    }
 
-    // Read and parse header
-    let header_line = read_header(&mut tls_stream)?;
-    let (status, meta) = parse_response_header(&header_line)?;
+    /// Like `fetch_with_redirects`, also returning each permanent (31)
+    /// redirect followed on the way, as `(from, to)`.
+    pub fn fetch_with_moves(
+        &self,
+        start_url: &Url,
+    ) -> Result<(GeminiResponse, Url, Moves), GeminiError> {
+        let mut moved = Vec::new();
+        let (response, final_url, ()) =
+            self.follow_redirects(start_url, &mut moved, |url| Ok((self.fetch(url)?, ())))?;
+        Ok((response, final_url, moved))
+    }
 
-    // Validate that 1x and 3x have non-empty meta
-    let first_digit = status / 10;
-    if (first_digit == 1 || first_digit == 3) && meta.is_empty() {
-        return Err(GeminiError::InvalidResponse("missing meta".to_string()));
+    /// Fetch a URL, following redirects, and stream a 2x body into `writer`
+    /// in 8 KB chunks. Unlike `fetch`, no body size limit is applied.
+    ///
+    /// Returns the final response (with `body` left as None) and the number
+    /// of body bytes written. Nothing is written for non-2x responses, and a
+    /// 2x response whose meta isn't a valid MIME type is an error.
+    pub fn fetch_to_writer(
+        &self,
+        url: &Url,
+        writer: &mut dyn Write,
+    ) -> Result<(GeminiResponse, u64), GeminiError> {
+        let (response, _, written) = self.follow_redirects(url, &mut Vec::new(), |url| {
+            let (mut stream, mut response, started) = self.open(url, &request_line(url), &[])?;
+            let written = if response.class() == Some(StatusClass::Success) {
+                if !mime::parse(&response.meta).is_valid() {
+                    return Err(GeminiError::InvalidResponse(format!(
+                        "invalid MIME type: {}",
+                        response.meta
+                    )));
+                }
+                copy_body(&mut stream, writer, &self.cancel)?
+            } else {
+                0
+            };
+            self.finish_metrics(&mut response.metrics, started, written);
+            Ok((response, written))
+        })?;
+        Ok((response, written))
     }
 
-    // Read body for 2x responses only
-    let body = if first_digit == 2 {
-        Some(read_body(&mut tls_stream)?)
-    } else {
-        None
-    };
+    /// Call `fetch_one` for `start_url` and each 3x redirect target in turn,
+    /// enforcing the hop limit and loop detection. The first 44 (SLOW DOWN)
+    /// with a valid delay is waited out and the same URL retried once; other
+    /// 4x responses are retried up to `max_retries` times with exponential
+    /// backoff. Permanent redirects are added to `moved` as `(from, to)`.
+    fn follow_redirects<T>(
+        &self,
+        start_url: &Url,
+        moved: &mut Moves,
+        mut fetch_one: impl FnMut(&Url) -> Result<(GeminiResponse, T), GeminiError>,
+    ) -> Result<(GeminiResponse, Url, T), GeminiError> {
+        let mut current_url = start_url.clone();
+        // Compared canonicalized, so a loop can't hide behind spelling changes
+        let mut visited: Vec<String> = vec![url_utils::canonicalize(&current_url).to_string()];
+        let mut retried = false;
+        let mut retries = 0;
+
+        loop {
+            self.check_cancelled()?;
+            let (response, extra) = fetch_one(&current_url)?;
+
+            if response.is_slow_down() && !retried {
+                if let Some(delay) = slow_down_delay(&response.meta) {
+                    self.emit(ClientEvent::SlowDown {
+                        url: current_url.clone(),
+                        delay,
+                    });
+                    self.wait(delay)?;
+                    retried = true;
+                    continue;
+                }
+            }
+
+            let class = response.class();
+            if class == Some(StatusClass::TemporaryFailure)
+                && !response.is_slow_down()
+                && retries < self.max_retries
+            {
+                let delay = self.retry_delay * 2u32.pow(retries);
+                retries += 1;
+                self.emit(ClientEvent::Retry {
+                    url: current_url.clone(),
+                    status: response.status,
+                    attempt: retries,
+                    max_retries: self.max_retries,
+                    delay,
+                });
+                self.wait(delay)?;
+                continue;
+            }
 
-    Ok(GeminiResponse { status, meta, body })
+            if class == Some(StatusClass::Redirect) {
+                let target = resolve_redirect(&current_url, &response.meta)?;
+                self.log(format_args!(
+                    "{} redirect to {target}",
+                    if response.is_temporary_redirect() {
+                        "temporary"
+                    } else {
+                        "permanent"
+                    }
+                ));
+                let canonical = url_utils::canonicalize(&target).to_string();
+                check_redirect(&visited, &canonical, self.max_redirects)?;
+                if let Some(confirm) = &self.confirm_cross_host_redirects {
+                    if !same_host(&current_url, &target) && !(confirm.0)(&current_url, &target) {
+                        return Err(GeminiError::CrossHostRedirect {
+                            from: current_url.to_string(),
+                            to: target.to_string(),
+                        });
+                    }
+                }
+                visited.push(canonical);
+                if response.is_permanent_redirect() {
+                    moved.push((current_url, target.clone()));
+                }
+                current_url = target;
+                retries = 0;
+            } else {
+                return Ok((response, current_url, extra));
+            }
+        }
+    }
+}
+
+/// The request line for `url`: the URL and CRLF. A fragment only means
+/// something to the client, and the spec forbids sending one, so it is
+/// left off; callers keep the original URL for display and history.
+pub fn request_line(url: &Url) -> String {
+    let mut target = url.clone();
+    target.set_fragment(None);
+    format!("{target}\r\n")
+}
+
+/// Parse the meta of a 44 (SLOW DOWN) response as the number of seconds to
+/// wait, capped at two minutes. Returns None if it isn't a number.
+pub fn slow_down_delay(meta: &str) -> Option<Duration> {
+    let seconds: u64 = meta.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds).min(MAX_SLOW_DOWN_WAIT))
+}
+
+/// Fetch a single URL with the default client. See `GeminiClient::fetch`.
+pub fn fetch(url: &Url) -> Result<GeminiResponse, GeminiError> {
+    GeminiClient::default().fetch(url)
 }
 
-/// Try connecting to any of the resolved addresses with a timeout.
-fn connect_with_timeout(
-    addrs: &[std::net::SocketAddr],
-    timeout: Duration,
+/// Fetch a URL with the default client, following redirects.
+/// See `GeminiClient::fetch_with_redirects`.
+pub fn fetch_with_redirects(start_url: &Url) -> Result<(GeminiResponse, Url), GeminiError> {
+    GeminiClient::default().fetch_with_redirects(start_url)
+}
+
+/// Connect to the first reachable address within `timeout`, happy-eyeballs
+/// style: IPv6 and IPv4 addresses are tried on separate threads at the same
+/// time, each family falling back through its remaining addresses, and the
+/// first connection to succeed wins.
+fn connect_with_timeout(addrs: &[SocketAddr], timeout: Duration) -> Result<TcpStream, GeminiError> {
+    let deadline = Instant::now() + timeout;
+    let (v6, v4): (Vec<SocketAddr>, Vec<SocketAddr>) = addrs.iter().partition(|a| a.is_ipv6());
+    let families: Vec<Vec<SocketAddr>> = [v6, v4]
+        .into_iter()
+        .filter(|family| !family.is_empty())
+        .collect();
+    if families.is_empty() {
+        return Err(GeminiError::ConnectionFailed(
+            "no addresses to connect to".to_string(),
+        ));
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let attempts = families.len();
+    for family in families {
+        let tx = tx.clone();
+        // A losing connection is dropped when the send fails
+        thread::spawn(move || tx.send(connect_in_order(&family, deadline)));
+    }
+    drop(tx);
+
+    let mut last_err = None;
+    for _ in 0..attempts {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(remaining) {
+            Ok(Ok(stream)) => return Ok(stream),
+            Ok(Err(e)) => last_err = Some(e),
+            Err(RecvTimeoutError::Timeout) => return Err(GeminiError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    Err(last_err.unwrap_or(GeminiError::Timeout))
+}
+
+/// Try `addrs` one after another until one connects or `deadline` passes.
+fn connect_in_order(addrs: &[SocketAddr], deadline: Instant) -> Result<TcpStream, GeminiError> {
+    connect_in_order_with(addrs, deadline, TcpStream::connect_timeout)
+}
+
+/// `connect_in_order` with the connection attempt supplied by `connect`. An
+/// address that times out is passed over like any other failure; Timeout is
+/// only reported once the deadline passes or no address is left and at
+/// least one of them timed out.
+fn connect_in_order_with(
+    addrs: &[SocketAddr],
+    deadline: Instant,
+    connect: impl Fn(&SocketAddr, Duration) -> io::Result<TcpStream>,
 ) -> Result<TcpStream, GeminiError> {
     let mut last_err = None;
+    let mut timed_out = false;
     for addr in addrs {
-        match TcpStream::connect_timeout(addr, timeout) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(GeminiError::Timeout);
+        }
+        match connect(addr, remaining) {
             Ok(stream) => return Ok(stream),
-            Err(e) => {
-                if e.kind() == io::ErrorKind::TimedOut {
-                    return Err(GeminiError::Timeout);
-                }
-                last_err = Some(e);
-            }
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => timed_out = true,
+            Err(e) => last_err = Some(e),
         }
     }
+    if timed_out {
+        return Err(GeminiError::Timeout);
+    }
     Err(GeminiError::ConnectionFailed(
         last_err
             .map(|e| e.to_string())
@@ -297,6 +1367,28 @@ fn connect_with_timeout(
 
 use std::net::ToSocketAddrs;
 
+/// Resolve a 3x meta against the URL that returned it. Targets with any
+/// scheme other than `gemini` are rejected with `CrossSchemeRedirect`.
+pub fn resolve_redirect(base: &Url, meta: &str) -> Result<Url, GeminiError> {
+    let target = meta.trim();
+    let joined = base
+        .join(target)
+        .map_err(|e| GeminiError::InvalidUrl(format!("cannot resolve {target}: {e}")))?;
+    if joined.scheme() != "gemini" {
+        return Err(GeminiError::CrossSchemeRedirect(joined.to_string()));
+    }
+    url_utils::resolve_url(base, target)
+}
+
+/// Whether two URLs name the same server (host and port).
+pub fn same_host(a: &Url, b: &Url) -> bool {
+    let host_matches = match (a.host_str(), b.host_str()) {
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        _ => false,
+    };
+    host_matches && a.port().unwrap_or(1965) == b.port().unwrap_or(1965)
+}
+
 /// Check for redirect loops and max hops.
 pub fn check_redirect(
     visited: &[String],
@@ -312,91 +1404,125 @@ pub fn check_redirect(
     Ok(())
 }
 
-/// Navigate to a URL, following redirects. Returns the final response and the final URL.
-pub fn fetch_with_redirects(start_url: &Url) -> Result<(GeminiResponse, Url), GeminiError> {
-    let mut current_url = start_url.clone();
-    let mut visited: Vec<String> = vec![current_url.to_string()];
-    let max_hops = 5;
-
-    loop {
-        let response = fetch(&current_url)?;
-        let first_digit = response.status / 10;
-
-        if first_digit == 3 {
-            let target = url_utils::resolve_url(&current_url, &response.meta)?;
-            let target_str = target.to_string();
-            check_redirect(&visited, &target_str, max_hops)?;
-            visited.push(target_str);
-            current_url = target;
-        } else {
-            return Ok((response, current_url));
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::MockServer;
 
     #[test]
     fn test_parse_success_header() {
-        let (status, meta) = parse_response_header("20 text/gemini").unwrap();
+        let (status, meta) = parse_response_header("20 text/gemini", false).unwrap();
         assert_eq!(status, 20);
         assert_eq!(meta, "text/gemini");
     }
 
     #[test]
     fn test_parse_input_header() {
-        let (status, meta) = parse_response_header("10 Enter your name").unwrap();
+        let (status, meta) = parse_response_header("10 Enter your name", false).unwrap();
         assert_eq!(status, 10);
         assert_eq!(meta, "Enter your name");
     }
 
     #[test]
     fn test_parse_redirect_header() {
-        let (status, meta) = parse_response_header("31 gemini://other/path").unwrap();
+        let (status, meta) = parse_response_header("31 gemini://other/path", false).unwrap();
         assert_eq!(status, 31);
         assert_eq!(meta, "gemini://other/path");
     }
 
     #[test]
     fn test_parse_temp_failure() {
-        let (status, meta) = parse_response_header("40 Server busy").unwrap();
+        let (status, meta) = parse_response_header("40 Server busy", false).unwrap();
         assert_eq!(status, 40);
         assert_eq!(meta, "Server busy");
     }
 
     #[test]
     fn test_parse_perm_failure() {
-        let (status, meta) = parse_response_header("51 Not found").unwrap();
+        let (status, meta) = parse_response_header("51 Not found", false).unwrap();
         assert_eq!(status, 51);
         assert_eq!(meta, "Not found");
     }
 
     #[test]
     fn test_parse_cert_required() {
-        let (status, meta) = parse_response_header("60 Certificate required").unwrap();
+        let (status, meta) = parse_response_header("60 Certificate required", false).unwrap();
         assert_eq!(status, 60);
         assert_eq!(meta, "Certificate required");
     }
 
     #[test]
     fn test_parse_empty_meta() {
-        let (status, meta) = parse_response_header("20 ").unwrap();
+        let (status, meta) = parse_response_header("20 ", false).unwrap();
         assert_eq!(status, 20);
         assert_eq!(meta, "");
     }
 
+    #[test]
+    fn test_parse_meta_length_limit() {
+        let meta = "a".repeat(MAX_META_LEN);
+        let (_, parsed) = parse_response_header(&format!("20 {meta}"), false).unwrap();
+        assert_eq!(parsed.len(), 1024);
+
+        let too_long = "a".repeat(1025);
+        match parse_response_header(&format!("20 {too_long}"), false) {
+            Err(GeminiError::InvalidResponse(msg)) => assert_eq!(msg, "meta too long"),
+            other => panic!("expected meta too long, got {:?}", other.map(|(s, _)| s)),
+        }
+    }
+
+    #[test]
+    fn test_read_header_accepts_maximum_meta() {
+        let header = format!("20 {}", "a".repeat(MAX_META_LEN));
+        let line = read_header(&mut chunked(&[header.as_bytes(), b"\r\n"]), &LIVE).unwrap();
+        assert_eq!(line, header);
+    }
+
     #[test]
     fn test_parse_meta_no_space() {
-        let (status, meta) = parse_response_header("20").unwrap();
+        let (status, meta) = parse_response_header("20", false).unwrap();
         assert_eq!(status, 20);
         assert_eq!(meta, "");
     }
 
+    #[test]
+    fn test_missing_space_strict_and_lenient() {
+        assert!(matches!(
+            parse_response_header("20text/gemini", false),
+            Err(GeminiError::InvalidResponse(_))
+        ));
+        let (status, meta) = parse_response_header("20text/gemini", true).unwrap();
+        assert_eq!(status, 20);
+        assert_eq!(meta, "text/gemini");
+        // A well-formed header parses the same either way
+        assert_eq!(
+            parse_response_header("20 text/gemini", true).unwrap(),
+            (20, "text/gemini".to_string())
+        );
+        assert!(parse_response_header("2Xtext/gemini", true).is_err());
+    }
+
+    #[test]
+    fn test_lenient_client_accepts_missing_space() {
+        let server = MockServer::new()
+            .route("/", "20text/gemini\r\n# Hi\n")
+            .start();
+        assert!(matches!(
+            mock_client().build().fetch(&server.url("/")),
+            Err(GeminiError::InvalidResponse(_))
+        ));
+        let response = mock_client()
+            .lenient_headers(true)
+            .build()
+            .fetch(&server.url("/"))
+            .unwrap();
+        assert_eq!(response.meta, "text/gemini");
+        assert_eq!(response.body.as_deref(), Some(&b"# Hi\n"[..]));
+    }
+
     #[test]
     fn test_reject_single_digit() {
-        let result = parse_response_header("2 text");
+        let result = parse_response_header("2 text", false);
         assert!(result.is_err());
         let err = result.unwrap_err();
         assert!(matches!(err, GeminiError::InvalidResponse(_)));
@@ -404,7 +1530,7 @@ mod tests {
 
     #[test]
     fn test_reject_non_numeric() {
-        let result = parse_response_header("AB text");
+        let result = parse_response_header("AB text", false);
         assert!(result.is_err());
         let err = result.unwrap_err();
         assert!(matches!(err, GeminiError::InvalidResponse(_)));
@@ -412,7 +1538,7 @@ mod tests {
 
     #[test]
     fn test_reject_status_out_of_range() {
-        let result = parse_response_header("70 whatever");
+        let result = parse_response_header("70 whatever", false);
         assert!(result.is_err());
         let err = result.unwrap_err();
         assert!(matches!(err, GeminiError::InvalidResponse(_)));
@@ -420,7 +1546,7 @@ mod tests {
 
     #[test]
     fn test_reject_status_too_low() {
-        let result = parse_response_header("09 whatever");
+        let result = parse_response_header("09 whatever", false);
         assert!(result.is_err());
     }
 
@@ -434,6 +1560,29 @@ mod tests {
         assert!(matches!(result, Err(GeminiError::RedirectLoop)));
     }
 
+    #[test]
+    fn test_disguised_redirect_loop_detected() {
+        let server = MockServer::new()
+            .route("/a/", "31 /b\r\n")
+            .route("/b", "31 /x/../a/./\r\n")
+            .start();
+        let result = mock_client()
+            .build()
+            .fetch_with_redirects(&server.url("/a/"));
+        assert!(matches!(result, Err(GeminiError::RedirectLoop)));
+        // Only the two real requests were made
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_redirect_loop_detection_normalizes_host_and_port() {
+        let start = Url::parse("gemini://h.example/a/").unwrap();
+        let visited = vec![url_utils::normalize(&start).to_string()];
+        let target = Url::parse("gemini://H.EXAMPLE:1965/b/../a/").unwrap();
+        let result = check_redirect(&visited, url_utils::normalize(&target).as_str(), 5);
+        assert!(matches!(result, Err(GeminiError::RedirectLoop)));
+    }
+
     #[test]
     fn test_redirect_max_hops() {
         let visited: Vec<String> = (0..5)
@@ -443,10 +1592,749 @@ mod tests {
         assert!(matches!(result, Err(GeminiError::TooManyRedirects)));
     }
 
+    #[test]
+    fn test_client_builder_overrides_defaults() {
+        let client = GeminiClient::builder()
+            .connect_timeout(Duration::from_secs(20))
+            .io_timeout(Duration::from_secs(30))
+            .max_body_size(1024)
+            .max_redirects(2)
+            .build();
+        assert_eq!(client.connect_timeout, Duration::from_secs(20));
+        assert_eq!(client.io_timeout, Duration::from_secs(30));
+        assert_eq!(client.max_body_size, 1024);
+        assert_eq!(client.max_redirects, 2);
+    }
+
+    #[test]
+    fn test_client_default_limits() {
+        let client = GeminiClient::default();
+        assert_eq!(client.connect_timeout, DEFAULT_CONNECT_TIMEOUT);
+        assert_eq!(client.io_timeout, DEFAULT_IO_TIMEOUT);
+        assert_eq!(client.max_body_size, DEFAULT_MAX_BODY_SIZE);
+        assert_eq!(client.max_redirects, DEFAULT_MAX_REDIRECTS);
+    }
+
+    #[test]
+    fn test_slow_down_delay() {
+        assert_eq!(slow_down_delay("30"), Some(Duration::from_secs(30)));
+        assert_eq!(slow_down_delay(" 5 "), Some(Duration::from_secs(5)));
+        assert_eq!(slow_down_delay("9999"), Some(MAX_SLOW_DOWN_WAIT));
+        assert_eq!(slow_down_delay("soon"), None);
+        assert_eq!(slow_down_delay(""), None);
+    }
+
+    #[test]
+    fn test_resolve_redirect_rejects_other_schemes() {
+        let base = Url::parse("gemini://example.com/dir/page").unwrap();
+        for target in [
+            "https://example.com/",
+            "gopher://example.com/",
+            "file:///etc/passwd",
+        ] {
+            assert!(matches!(
+                resolve_redirect(&base, target),
+                Err(GeminiError::CrossSchemeRedirect(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_resolve_redirect_relative_and_absolute() {
+        let base = Url::parse("gemini://example.com/dir/page").unwrap();
+        assert_eq!(
+            resolve_redirect(&base, "other").unwrap().as_str(),
+            "gemini://example.com/dir/other"
+        );
+        assert_eq!(
+            resolve_redirect(&base, " gemini://elsewhere.net/ ")
+                .unwrap()
+                .as_str(),
+            "gemini://elsewhere.net/"
+        );
+    }
+
+    #[test]
+    fn test_same_host() {
+        let a = Url::parse("gemini://example.com/a").unwrap();
+        assert!(same_host(
+            &a,
+            &Url::parse("gemini://EXAMPLE.com/b").unwrap()
+        ));
+        assert!(same_host(
+            &a,
+            &Url::parse("gemini://example.com:1965/").unwrap()
+        ));
+        assert!(!same_host(
+            &a,
+            &Url::parse("gemini://example.com:1966/").unwrap()
+        ));
+        assert!(!same_host(
+            &a,
+            &Url::parse("gemini://evil.example/").unwrap()
+        ));
+    }
+
+    /// Hands out its data in fixed, deliberately awkward pieces, one per read.
+    struct ChunkedReader {
+        chunks: Vec<Vec<u8>>,
+    }
+
+    impl Read for ChunkedReader {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            if self.chunks.is_empty() {
+                return Ok(0);
+            }
+            let chunk = self.chunks.remove(0);
+            out[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    /// A cancel flag that is never raised.
+    static LIVE: AtomicBool = AtomicBool::new(false);
+
+    /// Fails every read as a socket whose read timeout expired would.
+    struct StalledReader(io::ErrorKind);
+
+    impl Read for StalledReader {
+        fn read(&mut self, _out: &mut [u8]) -> io::Result<usize> {
+            Err(self.0.into())
+        }
+    }
+
+    #[test]
+    fn test_stalled_reads_are_read_timeouts() {
+        for kind in [io::ErrorKind::WouldBlock, io::ErrorKind::TimedOut] {
+            assert!(matches!(
+                read_header(&mut BufReader::new(StalledReader(kind)), &LIVE),
+                Err(GeminiError::ReadTimeout)
+            ));
+            assert!(matches!(
+                read_body(&mut StalledReader(kind), 1024, &LIVE),
+                Err(GeminiError::ReadTimeout)
+            ));
+        }
+        assert!(matches!(
+            read_body(&mut StalledReader(io::ErrorKind::BrokenPipe), 1024, &LIVE),
+            Err(GeminiError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_handshake_error_names_version_only_for_version_problems() {
+        let error = |e: rustls::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+        let version = handshake_error(
+            error(PeerIncompatible::ServerTlsVersionIsDisabledByOurConfig.into()),
+            TlsVersion::Tls13,
+        );
+        assert_eq!(
+            version.to_string(),
+            "TLS error: server does not support TLS 1.3 or newer"
+        );
+        let ciphers = handshake_error(
+            error(PeerIncompatible::NoCipherSuitesInCommon.into()),
+            TlsVersion::Tls12,
+        );
+        assert!(matches!(&ciphers, GeminiError::TlsError(m) if !m.contains("does not support")));
+    }
+
+    #[test]
+    fn test_reset_after_partial_body_keeps_body() {
+        let reset = || {
+            io::Cursor::new(b"partial".to_vec())
+                .chain(StalledReader(io::ErrorKind::ConnectionReset))
+        };
+        assert_eq!(read_body(&mut reset(), 1024, &LIVE).unwrap(), b"partial");
+        let mut body = Vec::new();
+        assert_eq!(copy_body(&mut reset(), &mut body, &LIVE).unwrap(), 7);
+        assert_eq!(body, b"partial");
+    }
+
+    #[test]
+    fn test_silent_server_is_read_timeout() {
+        // Accepts the connection but never answers the TLS handshake
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let url = Url::parse(&format!("gemini://127.0.0.1:{port}/")).unwrap();
+        let client = mock_client().io_timeout(Duration::from_millis(100)).build();
+        assert!(matches!(client.fetch(&url), Err(GeminiError::ReadTimeout)));
+        drop(listener);
+    }
+
+    #[test]
+    fn test_tls_config_reused_and_session_resumed() {
+        let server = MockServer::new()
+            .route("/", "20 text/gemini\r\n# Hello\n")
+            .start();
+        let client = mock_client().build();
+        let host = server.url("/").host_str().unwrap().to_string();
+        let port = server.url("/").port().unwrap();
+
+        let first = client.fetch(&server.url("/")).unwrap();
+        let config = client.tls_config(&host, port).unwrap();
+        let second = client.clone().fetch(&server.url("/")).unwrap();
+        assert!(Arc::ptr_eq(
+            &config,
+            &client.tls_config(&host, port).unwrap()
+        ));
+        assert!(!first.metrics.resumed);
+        assert!(second.metrics.resumed);
+
+        client.clear_tls_configs();
+        assert!(!Arc::ptr_eq(
+            &config,
+            &client.tls_config(&host, port).unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_tls_version_parse() {
+        assert_eq!(TlsVersion::parse("1.2"), Some(TlsVersion::Tls12));
+        assert_eq!(TlsVersion::parse(" 1.3 "), Some(TlsVersion::Tls13));
+        assert_eq!(TlsVersion::parse("1.1"), None);
+        assert_eq!(TlsVersion::default(), TlsVersion::Tls12);
+    }
+
+    #[test]
+    fn test_min_tls_version_accepts_matching_server() {
+        let server = MockServer::new()
+            .route("/", "20 text/gemini\r\n")
+            .tls12_only()
+            .start();
+        let response = mock_client().build().fetch(&server.url("/")).unwrap();
+        assert_eq!(response.status, 20);
+
+        let server = MockServer::new().route("/", "20 text/gemini\r\n").start();
+        let response = mock_client()
+            .min_tls_version(TlsVersion::Tls13)
+            .build()
+            .fetch(&server.url("/"))
+            .unwrap();
+        assert_eq!(response.status, 20);
+    }
+
+    #[test]
+    fn test_min_tls_version_rejects_older_server() {
+        let server = MockServer::new()
+            .route("/", "20 text/gemini\r\n")
+            .tls12_only()
+            .start();
+        let result = mock_client()
+            .min_tls_version(TlsVersion::Tls13)
+            .build()
+            .fetch(&server.url("/"));
+        match result {
+            Err(GeminiError::TlsError(message)) => {
+                assert_eq!(message, "server does not support TLS 1.3 or newer");
+            }
+            other => panic!("expected TlsError, got {:?}", other.map(|r| r.status)),
+        }
+    }
+
+    fn response(status: u8) -> GeminiResponse {
+        GeminiResponse {
+            status,
+            meta: String::new(),
+            body: None,
+            certificate: None,
+            metrics: FetchMetrics::default(),
+        }
+    }
+
+    #[test]
+    fn test_status_class_of_each_status() {
+        let classes = [
+            (10, StatusClass::Input),
+            (11, StatusClass::Input),
+            (20, StatusClass::Success),
+            (30, StatusClass::Redirect),
+            (31, StatusClass::Redirect),
+            (40, StatusClass::TemporaryFailure),
+            (44, StatusClass::TemporaryFailure),
+            (51, StatusClass::PermanentFailure),
+            (59, StatusClass::PermanentFailure),
+            (60, StatusClass::ClientCertRequired),
+            (62, StatusClass::ClientCertRequired),
+        ];
+        for (status, class) in classes {
+            assert_eq!(response(status).class(), Some(class), "status {status}");
+        }
+        assert_eq!(StatusClass::of(9), None);
+        assert_eq!(StatusClass::of(70), None);
+    }
+
+    #[test]
+    fn test_status_detail_helpers() {
+        assert!(response(11).is_sensitive_input());
+        assert!(!response(10).is_sensitive_input());
+        assert!(response(30).is_temporary_redirect());
+        assert!(!response(31).is_temporary_redirect());
+        assert!(response(31).is_permanent_redirect());
+        assert!(!response(30).is_permanent_redirect());
+        assert!(response(44).is_slow_down());
+        assert!(!response(40).is_slow_down());
+    }
+
+    #[test]
+    fn test_request_slots_are_per_host() {
+        let interval = Duration::from_secs(10);
+        let client = GeminiClient::builder().request_interval(interval).build();
+        assert_eq!(client.reserve_request_slot("a.example"), Duration::ZERO);
+        let delay = client.reserve_request_slot("a.example");
+        assert!(delay > interval - Duration::from_secs(1) && delay <= interval);
+        // The next slot queues behind the one just handed out
+        assert!(client.reserve_request_slot("a.example") > interval);
+        assert_eq!(client.reserve_request_slot("b.example"), Duration::ZERO);
+
+        let unlimited = GeminiClient::default();
+        assert_eq!(unlimited.reserve_request_slot("a.example"), Duration::ZERO);
+        assert_eq!(unlimited.reserve_request_slot("a.example"), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_request_interval_delays_fetches_to_same_host() {
+        let server = MockServer::new().route("/", "20 text/gemini\r\n").start();
+        let interval = Duration::from_millis(200);
+        let client = mock_client().request_interval(interval).build();
+        let started = Instant::now();
+        client.fetch(&server.url("/")).unwrap();
+        client.fetch(&server.url("/")).unwrap();
+        assert!(started.elapsed() >= interval);
+    }
+
+    #[test]
+    fn test_request_line_drops_fragment() {
+        let url = Url::parse("gemini://example.com/doc?q#section").unwrap();
+        assert_eq!(request_line(&url), "gemini://example.com/doc?q\r\n");
+        // Only the request changes
+        assert_eq!(url.fragment(), Some("section"));
+    }
+
+    #[test]
+    fn test_fetch_to_writer_never_sends_fragment() {
+        let server = MockServer::new()
+            .route("/doc", "20 text/plain\r\nbody")
+            .start();
+        let mut body = Vec::new();
+        mock_client()
+            .build()
+            .fetch_to_writer(&server.url("/doc#section"), &mut body)
+            .unwrap();
+        assert_eq!(body, b"body");
+        assert_eq!(server.requests(), vec![server.url("/doc").to_string()]);
+    }
+
+    #[test]
+    fn test_fetch_to_writer_rejects_invalid_mime() {
+        let server = MockServer::new()
+            .route("/doc", "20 not a type\r\nbody")
+            .start();
+        let mut body = Vec::new();
+        let result = mock_client()
+            .build()
+            .fetch_to_writer(&server.url("/doc"), &mut body);
+        assert!(matches!(result, Err(GeminiError::InvalidResponse(_))));
+        assert!(body.is_empty());
+    }
+
+    #[test]
+    fn test_fetch_uppercase_host_connects_lowercased() {
+        let server = MockServer::new()
+            .route("/Path", "20 text/gemini\r\n")
+            .start();
+        let port = server.url("/").port().unwrap();
+        let url = url_utils::parse_gemini_url(&format!("GEMINI://LocalHost:{port}/Path")).unwrap();
+        let response = mock_client().build().fetch(&url).unwrap();
+        assert_eq!(response.status, 20);
+        assert_eq!(
+            server.requests(),
+            vec![format!("gemini://localhost:{port}/Path")]
+        );
+    }
+
+    #[test]
+    fn test_fetch_never_sends_fragment() {
+        let server = MockServer::new()
+            .route("/page?q", "20 text/gemini\r\n")
+            .start();
+        let response = mock_client()
+            .build()
+            .fetch(&server.url("/page?q#results"))
+            .unwrap();
+        assert_eq!(response.status, 20);
+        assert_eq!(server.requests(), vec![server.url("/page?q").to_string()]);
+    }
+
+    #[test]
+    fn test_raised_flag_stops_reads() {
+        let cancel = AtomicBool::new(true);
+        assert!(matches!(
+            read_header(&mut chunked(&[b"20 text/gemini\r\n"]), &cancel),
+            Err(GeminiError::Cancelled)
+        ));
+        assert!(matches!(
+            read_body(&mut chunked(&[b"body"]), 1024, &cancel),
+            Err(GeminiError::Cancelled)
+        ));
+        assert!(matches!(
+            copy_body(&mut chunked(&[b"body"]), &mut Vec::new(), &cancel),
+            Err(GeminiError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn test_cancel_interrupts_retry_wait() {
+        let server = MockServer::new().route("/", "41 Busy\r\n").start();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let client = mock_client()
+            .max_retries(1)
+            .retry_delay(Duration::from_secs(30))
+            .cancel_flag(cancel.clone())
+            .build();
+        let raiser = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(200));
+                cancel.store(true, Ordering::Relaxed);
+            })
+        };
+        let started = Instant::now();
+        assert!(matches!(
+            client.fetch_with_redirects(&server.url("/")),
+            Err(GeminiError::Cancelled)
+        ));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        raiser.join().unwrap();
+    }
+
+    fn chunked(chunks: &[&[u8]]) -> BufReader<ChunkedReader> {
+        BufReader::new(ChunkedReader {
+            chunks: chunks.iter().map(|c| c.to_vec()).collect(),
+        })
+    }
+
+    #[test]
+    fn test_read_header_split_across_reads() {
+        let mut stream = chunked(&[b"2", b"0 text/gem", b"ini\r", b"\n# Hel", b"lo\nworld"]);
+        assert_eq!(read_header(&mut stream, &LIVE).unwrap(), "20 text/gemini");
+        assert_eq!(
+            read_body(&mut stream, 1024, &LIVE).unwrap(),
+            b"# Hello\nworld"
+        );
+    }
+
+    #[test]
+    fn test_read_header_keeps_body_from_same_read() {
+        let mut stream = chunked(&[b"20 text/plain\r\nbody bytes"]);
+        assert_eq!(read_header(&mut stream, &LIVE).unwrap(), "20 text/plain");
+        let mut body = Vec::new();
+        copy_body(&mut stream, &mut body, &LIVE).unwrap();
+        assert_eq!(body, b"body bytes");
+    }
+
+    #[test]
+    fn test_read_header_lone_newline_is_not_terminator() {
+        let mut stream = chunked(&[b"20 a\nb\r\n"]);
+        assert_eq!(read_header(&mut stream, &LIVE).unwrap(), "20 a\nb");
+    }
+
+    #[test]
+    fn test_read_header_errors() {
+        assert!(matches!(
+            read_header(&mut chunked(&[]), &LIVE),
+            Err(GeminiError::InvalidResponse(_))
+        ));
+
+        let long = vec![b'a'; MAX_HEADER_LEN + 10];
+        assert!(matches!(
+            read_header(&mut chunked(&[&long, b"\r\n"]), &LIVE),
+            Err(GeminiError::InvalidResponse(_))
+        ));
+    }
+
     #[test]
     fn test_redirect_ok() {
         let visited = vec!["gemini://example.com/a".to_string()];
         let result = check_redirect(&visited, "gemini://example.com/b", 5);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_connect_falls_back_across_families() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        // Nothing listens on this port once the probe listener is dropped
+        let closed = std::net::TcpListener::bind("[::1]:0")
+            .and_then(|l| l.local_addr())
+            .unwrap_or_else(|_| "[::1]:1".parse().unwrap());
+
+        let stream = connect_with_timeout(&[closed, open], Duration::from_secs(5)).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), open);
+    }
+
+    #[test]
+    fn test_connect_reports_failure() {
+        let closed = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|l| l.local_addr())
+            .unwrap();
+        assert!(matches!(
+            connect_with_timeout(&[closed], Duration::from_secs(5)),
+            Err(GeminiError::ConnectionFailed(_))
+        ));
+        assert!(matches!(
+            connect_with_timeout(&[], Duration::from_secs(5)),
+            Err(GeminiError::ConnectionFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_connect_moves_on_after_a_timeout() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        let slow: SocketAddr = "192.0.2.1:1965".parse().unwrap();
+        let connect = |addr: &SocketAddr, timeout: Duration| {
+            if *addr == slow {
+                Err(io::Error::from(io::ErrorKind::TimedOut))
+            } else {
+                TcpStream::connect_timeout(addr, timeout)
+            }
+        };
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        let stream = connect_in_order_with(&[slow, open], deadline, connect).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), open);
+        assert!(matches!(
+            connect_in_order_with(&[slow, slow], deadline, connect),
+            Err(GeminiError::Timeout)
+        ));
+    }
+
+    fn mock_client() -> GeminiClientBuilder {
+        GeminiClient::builder().cert_verifier(Arc::new(crate::verify::InsecureVerifier))
+    }
+
+    #[test]
+    fn test_fetch_success_end_to_end() {
+        let server = MockServer::new()
+            .route("/", "20 text/gemini\r\n# Hello\n")
+            .start();
+        let response = mock_client().build().fetch(&server.url("/")).unwrap();
+        assert_eq!(response.status, 20);
+        assert_eq!(response.meta, "text/gemini");
+        assert_eq!(response.body.as_deref(), Some(&b"# Hello\n"[..]));
+        assert_eq!(response.metrics.body_bytes, 8);
+        assert!(response.certificate.is_some());
+    }
+
+    #[test]
+    fn test_fetch_follows_redirect_chain() {
+        let server = MockServer::new()
+            .route("/a", "31 /b\r\n")
+            .route("/b", "31 c\r\n")
+            .route("/c", "20 text/plain\r\ndone")
+            .start();
+        let (response, final_url) = mock_client()
+            .build()
+            .fetch_with_redirects(&server.url("/a"))
+            .unwrap();
+        assert_eq!(response.status, 20);
+        assert_eq!(final_url, server.url("/c"));
+        assert_eq!(response.body.as_deref(), Some(&b"done"[..]));
+    }
+
+    #[test]
+    fn test_only_permanent_redirects_are_reported_as_moves() {
+        let server = MockServer::new()
+            .route("/old", "31 /new\r\n")
+            .route("/detour", "30 /new\r\n")
+            .route("/new", "20 text/gemini\r\n")
+            .start();
+        let client = mock_client().build();
+        let (_, final_url, moved) = client.fetch_with_moves(&server.url("/old")).unwrap();
+        assert_eq!(final_url, server.url("/new"));
+        assert_eq!(moved, vec![(server.url("/old"), server.url("/new"))]);
+        let (_, final_url, moved) = client.fetch_with_moves(&server.url("/detour")).unwrap();
+        assert_eq!(final_url, server.url("/new"));
+        assert!(moved.is_empty());
+    }
+
+    #[test]
+    fn test_confirmed_cross_host_redirects_keep_loop_detection_and_moves() {
+        let server = MockServer::new()
+            .route("/a", "31 gemini://localhost:{port}/b\r\n")
+            .route("/b", "31 gemini://127.0.0.1:{port}/a\r\n")
+            .route("/old", "31 gemini://localhost:{port}/new\r\n")
+            .route("/new", "20 text/gemini\r\n")
+            .start();
+        let port = server.url("/").port().unwrap();
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let client = {
+            let asked = asked.clone();
+            mock_client()
+                .confirm_cross_host_redirects(move |from, to| {
+                    asked.lock().unwrap().push((from.clone(), to.clone()));
+                    true
+                })
+                .build()
+        };
+
+        assert!(matches!(
+            client.fetch_with_moves(&server.url("/a")),
+            Err(GeminiError::RedirectLoop)
+        ));
+        assert_eq!(asked.lock().unwrap().len(), 1);
+
+        let new = Url::parse(&format!("gemini://localhost:{port}/new")).unwrap();
+        let (response, final_url, moved) = client.fetch_with_moves(&server.url("/old")).unwrap();
+        assert_eq!(response.status, 20);
+        assert_eq!(final_url, new);
+        assert_eq!(moved, vec![(server.url("/old"), new)]);
+    }
+
+    #[test]
+    fn test_declined_cross_host_redirect_stops() {
+        let server = MockServer::new()
+            .route("/a", "30 gemini://localhost:{port}/b\r\n")
+            .route("/c", "30 /d\r\n")
+            .route("/d", "20 text/gemini\r\n")
+            .start();
+        let client = mock_client()
+            .confirm_cross_host_redirects(|_, _| false)
+            .build();
+        assert!(matches!(
+            client.fetch_with_redirects(&server.url("/a")),
+            Err(GeminiError::CrossHostRedirect { .. })
+        ));
+        // Same-host redirects don't ask
+        let (response, _) = client.fetch_with_redirects(&server.url("/c")).unwrap();
+        assert_eq!(response.status, 20);
+    }
+
+    #[test]
+    fn test_temporary_failures_are_retried() {
+        let server = MockServer::new()
+            .route("/flaky", "41 Server unavailable\r\n")
+            .route("/flaky", "41 Server unavailable\r\n")
+            .route("/flaky", "20 text/gemini\r\nback\n")
+            .start();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let client = {
+            let events = events.clone();
+            mock_client()
+                .max_retries(2)
+                .retry_delay(Duration::from_millis(1))
+                .on_event(move |event| events.lock().unwrap().push(event.clone()))
+                .build()
+        };
+        let (response, _) = client.fetch_with_redirects(&server.url("/flaky")).unwrap();
+        assert_eq!(response.status, 20);
+        assert_eq!(response.body.as_deref(), Some(&b"back\n"[..]));
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[1],
+            ClientEvent::Retry {
+                url: server.url("/flaky"),
+                status: 41,
+                attempt: 2,
+                max_retries: 2,
+                delay: Duration::from_millis(2),
+            }
+        );
+    }
+
+    #[test]
+    fn test_verbose_lines_and_warnings_go_to_the_event_handler() {
+        let server = MockServer::new().route("/", "20 text/gemini\r\n").start();
+        let identities = std::env::temp_dir().join(format!(
+            "gemini-browser-bad-identity-{}",
+            std::process::id()
+        ));
+        let host_dir = identities.join("127.0.0.1");
+        std::fs::create_dir_all(&host_dir).unwrap();
+        std::fs::write(host_dir.join("cert.pem"), "garbage").unwrap();
+        std::fs::write(host_dir.join("key.pem"), "garbage").unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let client = {
+            let events = events.clone();
+            mock_client()
+                .verbose(true)
+                .identity_dir(identities.clone())
+                .on_event(move |event| events.lock().unwrap().push(event.clone()))
+                .build()
+        };
+        client.fetch(&server.url("/")).unwrap();
+        let _ = std::fs::remove_dir_all(identities);
+
+        let events = events.lock().unwrap();
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, ClientEvent::Verbose(line) if line.starts_with("> "))));
+        assert!(events.iter().any(
+            |e| matches!(e, ClientEvent::Warning(message) if message.contains("ignoring identity"))
+        ));
+    }
+
+    #[test]
+    fn test_temporary_failures_are_not_retried_by_default() {
+        let server = MockServer::new()
+            .route("/flaky", "41 Server unavailable\r\n")
+            .route("/flaky", "20 text/gemini\r\n")
+            .start();
+        let (response, _) = mock_client()
+            .build()
+            .fetch_with_redirects(&server.url("/flaky"))
+            .unwrap();
+        assert_eq!(response.status, 41);
+    }
+
+    #[test]
+    fn test_retries_give_up_and_skip_permanent_failures() {
+        let server = MockServer::new()
+            .route("/down", "40 Down\r\n")
+            .route("/gone", "51 Not here\r\n")
+            .route("/gone", "20 text/gemini\r\n")
+            .start();
+        let client = mock_client()
+            .max_retries(1)
+            .retry_delay(Duration::from_millis(1))
+            .build();
+        let (response, _) = client.fetch_with_redirects(&server.url("/down")).unwrap();
+        assert_eq!(response.status, 40);
+        let (response, _) = client.fetch_with_redirects(&server.url("/gone")).unwrap();
+        assert_eq!(response.status, 51);
+    }
+
+    #[test]
+    fn test_fetch_input_end_to_end() {
+        let server = MockServer::new()
+            .route("/search", "10 Search terms\r\n")
+            .route("/search?cats", "20 text/gemini\r\nfound\n")
+            .start();
+        let client = mock_client().build();
+        let response = client.fetch(&server.url("/search")).unwrap();
+        assert_eq!(
+            (response.status, response.meta.as_str()),
+            (10, "Search terms")
+        );
+        assert_eq!(response.body, None);
+
+        let response = client.fetch(&server.url("/search?cats")).unwrap();
+        assert_eq!(response.status, 20);
+    }
+
+    #[test]
+    fn test_fetch_rejects_oversized_body_end_to_end() {
+        let server = MockServer::new()
+            .route("/big", format!("20 text/plain\r\n{}", "x".repeat(64)))
+            .start();
+        let client = mock_client().max_body_size(16).build();
+        assert!(matches!(
+            client.fetch(&server.url("/big")),
+            Err(GeminiError::BodyTooLarge(16))
+        ));
+    }
 }
@@ -0,0 +1,75 @@
+use std::sync::OnceLock;
+
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::as_24_bit_terminal_escaped;
+
+const THEME_NAME: &str = "base16-ocean.dark";
+const RESET: &str = "\x1b[0m";
+
+/// Syntax definitions and the color theme, loaded on first use.
+struct Assets {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+fn assets() -> &'static Assets {
+    static ASSETS: OnceLock<Assets> = OnceLock::new();
+    ASSETS.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults();
+        Assets {
+            syntaxes: SyntaxSet::load_defaults_nonewlines(),
+            theme: themes.themes.remove(THEME_NAME).unwrap_or_default(),
+        }
+    })
+}
+
+/// Highlights the lines of one preformatted block, keeping parser state from
+/// line to line.
+pub struct BlockHighlighter {
+    lines: HighlightLines<'static>,
+}
+
+impl BlockHighlighter {
+    /// Start a block whose alt text begins with a language name or file
+    /// extension (e.g. `rust`, `py`). Returns None for empty or unknown alt
+    /// text, in which case the block should be shown as-is.
+    pub fn for_alt_text(alt_text: &str) -> Option<Self> {
+        let token = alt_text.split_whitespace().next()?;
+        let assets = assets();
+        let syntax = assets.syntaxes.find_syntax_by_token(token)?;
+        Some(Self {
+            lines: HighlightLines::new(syntax, &assets.theme),
+        })
+    }
+
+    /// Highlight the next line of the block as 24-bit color ANSI.
+    pub fn highlight(&mut self, line: &str) -> Option<String> {
+        let ranges = self.lines.highlight_line(line, &assets().syntaxes).ok()?;
+        Some(format!(
+            "{}{RESET}",
+            as_24_bit_terminal_escaped(&ranges, false)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::strip_ansi;
+
+    #[test]
+    fn test_known_language_is_highlighted() {
+        let mut highlighter = BlockHighlighter::for_alt_text("rust example").unwrap();
+        let line = highlighter.highlight("fn main() {}").unwrap();
+        assert!(line.contains("\x1b[38;2;"));
+        assert_eq!(strip_ansi(&line), "fn main() {}");
+    }
+
+    #[test]
+    fn test_unknown_or_empty_alt_text() {
+        assert!(BlockHighlighter::for_alt_text("").is_none());
+        assert!(BlockHighlighter::for_alt_text("ascii art of a cat").is_none());
+    }
+}
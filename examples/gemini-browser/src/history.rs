@@ -0,0 +1,149 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::paths;
+
+const HISTORY_FILE: &str = "history.log";
+
+/// Number of history entries loaded back onto the back stack at startup.
+pub const DEFAULT_HISTORY_CAP: usize = 50;
+
+/// One visited page and when it was visited.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// ISO-8601 (RFC 3339) timestamp in UTC.
+    pub timestamp: String,
+    pub url: String,
+}
+
+/// Browsing history, oldest first.
+///
+/// Backed by an append-only log with one `timestamp url` entry per line.
+/// When no path is available (no home directory), history is kept in memory
+/// only.
+#[derive(Debug)]
+pub struct HistoryLog {
+    path: Option<PathBuf>,
+    entries: Vec<HistoryEntry>,
+}
+
+impl HistoryLog {
+    /// Load `history.log` from the data directory.
+    pub fn load_default() -> Self {
+        match paths::data_dir() {
+            Some(dir) => Self::load(dir.join(HISTORY_FILE)),
+            None => Self {
+                path: None,
+                entries: Vec::new(),
+            },
+        }
+    }
+
+    /// Load history from `path`. A missing or unreadable file yields an
+    /// empty history.
+    pub fn load(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .map(|contents| parse_history(&contents))
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            entries,
+        }
+    }
+
+    /// Record a visit to `url` now and append it to the log file.
+    pub fn record(&mut self, url: &str) -> io::Result<()> {
+        let timestamp = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .map_err(io::Error::other)?;
+        let entry = HistoryEntry {
+            timestamp,
+            url: url.to_string(),
+        };
+        let result = self.append(&entry);
+        self.entries.push(entry);
+        result
+    }
+
+    /// The most recent `n` entries, newest first.
+    pub fn recent(&self, n: usize) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter().rev().take(n)
+    }
+
+    fn append(&self, entry: &HistoryEntry) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{} {}", entry.timestamp, entry.url)
+    }
+}
+
+/// Parse log contents. Lines without both a timestamp and a URL are skipped.
+fn parse_history(contents: &str) -> Vec<HistoryEntry> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (timestamp, url) = line.trim().split_once(' ')?;
+            let url = url.trim();
+            if url.is_empty() {
+                return None;
+            }
+            Some(HistoryEntry {
+                timestamp: timestamp.to_string(),
+                url: url.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_history() {
+        let entries = parse_history(
+            "2024-01-01T00:00:00Z gemini://a.example/\n\nbroken\n2024-01-02T00:00:00Z gemini://b.example/\n",
+        );
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].timestamp, "2024-01-01T00:00:00Z");
+        assert_eq!(entries[1].url, "gemini://b.example/");
+    }
+
+    #[test]
+    fn test_recent_is_newest_first() {
+        let mut log = HistoryLog {
+            path: None,
+            entries: Vec::new(),
+        };
+        log.record("gemini://a.example/").unwrap();
+        log.record("gemini://b.example/").unwrap();
+        log.record("gemini://c.example/").unwrap();
+
+        let urls: Vec<&str> = log.recent(2).map(|e| e.url.as_str()).collect();
+        assert_eq!(urls, vec!["gemini://c.example/", "gemini://b.example/"]);
+    }
+
+    #[test]
+    fn test_history_roundtrip() {
+        let path =
+            std::env::temp_dir().join(format!("gemini-browser-history-{}", std::process::id()));
+        let mut log = HistoryLog::load(path.clone());
+        log.record("gemini://a.example/").unwrap();
+        log.record("gemini://b.example/").unwrap();
+
+        let reloaded = HistoryLog::load(path.clone());
+        let urls: Vec<&str> = reloaded.recent(10).map(|e| e.url.as_str()).collect();
+        assert_eq!(urls, vec!["gemini://b.example/", "gemini://a.example/"]);
+        assert!(reloaded.recent(1).next().unwrap().timestamp.ends_with('Z'));
+        let _ = fs::remove_file(path);
+    }
+}
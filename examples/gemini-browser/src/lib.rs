@@ -0,0 +1,53 @@
+//! The protocol and rendering half of the Gemini browser, for use outside
+//! the `gemini-browser` binary.
+//!
+//! - `gemini`: fetch pages over TLS, with redirects, retries, and TOFU
+//!   certificate pinning
+//! - `parser`: split a `text/gemini` body into typed lines
+//! - `render`: format parsed lines as styled terminal text
+//! - `url_utils`: parse, resolve, and normalize gemini URLs
+//!
+//! The other public modules hold types those four hand out or accept
+//! (certificates, MIME types, themes, verifiers), along with Titan uploads
+//! and session recording, which build on the client's connections. Modules
+//! marked hidden are shared with the binary and may change without notice.
+//!
+//! Nothing here prints. The client reports retries, warnings, and verbose
+//! logging through `GeminiClientBuilder::on_event`, and it only touches the
+//! user's `known_hosts` file and identities when given them with
+//! `known_hosts` and `identity_dir`.
+//!
+//! ```
+//! use std::collections::HashSet;
+//!
+//! use gemini_browser::render::{render, RenderOptions};
+//! use gemini_browser::{parser, theme::Theme};
+//!
+//! let lines = parser::parse_gemini("# Hello\n=> gemini://example.com/ Example\n");
+//! let opts = RenderOptions {
+//!     color: false,
+//!     ..RenderOptions::default()
+//! };
+//! let page = render(&lines, None, &HashSet::new(), 80, &opts, &Theme::default());
+//! assert_eq!(page.links[0].0, "gemini://example.com/");
+//! ```
+
+pub mod cert;
+pub mod gemini;
+mod highlight;
+pub mod identity;
+pub mod mime;
+pub mod parser;
+#[doc(hidden)]
+pub mod paths;
+pub mod record;
+pub mod render;
+#[cfg(test)]
+mod test_support;
+#[doc(hidden)]
+pub mod text;
+pub mod theme;
+pub mod titan;
+pub mod tofu;
+pub mod url_utils;
+pub mod verify;
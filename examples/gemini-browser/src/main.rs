@@ -1,38 +1,374 @@
-mod gemini;
+mod aliases;
+mod bookmarks;
+mod completion;
+mod config;
+mod favicon;
+mod history;
+mod moved;
 mod pager;
-mod parser;
-mod render;
-mod url_utils;
+mod subscriptions;
+mod viewer;
 
+use gemini_browser::{
+    cert, gemini, identity, mime, parser, paths, record, render, text, theme, titan, tofu,
+    url_utils, verify,
+};
+
+use std::collections::HashSet;
 use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
+use rustyline::error::ReadlineError;
+use rustyline::history::FileHistory;
+use rustyline::{CompletionType, Editor};
 use url::Url;
 
-use gemini::GeminiResponse;
+use bookmarks::Bookmarks;
+use gemini::{GeminiClient, GeminiError, GeminiResponse, StatusClass, TlsVersion};
+use history::HistoryLog;
+use render::{status_color, ColorMode, RenderOptions};
+use tofu::KnownHosts;
+use verify::VerifyMode;
 
 /// Holds the browser's runtime state.
 struct BrowserState {
-    history: Vec<Url>,
+    client: GeminiClient,
+    /// Raised by Ctrl-C to abandon the fetch in progress; shared with
+    /// `client`.
+    cancel: Arc<AtomicBool>,
+    /// Set while a fetch waits for the user to confirm a redirect. Ctrl-C
+    /// doesn't interrupt reading the answer, so the fetch isn't abandoned
+    /// until it has one.
+    confirming: Arc<AtomicBool>,
+    render_options: RenderOptions,
+    theme: theme::Theme,
+    /// Fixed wrap width from the config file; None follows the terminal.
+    wrap_width: Option<usize>,
+    /// Home page from the config file, for `home`.
+    home: Option<String>,
+    /// Earlier pages, most recent last, each with the pager offset it was
+    /// left at so `back` can return to the same spot.
+    history: Vec<(Url, usize)>,
     current_url: Option<Url>,
-    links: Vec<String>,
+    /// `(url, label)` for each link on the current page, in link-number order.
+    links: Vec<(String, String)>,
     pager: Option<pager::Pager>,
+    /// Whether `pager` holds the gemtext shown by `source` rather than the
+    /// rendered page.
+    viewing_source: bool,
+    /// The rendered page's pager, set aside while `source` is shown.
+    rendered_pager: Option<pager::Pager>,
+    /// Absolute URLs of every page successfully loaded this session.
+    visited: HashSet<String>,
+    /// Text of the current page's first `# heading`, if it has one.
+    title: Option<String>,
+    /// The current page's parsed gemtext with source line numbers, for `toc`.
+    document: Vec<(usize, parser::GeminiLine)>,
+    /// Output-line offset of each heading in `document`, from `render`.
+    heading_offsets: Vec<usize>,
+    /// The current page's gemtext exactly as received (after charset
+    /// decoding), for `source`.
+    source: Option<String>,
+    /// MIME type and undecoded body of the current page, for `save <path>`.
+    body: Option<(mime::MimeType, Vec<u8>)>,
+    bookmarks: Bookmarks,
+    aliases: aliases::Aliases,
+    history_log: HistoryLog,
+    /// Pattern of the most recent `/` search.
+    last_search: Option<String>,
+    /// Whether non-gemini URLs may be handed to the system handler.
+    open_external: bool,
+    /// Whether images and other media are opened in `viewer` on arrival.
+    open_media: bool,
+    viewer: viewer::Viewer,
+    /// Whether to show the page title in the terminal's title bar.
+    terminal_title: bool,
+    /// Status and meta of the most recent response, for `info`.
+    last_status: Option<(u8, String)>,
+    /// Timings of the most recent response, for `info`.
+    last_metrics: Option<gemini::FetchMetrics>,
+    /// Certificate presented with the most recent response, for `info`.
+    certificate: Option<cert::CertificateInfo>,
+    /// Emoji favicons of visited hosts, shown in the prompt.
+    favicons: favicon::Favicons,
+    subscriptions: subscriptions::Subscriptions,
+    /// New feed entries from the last `updates`, for `updates go`.
+    updates: Vec<subscriptions::FeedEntry>,
+    /// Pages that answered with a permanent redirect, and where to.
+    moved: moved::MovedPages,
+    /// Leave out the pager status line, the REPL prompt, and warnings about
+    /// mangled pages, for scripting.
+    quiet: bool,
+}
+
+/// How often a fetch running on a worker thread is checked for Ctrl-C.
+const CANCEL_POLL: Duration = Duration::from_millis(50);
+
+/// Number of entries printed by the `history` command.
+const HISTORY_LIST_LEN: usize = 20;
+
+/// File in the data directory holding commands typed at the prompt.
+const COMMAND_HISTORY_FILE: &str = "cmdhistory";
+
+/// Most commands kept in the command history.
+const MAX_COMMAND_HISTORY: usize = 1000;
+
+/// Options given on the command line.
+struct CliArgs {
+    url: Option<String>,
+    color: Option<ColorMode>,
+    hyperlinks: bool,
+    show_link_urls: bool,
+    mark_external: bool,
+    number_lists: bool,
+    reflow: bool,
+    truncate_lines: bool,
+    center_preformatted: bool,
+    history_cap: usize,
+    confirm_redirects: bool,
+    verbose: bool,
+    open_external: bool,
+    open_media: bool,
+    highlight: bool,
+    terminal_title: bool,
+    verify: Option<VerifyMode>,
+    min_tls: Option<TlsVersion>,
+    /// How many times to retry temporary failures.
+    retries: Option<u32>,
+    /// Session file to record requests and responses to.
+    record: Option<PathBuf>,
+    /// Session file to answer requests from instead of the network.
+    replay: Option<PathBuf>,
+    quiet: bool,
+}
+
+/// Parse command-line arguments (excluding the program name).
+/// Accepts an optional start URL, `--color=<always|never|auto>`,
+/// `--hyperlinks`, `--show-urls`, `--mark-external`, `--number-lists`,
+/// `--reflow`, `--truncate-lines`, `--center-pre`, `--history-cap=<n>`,
+/// `--confirm-redirects`, `--verbose`, `--no-external`, `--no-open-media`,
+/// `--no-highlight`, `--no-title`, `--verify=<tofu|ca|insecure>`,
+/// `--min-tls=<1.2|1.3>`, `--retries=<n>`, `--record <file>`,
+/// `--replay <file>`, and `--quiet`.
+fn parse_args(args: &[String]) -> Result<CliArgs, String> {
+    let mut cli = CliArgs {
+        url: None,
+        color: None,
+        hyperlinks: false,
+        show_link_urls: false,
+        mark_external: false,
+        number_lists: false,
+        reflow: false,
+        truncate_lines: false,
+        center_preformatted: false,
+        history_cap: history::DEFAULT_HISTORY_CAP,
+        confirm_redirects: false,
+        verbose: false,
+        open_external: true,
+        open_media: true,
+        highlight: true,
+        terminal_title: true,
+        verify: None,
+        min_tls: None,
+        retries: None,
+        record: None,
+        replay: None,
+        quiet: false,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--record" || arg == "--replay" {
+            let path = args
+                .next()
+                .ok_or_else(|| format!("{arg} needs a file name"))?;
+            let path = Some(PathBuf::from(path));
+            if arg == "--record" {
+                cli.record = path;
+            } else {
+                cli.replay = path;
+            }
+            continue;
+        }
+        if arg == "--hyperlinks" {
+            cli.hyperlinks = true;
+            continue;
+        }
+        if arg == "--show-urls" {
+            cli.show_link_urls = true;
+            continue;
+        }
+        if arg == "--mark-external" {
+            cli.mark_external = true;
+            continue;
+        }
+        if arg == "--number-lists" {
+            cli.number_lists = true;
+            continue;
+        }
+        if arg == "--reflow" {
+            cli.reflow = true;
+            continue;
+        }
+        if arg == "--truncate-lines" {
+            cli.truncate_lines = true;
+            continue;
+        }
+        if arg == "--center-pre" {
+            cli.center_preformatted = true;
+            continue;
+        }
+        if arg == "--confirm-redirects" {
+            cli.confirm_redirects = true;
+            continue;
+        }
+        if arg == "--verbose" {
+            cli.verbose = true;
+            continue;
+        }
+        if arg == "--no-external" {
+            cli.open_external = false;
+            continue;
+        }
+        if arg == "--no-open-media" {
+            cli.open_media = false;
+            continue;
+        }
+        if arg == "--no-highlight" {
+            cli.highlight = false;
+            continue;
+        }
+        if arg == "--no-title" {
+            cli.terminal_title = false;
+            continue;
+        }
+        if arg == "--quiet" {
+            cli.quiet = true;
+            continue;
+        }
+        if let Some(mode) = arg.strip_prefix("--color=") {
+            cli.color = Some(
+                ColorMode::parse(mode).ok_or_else(|| format!("invalid --color value: {mode}"))?,
+            );
+        } else if let Some(cap) = arg.strip_prefix("--history-cap=") {
+            cli.history_cap = cap
+                .parse()
+                .map_err(|_| format!("invalid --history-cap value: {cap}"))?;
+        } else if let Some(mode) = arg.strip_prefix("--verify=") {
+            cli.verify = Some(
+                VerifyMode::parse(mode).ok_or_else(|| format!("invalid --verify value: {mode}"))?,
+            );
+        } else if let Some(version) = arg.strip_prefix("--min-tls=") {
+            cli.min_tls = Some(
+                TlsVersion::parse(version)
+                    .ok_or_else(|| format!("invalid --min-tls value: {version}"))?,
+            );
+        } else if let Some(retries) = arg.strip_prefix("--retries=") {
+            cli.retries = Some(
+                retries
+                    .parse()
+                    .map_err(|_| format!("invalid --retries value: {retries}"))?,
+            );
+        } else if arg.starts_with("--") {
+            return Err(format!("unknown option: {arg}"));
+        } else if cli.url.is_none() {
+            cli.url = Some(arg.clone());
+        } else {
+            return Err(format!("unexpected argument: {arg}"));
+        }
+    }
+
+    Ok(cli)
+}
+
+/// Formats the current page can be exported to.
+#[derive(Debug, PartialEq)]
+enum ExportFormat {
+    Markdown,
+    Html,
 }
 
 /// Commands parsed from the REPL prompt.
 enum Command {
     FollowLink(usize),
+    FollowLast,
     Back,
+    Reload,
+    Home,
     Go(String),
     Quit,
     Help,
+    Links,
+    Info,
+    Verbose,
+    Source,
+    Toc,
+    TocGo(usize),
+    Peek(usize),
+    Follow(String),
     Navigate(String),
     NextPage,
     PrevPage,
+    GotoPage(usize),
+    ScrollLines(isize),
+    HalfPageDown,
+    HalfPageUp,
+    Top,
+    Bottom,
+    /// Search the paged document; an empty pattern repeats the last search.
+    Search(String),
+    /// `N`: repeat the last search backwards.
+    SearchPrev,
+    Save(usize, String),
+    /// `save [--rendered] [--force] <path>`
+    SavePage {
+        path: String,
+        rendered: bool,
+        force: bool,
+    },
+    /// `upload <path> <titan-url> [token]`
+    Upload {
+        path: String,
+        url: String,
+        token: Option<String>,
+    },
+    Export(ExportFormat, String),
+    CertNew(String),
+    Bookmark,
+    Bookmarks,
+    BookmarkRemove(usize),
+    BookmarkGo(usize),
+    BookmarkExport {
+        path: String,
+        force: bool,
+    },
+    BookmarkImport(String),
+    History,
+    HistoryGo(usize),
+    AliasAdd {
+        name: String,
+        url: String,
+    },
+    AliasList,
+    AliasRemove(String),
+    Subscribe,
+    Updates,
+    UpdatesGo(usize),
     Empty,
     Unknown,
 }
 
 fn parse_command(input: &str) -> Command {
+    // A lone space scrolls, so check before trimming it away
+    if input.trim_end_matches(['\n', '\r']) == " " {
+        return Command::HalfPageDown;
+    }
+
     let trimmed = input.trim();
 
     if trimmed.is_empty() {
@@ -42,10 +378,45 @@ fn parse_command(input: &str) -> Command {
     match trimmed {
         "quit" | "q" => Command::Quit,
         "back" | "b" => Command::Back,
+        "last" | "." => Command::FollowLast,
+        "reload" | "r" => Command::Reload,
+        "home" | "h" => Command::Home,
         "help" | "?" => Command::Help,
+        "links" | "l" => Command::Links,
+        "info" => Command::Info,
+        "verbose" => Command::Verbose,
+        "source" | "view-source" => Command::Source,
+        "toc" => Command::Toc,
         "next" | "n" => Command::NextPage,
         "prev" | "p" => Command::PrevPage,
+        "N" => Command::SearchPrev,
+        "j" => Command::ScrollLines(1),
+        "k" => Command::ScrollLines(-1),
+        "d" => Command::HalfPageDown,
+        "u" => Command::HalfPageUp,
+        "top" => Command::Top,
+        "bottom" => Command::Bottom,
+        "bookmark" | "bm" => Command::Bookmark,
+        "bookmarks" => Command::Bookmarks,
+        "history" => Command::History,
+        "alias" | "alias list" => Command::AliasList,
+        "subscribe" => Command::Subscribe,
+        "updates" => Command::Updates,
         _ => {
+            if let Some(pattern) = trimmed.strip_prefix('/') {
+                return Command::Search(pattern.trim().to_string());
+            }
+            if let Some(args) = trimmed
+                .strip_prefix("bookmark ")
+                .or_else(|| trimmed.strip_prefix("bm "))
+            {
+                return parse_bookmark_command(args.trim());
+            }
+
+            if let Some(args) = trimmed.strip_prefix("alias ") {
+                return parse_alias_command(args.trim());
+            }
+
             if let Some(url) = trimmed.strip_prefix("go ") {
                 let url = url.trim();
                 if url.is_empty() {
@@ -53,7 +424,64 @@ fn parse_command(input: &str) -> Command {
                 } else {
                     Command::Go(url.to_string())
                 }
-            } else if trimmed.starts_with("gemini://") {
+            } else if let Some(n) = trimmed.strip_prefix("history go ") {
+                match n.trim().parse::<usize>() {
+                    Ok(n) => Command::HistoryGo(n),
+                    Err(_) => Command::Unknown,
+                }
+            } else if let Some(n) = trimmed.strip_prefix("updates go ") {
+                match n.trim().parse::<usize>() {
+                    Ok(n) => Command::UpdatesGo(n),
+                    Err(_) => Command::Unknown,
+                }
+            } else if let Some(n) = trimmed.strip_prefix("peek ") {
+                match n.trim().parse::<usize>() {
+                    Ok(n) => Command::Peek(n),
+                    Err(_) => Command::Unknown,
+                }
+            } else if let Some(text) = trimmed.strip_prefix("follow ") {
+                Command::Follow(text.trim().to_string())
+            } else if let Some(n) = trimmed.strip_prefix("toc go ") {
+                match n.trim().parse::<usize>() {
+                    Ok(n) => Command::TocGo(n),
+                    Err(_) => Command::Unknown,
+                }
+            } else if let Some(n) = trimmed.strip_prefix("page ") {
+                match n.trim().parse::<usize>() {
+                    Ok(n) => Command::GotoPage(n),
+                    Err(_) => Command::Unknown,
+                }
+            } else if let Some(args) = trimmed.strip_prefix("export ") {
+                let (format, path) = args.trim().split_once(char::is_whitespace).unzip();
+                let format = match format {
+                    Some("md") => ExportFormat::Markdown,
+                    Some("html") => ExportFormat::Html,
+                    _ => return Command::Unknown,
+                };
+                match path.map(str::trim) {
+                    Some(path) if !path.is_empty() => Command::Export(format, path.to_string()),
+                    _ => Command::Unknown,
+                }
+            } else if let Some(host) = trimmed.strip_prefix("cert new ") {
+                let host = host.trim();
+                if host.is_empty() {
+                    Command::Unknown
+                } else {
+                    Command::CertNew(host.to_string())
+                }
+            } else if let Some(args) = trimmed.strip_prefix("upload ") {
+                let mut args = args.split_whitespace();
+                match (args.next(), args.next(), args.next(), args.next()) {
+                    (Some(path), Some(url), token, None) => Command::Upload {
+                        path: path.to_string(),
+                        url: url.to_string(),
+                        token: token.map(str::to_string),
+                    },
+                    _ => Command::Unknown,
+                }
+            } else if let Some(args) = trimmed.strip_prefix("save ") {
+                parse_save_command(args)
+            } else if trimmed.contains("://") {
                 Command::Navigate(trimmed.to_string())
             } else if let Ok(n) = trimmed.parse::<usize>() {
                 Command::FollowLink(n)
@@ -64,30 +492,204 @@ fn parse_command(input: &str) -> Command {
     }
 }
 
+/// Parse the arguments of `save`: `<n> <path>` downloads link n, while
+/// `[--rendered] [--force] <path>` writes out the current page.
+fn parse_save_command(args: &str) -> Command {
+    let mut rendered = false;
+    let mut force = false;
+    let mut rest = args.trim();
+    loop {
+        let (word, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        match word {
+            "--rendered" => rendered = true,
+            "--force" => force = true,
+            _ => break,
+        }
+        rest = after.trim_start();
+    }
+
+    if let Some((n, path)) = rest.split_once(char::is_whitespace) {
+        return match n.parse::<usize>() {
+            Ok(n) if !rendered && !force => Command::Save(n, path.trim().to_string()),
+            _ => Command::Unknown,
+        };
+    }
+    // A lone number is a link missing its destination, not a file name
+    if rest.is_empty() || rest.parse::<usize>().is_ok() {
+        return Command::Unknown;
+    }
+    Command::SavePage {
+        path: rest.to_string(),
+        rendered,
+        force,
+    }
+}
+
+/// Parse the arguments of `bookmark <sub> <n>` / `bm <sub> <n>`, or of
+/// `bookmark export [--force] <path>` and `bookmark import <path>`.
+fn parse_bookmark_command(args: &str) -> Command {
+    let Some((sub, n)) = args.split_once(char::is_whitespace) else {
+        return Command::Unknown;
+    };
+    match sub {
+        "export" => {
+            let path = n.trim();
+            return match path.strip_prefix("--force") {
+                Some(path) if path.starts_with(char::is_whitespace) => Command::BookmarkExport {
+                    path: path.trim().to_string(),
+                    force: true,
+                },
+                _ => Command::BookmarkExport {
+                    path: path.to_string(),
+                    force: false,
+                },
+            };
+        }
+        "import" => return Command::BookmarkImport(n.trim().to_string()),
+        _ => {}
+    }
+    let Ok(n) = n.trim().parse::<usize>() else {
+        return Command::Unknown;
+    };
+    match sub {
+        "rm" => Command::BookmarkRemove(n),
+        "go" => Command::BookmarkGo(n),
+        _ => Command::Unknown,
+    }
+}
+
+fn parse_alias_command(args: &str) -> Command {
+    let mut args = args.split_whitespace();
+    match (args.next(), args.next(), args.next(), args.next()) {
+        (Some("add"), Some(name), Some(url), None) => Command::AliasAdd {
+            name: name.to_string(),
+            url: url.to_string(),
+        },
+        (Some("rm"), Some(name), None, None) => Command::AliasRemove(name.to_string()),
+        _ => Command::Unknown,
+    }
+}
+
 fn navigate(state: &mut BrowserState, url: Url) {
-    match gemini::fetch_with_redirects(&url) {
-        Ok((response, final_url)) => {
-            handle_response(state, response, final_url);
+    load(state, url, true, 0);
+}
+
+/// Run `fetch` with a copy of the client on a worker thread, so Ctrl-C
+/// (which raises `state.cancel`) gets back to the prompt straight away
+/// rather than once a slow server's read times out. The abandoned worker
+/// sees the flag at its next read and stops on its own.
+fn cancellable<T: Send + 'static>(
+    state: &BrowserState,
+    fetch: impl FnOnce(&GeminiClient) -> Result<T, GeminiError> + Send + 'static,
+) -> Result<T, GeminiError> {
+    state.cancel.store(false, Ordering::Relaxed);
+    let client = state.client.clone();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(fetch(&client));
+    });
+
+    loop {
+        match rx.recv_timeout(CANCEL_POLL) {
+            Ok(result) => return result,
+            Err(RecvTimeoutError::Timeout) => {
+                if state.cancel.load(Ordering::Relaxed) && !state.confirming.load(Ordering::Relaxed)
+                {
+                    return Err(GeminiError::Cancelled);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::other("fetch thread panicked").into());
+            }
+        }
+    }
+}
+
+/// Fetch and display `url`. When `push_history` is false the page replaces
+/// the current one without recording it in history (used by back and reload).
+/// The page opens scrolled down to line `scroll`.
+fn load(state: &mut BrowserState, url: Url, push_history: bool, scroll: usize) {
+    let url = state.moved.resolve(&url);
+    let requested = url.clone();
+    match cancellable(state, move |client| client.fetch_with_moves(&url)) {
+        Ok((response, final_url, moves)) => {
+            record_moves(state, &moves);
+            state.favicons.request(&state.client, &final_url);
+            handle_response(state, response, final_url, push_history, scroll);
+        }
+        Err(GeminiError::CertificateChanged {
+            host,
+            pinned,
+            presented,
+        }) => {
+            eprintln!("Warning: the certificate for {host} has changed.");
+            eprintln!("  Pinned:    {pinned}");
+            eprintln!("  Presented: {presented}");
+            match ask_certificate_choice() {
+                CertificateChoice::Trust => {
+                    if let Err(e) = state.client.pin_certificate(&host, &presented) {
+                        eprintln!("Error: could not save known_hosts: {e}");
+                        return;
+                    }
+                    load(state, requested, push_history, scroll);
+                }
+                CertificateChoice::Once => {
+                    let once = state.client.accepting_once(&host, &presented);
+                    let client = std::mem::replace(&mut state.client, once);
+                    load(state, requested, push_history, scroll);
+                    state.client = client;
+                }
+                CertificateChoice::Reject => println!("Not connecting to {host}."),
+            }
         }
+        // Declined when asked
+        Err(GeminiError::CrossHostRedirect { .. }) => {}
+        Err(GeminiError::Cancelled) => println!("Cancelled."),
         Err(e) => {
             eprintln!("Error: {e}");
         }
     }
 }
 
-fn handle_response(state: &mut BrowserState, response: GeminiResponse, url: Url) {
-    let first_digit = response.status / 10;
+/// Remember permanent redirects so later visits skip them, and point any
+/// bookmarks for the old URLs at the new ones.
+fn record_moves(state: &mut BrowserState, moves: &[(Url, Url)]) {
+    for (from, to) in moves {
+        state.moved.record(from, to);
+        match state.bookmarks.replace_url(from.as_str(), to.as_str()) {
+            Ok(0) => {}
+            Ok(_) => println!("{from} has moved; bookmark updated to {to}."),
+            Err(e) => eprintln!("Warning: could not update bookmarks: {e}"),
+        }
+    }
+}
+
+fn handle_response(
+    state: &mut BrowserState,
+    response: GeminiResponse,
+    url: Url,
+    push_history: bool,
+    scroll: usize,
+) {
+    state.last_status = Some((response.status, response.meta.clone()));
+    state.certificate = response.certificate.clone();
+    state.last_metrics = Some(response.metrics);
+    let color = state.render_options.color;
 
-    match first_digit {
-        1 => {
+    match response.class() {
+        Some(StatusClass::Input) => {
             // INPUT: prompt the user
-            println!("{}", response.meta);
+            println!("{}", status_color(response.status, &response.meta, color));
             print!("Input: ");
             let _ = io::stdout().flush();
 
-            let stdin = io::stdin();
-            let mut input = String::new();
-            if stdin.lock().read_line(&mut input).is_ok() {
+            let read = if response.is_sensitive_input() {
+                read_sensitive_line()
+            } else {
+                let mut input = String::new();
+                io::stdin().lock().read_line(&mut input).map(|_| input)
+            };
+            if let Ok(input) = read {
                 let input = input.trim_end_matches('\n').trim_end_matches('\r');
                 let mut new_url = url;
                 let encoded: String = url::form_urlencoded::Serializer::new(String::new())
@@ -97,60 +699,120 @@ fn handle_response(state: &mut BrowserState, response: GeminiResponse, url: Url)
                 navigate(state, new_url);
             }
         }
-        2 => {
-            // SUCCESS
-            let mime = if response.meta.is_empty() {
-                "text/gemini"
-            } else {
-                &response.meta
-            };
+        Some(StatusClass::Success) => {
+            let canonical = url_utils::canonicalize(&url);
+            state.visited.insert(canonical.to_string());
+            if push_history {
+                if let Err(e) = state.history_log.record(canonical.as_str()) {
+                    eprintln!("Warning: could not save history: {e}");
+                }
+            }
+            let mime = mime::parse(&response.meta);
+            leave_source(state);
+            // Where the page being replaced was scrolled to, for `back`
+            let left_at = state.pager.as_ref().map_or(0, pager::Pager::offset);
 
-            if mime.starts_with("text/gemini") {
+            if mime.is("text/gemini") {
                 let body_bytes = response.body.unwrap_or_default();
-                let body_str = String::from_utf8_lossy(&body_bytes);
+                let (body_str, bad_byte) = mime.decode_checked(&body_bytes);
+                if let Some(offset) = bad_byte.filter(|_| !state.quiet) {
+                    eprintln!(
+                        "Warning: page is not valid UTF-8 (first bad byte at offset {offset}); \
+                         shown with replacement characters"
+                    );
+                }
                 let parsed = parser::parse_gemini(&body_str);
-                let (output_lines, links) = render::render(&parsed);
-
-                // Determine pagination
-                let height = pager::terminal_height();
-                let page_height = std::cmp::max(height.saturating_sub(1), 1);
-
-                if output_lines.len() > page_height {
-                    let pg = pager::Pager::new(output_lines, page_height);
-                    pg.display_current_page();
-                    state.pager = Some(pg);
-                } else {
-                    for line in &output_lines {
-                        println!("{line}");
-                    }
-                    state.pager = None;
+                state.title = parsed.iter().find_map(|(_, line)| match line {
+                    parser::GeminiLine::Heading { level: 1, text } => Some(text.clone()),
+                    _ => None,
+                });
+                if state.terminal_title {
+                    let title = state.title.as_deref();
+                    set_terminal_title(title.unwrap_or(render::DEFAULT_TERMINAL_TITLE));
                 }
+                let page = render::render(
+                    &parsed,
+                    Some(&url),
+                    &state.visited,
+                    state.wrap_width.unwrap_or_else(pager::terminal_width),
+                    &state.render_options,
+                    &state.theme,
+                );
+                let mut heading_offsets = page.heading_offsets;
+                show_lines_at(state, page.lines, scroll, &mut heading_offsets);
+                state.source = Some(body_str);
+                state.body = Some((mime, body_bytes));
+                state.document = parsed;
+                state.heading_offsets = heading_offsets;
 
                 // Update state
-                if let Some(old_url) = state.current_url.take() {
-                    state.history.push(old_url);
+                if let Some(old_url) = state.current_url.replace(url) {
+                    if push_history {
+                        state.history.push((old_url, left_at));
+                    }
                 }
-                state.current_url = Some(url);
-                state.links = links;
+                state.links = page.links;
             } else {
-                println!("[Received {mime}, not rendering]");
+                let body = response.body.unwrap_or_default();
+                if state.open_media && viewer::Viewer::handles(&mime) {
+                    match state.viewer.open(&mime, &body) {
+                        Ok(_) => println!("[Opened {} in the viewer]", mime.essence),
+                        Err(e) => eprintln!("Error: cannot open {}: {e}", mime.essence),
+                    }
+                } else {
+                    println!("[Received {}, not rendering]", mime.essence);
+                }
                 // Still update navigation state
-                if let Some(old_url) = state.current_url.take() {
-                    state.history.push(old_url);
+                if let Some(old_url) = state.current_url.replace(url) {
+                    if push_history {
+                        state.history.push((old_url, left_at));
+                    }
                 }
-                state.current_url = Some(url);
+                state.title = None;
+                state.source = None;
+                state.body = Some((mime, body));
+                state.document.clear();
+                state.heading_offsets.clear();
                 state.links.clear();
                 state.pager = None;
             }
         }
-        4 => {
-            eprintln!("Temporary failure ({}): {}", response.status, response.meta);
+        Some(StatusClass::TemporaryFailure) => match gemini::slow_down_delay(&response.meta) {
+            Some(delay) if response.is_slow_down() => eprintln!(
+                "{}",
+                status_color(
+                    44,
+                    &format!("Slow down (44): try again in {} seconds.", delay.as_secs()),
+                    color
+                )
+            ),
+            _ => eprintln!(
+                "{}",
+                status_color(
+                    response.status,
+                    &format!("Temporary failure ({}): {}", response.status, response.meta),
+                    color
+                )
+            ),
+        },
+        Some(StatusClass::PermanentFailure) => {
+            eprintln!(
+                "{}",
+                status_color(
+                    response.status,
+                    &format!("Permanent failure ({}): {}", response.status, response.meta),
+                    color
+                )
+            );
         }
-        5 => {
-            eprintln!("Permanent failure ({}): {}", response.status, response.meta);
-        }
-        6 => {
-            eprintln!("Client certificates are not supported.");
+        Some(StatusClass::ClientCertRequired) => {
+            eprintln!(
+                "Client certificate required ({}): {}",
+                response.status, response.meta
+            );
+            if let Some(host) = url.host_str() {
+                eprintln!("Use 'cert new {host}' to create an identity for this capsule.");
+            }
         }
         _ => {
             eprintln!("Unexpected status: {} {}", response.status, response.meta);
@@ -158,96 +820,1359 @@ fn handle_response(state: &mut BrowserState, response: GeminiResponse, url: Url)
     }
 }
 
+/// Widest page label shown in the prompt, in columns.
+const PROMPT_LABEL_WIDTH: usize = 30;
+
+/// Build the REPL prompt, e.g. `🐱 [Page Title 2/5] > `: the host's
+/// favicon, the page's h1 title or else its host (and port, if not 1965),
+/// plus the page indicator
+/// while paginating.
+fn prompt(state: &BrowserState) -> String {
+    let label = state
+        .title
+        .clone()
+        .or_else(|| state.current_url.as_ref().map(url_utils::display_authority));
+    let page = state
+        .pager
+        .as_ref()
+        .filter(|pg| pg.needs_pagination())
+        .map(|pg| (pg.current_page(), pg.total_pages()));
+    let prompt = format_prompt(label.as_deref(), page);
+    match state
+        .current_url
+        .as_ref()
+        .and_then(|url| state.favicons.get(url))
+    {
+        Some(favicon) => format!("{favicon} {prompt}"),
+        None => prompt,
+    }
+}
+
+fn format_prompt(label: Option<&str>, page: Option<(usize, usize)>) -> String {
+    let label = label
+        .map(|label| {
+            label
+                .chars()
+                .filter(|c| !c.is_control())
+                .collect::<String>()
+        })
+        .filter(|label| !label.trim().is_empty())
+        .map(|label| text::truncate(label.trim(), PROMPT_LABEL_WIDTH));
+    match (label, page) {
+        (Some(label), Some((current, total))) => format!("[{label} {current}/{total}] > "),
+        (Some(label), None) => format!("[{label}] > "),
+        (None, Some((current, total))) => format!("[{current}/{total}] > "),
+        (None, None) => "> ".to_string(),
+    }
+}
+
+/// What to do about a host whose certificate no longer matches its pin.
+#[derive(Debug, PartialEq)]
+enum CertificateChoice {
+    /// Pin the new certificate and connect.
+    Trust,
+    /// Connect this time, keeping the old pin.
+    Once,
+    /// Don't connect.
+    Reject,
+}
+
+/// Read a `CertificateChoice` from an answer, rejecting anything unclear.
+fn parse_certificate_choice(answer: &str) -> CertificateChoice {
+    match answer.trim().to_ascii_lowercase().as_str() {
+        "t" | "trust" => CertificateChoice::Trust,
+        "o" | "once" => CertificateChoice::Once,
+        _ => CertificateChoice::Reject,
+    }
+}
+
+/// Ask on stdin what to do about a changed certificate. Anything but
+/// trust or once means reject.
+fn ask_certificate_choice() -> CertificateChoice {
+    print!("[t]rust the new certificate, connect [o]nce, or [r]eject? [t/o/R] ");
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return CertificateChoice::Reject;
+    }
+    parse_certificate_choice(&answer)
+}
+
+/// Ask a yes/no question on stdin. Anything but `y` or `yes` means no.
+fn confirm(question: &str) -> bool {
+    print!("{question} [y/N] ");
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+/// Disables terminal echo on stdin until dropped, restoring the saved
+/// settings.
+#[cfg(unix)]
+struct EchoGuard(Option<libc::termios>);
+
+#[cfg(unix)]
+impl EchoGuard {
+    fn disable() -> Self {
+        use libc::{tcgetattr, tcsetattr, termios, ECHO, STDIN_FILENO, TCSANOW};
+        let mut saved: termios = unsafe { std::mem::zeroed() };
+        if unsafe { tcgetattr(STDIN_FILENO, &mut saved) } != 0 {
+            // Not a terminal; nothing to hide
+            return Self(None);
+        }
+        let mut quiet = saved;
+        quiet.c_lflag &= !ECHO;
+        if unsafe { tcsetattr(STDIN_FILENO, TCSANOW, &quiet) } != 0 {
+            return Self(None);
+        }
+        Self(Some(saved))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for EchoGuard {
+    fn drop(&mut self) {
+        if let Some(saved) = self.0 {
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &saved) };
+        }
+    }
+}
+
+/// Read a line for status 11 (SENSITIVE INPUT) without echoing it.
+/// Echo is restored when the read finishes, whether or not it succeeded.
+fn read_sensitive_line() -> io::Result<String> {
+    #[cfg(unix)]
+    let _echo = EchoGuard::disable();
+
+    let mut input = String::new();
+    let result = io::stdin().lock().read_line(&mut input);
+    // The Enter key was not echoed either
+    println!();
+    result.map(|_| input)
+}
+
+/// Print `lines`, through the pager if they don't fit on one screen. Lines
+/// wider than the terminal count as the rows they wrap onto. When stdout
+/// isn't a terminal everything is printed at once.
+fn show_lines(state: &mut BrowserState, lines: Vec<String>) {
+    show_lines_at(state, lines, 0, &mut []);
+}
+
+/// Toggle `source`: show the current page's gemtext, keeping the rendered
+/// page's pager aside, or go back to the rendered page.
+fn toggle_source(state: &mut BrowserState) {
+    if leave_source(state) {
+        match state.pager {
+            Some(ref pg) => pg.display_current_page(),
+            None => println!("Back to the rendered page."),
+        }
+        return;
+    }
+    let Some(source) = state.source.clone() else {
+        println!("No gemtext source for this page.");
+        return;
+    };
+    state.rendered_pager = state.pager.take();
+    state.viewing_source = true;
+    show_lines(state, source.lines().map(String::from).collect());
+}
+
+/// Put the rendered page's pager back if `source` is shown. Returns whether
+/// it was.
+fn leave_source(state: &mut BrowserState) -> bool {
+    if !std::mem::take(&mut state.viewing_source) {
+        return false;
+    }
+    state.pager = state.rendered_pager.take();
+    true
+}
+
+/// `show_lines`, with the pager starting at row `offset` (clamped to the
+/// document) rather than the top.
+///
+/// When paginating, wide lines (such as preformatted text) are folded to
+/// the wrap width so pages count the rows they fill on screen, and each of
+/// `heading_offsets` is moved to the row its heading now starts on, for
+/// `toc go`. Output that isn't paged is left unfolded.
+fn show_lines_at(
+    state: &mut BrowserState,
+    lines: Vec<String>,
+    offset: usize,
+    heading_offsets: &mut [usize],
+) {
+    let page_height = render::stdout_is_tty().then(pager::page_height);
+    let lines = if page_height.is_some() {
+        let width = state.wrap_width.unwrap_or_else(pager::terminal_width);
+        pager::fold_lines(lines, width, heading_offsets)
+    } else {
+        lines
+    };
+    // Nothing useful to do if stdout has gone away
+    state.pager = pager::show_to(
+        lines,
+        page_height,
+        offset,
+        !state.quiet,
+        &mut io::stdout().lock(),
+    )
+    .unwrap_or(None);
+}
+
+/// Schemes without `//` that typed input may use for an external link.
+const OPAQUE_SCHEMES: &[&str] = &["mailto:", "tel:", "xmpp:", "magnet:"];
+
+/// If `raw` (resolved against `base`, when there is one) uses a scheme other
+/// than gemini, return it so it can be offered to the system handler.
+///
+/// Typed input (no `base`) only counts as external when it has `://` or
+/// starts with one of `OPAQUE_SCHEMES`, since `example.com:1965/` would
+/// otherwise parse as a URL with the scheme `example.com`.
+fn external_url(base: Option<&Url>, raw: &str) -> Option<Url> {
+    let raw = raw.trim();
+    let url = match base {
+        Some(base) => base.join(raw).ok()?,
+        None => {
+            let lower = raw.to_ascii_lowercase();
+            if !raw.contains("://") && !OPAQUE_SCHEMES.iter().any(|s| lower.starts_with(s)) {
+                return None;
+            }
+            Url::parse(raw).ok()?
+        }
+    };
+    (url.scheme() != "gemini").then_some(url)
+}
+
+/// Where `go` takes its argument.
+#[derive(Debug, PartialEq)]
+enum GoTarget {
+    /// A gemini URL to load.
+    Load(Url),
+    /// A link for the system handler.
+    External(Url),
+}
+
+/// Expand an alias in `raw` and decide whether `go` loads it or offers it
+/// to the system handler.
+fn go_target(aliases: &aliases::Aliases, raw: &str) -> Result<GoTarget, GeminiError> {
+    let raw = aliases.expand(raw).unwrap_or(raw);
+    if let Some(url) = external_url(None, raw) {
+        return Ok(GoTarget::External(url));
+    }
+    url_utils::parse_gemini_url(raw).map(GoTarget::Load)
+}
+
+/// Offer to open a non-gemini URL with `xdg-open` (or `open` on macOS),
+/// showing the full URL first. Never opens without confirmation.
+fn offer_external(state: &BrowserState, url: &Url) {
+    if !state.open_external {
+        println!("Not opening {url}: external links are disabled.");
+        return;
+    }
+    if !confirm(&format!(
+        "{url} is a {} link. Open it with the system handler?",
+        url.scheme()
+    )) {
+        return;
+    }
+
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    match std::process::Command::new(opener)
+        .arg(url.as_str())
+        .status()
+    {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("Error: {opener} exited with {status}"),
+        Err(e) => eprintln!("Error: cannot run {opener}: {e}"),
+    }
+}
+
+/// Follow link number `n` on the current page, offering external links to
+/// the system opener.
+fn follow_link(state: &mut BrowserState, n: usize) {
+    let external = n
+        .checked_sub(1)
+        .and_then(|i| state.links.get(i))
+        .and_then(|(raw, _)| external_url(state.current_url.as_ref(), raw));
+    if let Some(url) = external {
+        offer_external(state, &url);
+        return;
+    }
+    match resolve_link(state, n) {
+        Some(Ok(url)) => navigate(state, url),
+        Some(Err(e)) => eprintln!("Error: {e}"),
+        None => println!("Invalid link number."),
+    }
+}
+
+/// Numbers (from 1) of the links whose label contains `text`, ignoring case.
+fn links_matching(links: &[(String, String)], text: &str) -> Vec<usize> {
+    let text = text.to_lowercase();
+    links
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, label))| label.to_lowercase().contains(&text))
+        .map(|(i, _)| i + 1)
+        .collect()
+}
+
+/// Follow the one link whose label contains `text`, or list the candidates
+/// when there are several.
+fn follow_label(state: &mut BrowserState, text: &str) {
+    match links_matching(&state.links, text)[..] {
+        [] => println!("No link label contains {text:?}."),
+        [n] => follow_link(state, n),
+        ref matches => {
+            println!("{} links match {text:?}:", matches.len());
+            for &n in matches {
+                println!("[{n}] {}", state.links[n - 1].1);
+            }
+            println!("Type a number to follow one.");
+        }
+    }
+}
+
+/// Resolve link number `n` on the current page to an absolute URL.
+/// Returns None if `n` is not a valid link number.
+fn resolve_link(state: &BrowserState, n: usize) -> Option<Result<Url, GeminiError>> {
+    if n < 1 || n > state.links.len() {
+        return None;
+    }
+    let (link_url, _) = &state.links[n - 1];
+    Some(match state.current_url.as_ref() {
+        Some(base) => url_utils::resolve_url(base, link_url),
+        // No current URL to resolve against, try parsing as absolute
+        None => url_utils::parse_gemini_url(link_url),
+    })
+}
+
+/// Fetch `url` and stream its body to the file at `path`. The body goes to
+/// a temporary file next to it first, which only replaces `path` once the
+/// whole body has arrived, so a failed fetch leaves an existing file alone.
+fn save_to_file(state: &BrowserState, url: &Url, path: &str) {
+    let target = Path::new(path);
+    let Some(name) = target.file_name() else {
+        eprintln!("Error: {path} is not a file name");
+        return;
+    };
+    let partial = target.with_file_name(format!(
+        ".{}.{}.part",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+    let file = match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&partial)
+    {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Error: cannot create {path}: {e}");
+            return;
+        }
+    };
+    let mut writer = io::BufWriter::new(file);
+
+    let url = url.clone();
+    let fetched = cancellable(state, move |client| {
+        let fetched = client.fetch_to_writer(&url, &mut writer)?;
+        writer.flush().map_err(GeminiError::from)?;
+        Ok(fetched)
+    });
+    let saved = match fetched {
+        Ok((response, written)) if response.class() == Some(StatusClass::Success) => {
+            match std::fs::rename(&partial, target) {
+                Ok(()) => {
+                    println!("Saved {written} bytes ({}) to {path}", response.meta);
+                    true
+                }
+                Err(e) => {
+                    eprintln!("Error: cannot write {path}: {e}");
+                    false
+                }
+            }
+        }
+        Ok((response, _)) => {
+            eprintln!(
+                "Not saved: server responded {} {}",
+                response.status, response.meta
+            );
+            false
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
+            false
+        }
+    };
+    if !saved {
+        let _ = std::fs::remove_file(&partial);
+    }
+}
+
+/// Write the current page to `path`: the body exactly as received, or with
+/// `rendered`, the page as displayed minus colors and styling. An existing
+/// file is only replaced when `force` is set.
+fn save_page(state: &BrowserState, path: &str, rendered: bool, force: bool) {
+    let Some((mime, body)) = &state.body else {
+        println!("No page to save.");
+        return;
+    };
+    let rendered_text;
+    let contents = if rendered {
+        if !mime.is("text/gemini") {
+            println!("Only gemtext pages have rendered output; use save <path>.");
+            return;
+        }
+        let page = render::render(
+            &state.document,
+            state.current_url.as_ref(),
+            &state.visited,
+            state.wrap_width.unwrap_or_else(pager::terminal_width),
+            &state.render_options,
+            &state.theme,
+        );
+        rendered_text = page
+            .lines
+            .iter()
+            .map(|line| text::strip_ansi(line) + "\n")
+            .collect::<String>();
+        rendered_text.as_bytes()
+    } else {
+        body.as_slice()
+    };
+
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(!force)
+        .create(force)
+        .truncate(force)
+        .open(path);
+    let result = file.and_then(|mut file| file.write_all(contents));
+    match result {
+        Ok(()) => println!(
+            "Saved {} bytes ({}) to {path}",
+            contents.len(),
+            mime.essence
+        ),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            eprintln!("Error: {path} already exists; use save --force <path> to replace it")
+        }
+        Err(e) => eprintln!("Error: cannot write {path}: {e}"),
+    }
+}
+
+/// Upload the file at `path` to a titan:// URL and show the outcome. A
+/// redirect (the usual reply) is followed like any other navigation.
+fn upload_file(state: &mut BrowserState, path: &str, raw_url: &str, token: Option<&str>) {
+    let url = match titan::parse_titan_url(raw_url) {
+        Ok(url) => url,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return;
+        }
+    };
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Error: cannot read {path}: {e}");
+            return;
+        }
+    };
+    let mime = titan::guess_mime(std::path::Path::new(path));
+    let len = data.len();
+
+    let target = url.clone();
+    let token = token.map(str::to_string);
+    let result = cancellable(state, move |client| {
+        titan::upload(client, &target, &data, mime, token.as_deref())
+    });
+    match result {
+        Ok(response) if response.class() == Some(StatusClass::Redirect) => {
+            println!("Uploaded {len} bytes ({mime}).");
+            match gemini::resolve_redirect(&titan::gemini_url(&url), &response.meta) {
+                Ok(target) => navigate(state, target),
+                Err(e) => eprintln!("Error: {e}"),
+            }
+        }
+        Ok(response) => handle_response(state, response, titan::gemini_url(&url), true, 0),
+        Err(GeminiError::Cancelled) => println!("Cancelled."),
+        Err(e) => eprintln!("Error: {e}"),
+    }
+}
+
+/// Bookmark the current page, titled by its first heading or its URL.
+fn add_bookmark(state: &mut BrowserState) {
+    let Some(url) = state.current_url.as_ref() else {
+        println!("No page to bookmark.");
+        return;
+    };
+    let title = state
+        .title
+        .clone()
+        .unwrap_or_else(|| short_url(url.as_str()));
+    match state.bookmarks.add(url.as_str(), &title) {
+        Ok(()) => println!("Bookmarked {title}"),
+        Err(e) => eprintln!("Error: could not save bookmarks: {e}"),
+    }
+}
+
+fn print_aliases(aliases: &aliases::Aliases) {
+    let mut entries = aliases.list().peekable();
+    if entries.peek().is_none() {
+        println!("No aliases.");
+        return;
+    }
+    for (name, url) in entries {
+        println!("{name} -> {url}");
+    }
+}
+
+fn print_bookmarks(bookmarks: &Bookmarks) {
+    if bookmarks.list().is_empty() {
+        println!("No bookmarks.");
+        return;
+    }
+    for (i, bookmark) in bookmarks.list().iter().enumerate() {
+        println!("[{}] {} - {}", i + 1, bookmark.title, bookmark.url);
+    }
+}
+
+/// Subscribe to the current page as a feed.
+fn subscribe(state: &mut BrowserState) {
+    let Some(url) = state.current_url.as_ref() else {
+        println!("No page to subscribe to.");
+        return;
+    };
+    match state.subscriptions.subscribe(url.as_str()) {
+        Ok(true) => println!("Subscribed to {url}"),
+        Ok(false) => println!("Already subscribed to {url}"),
+        Err(e) => eprintln!("Error: could not save subscriptions: {e}"),
+    }
+}
+
+/// Fetch every subscribed feed and list the entries newer than those seen
+/// last time, numbered for `updates go`.
+fn check_updates(state: &mut BrowserState) {
+    if state.subscriptions.list().is_empty() {
+        println!("No subscriptions. Use 'subscribe' on a feed page.");
+        return;
+    }
+    state.updates.clear();
+    for index in 0..state.subscriptions.list().len() {
+        let subscription = state.subscriptions.list()[index].clone();
+        let entries = match fetch_feed(state, &subscription.url) {
+            Ok(entries) => entries,
+            Err(_) if state.cancel.load(Ordering::Relaxed) => {
+                println!("Cancelled.");
+                return;
+            }
+            Err(e) => {
+                eprintln!("Warning: cannot check {}: {e}", subscription.url);
+                continue;
+            }
+        };
+        let new = subscriptions::new_entries(entries, &subscription);
+        if let Err(e) = state.subscriptions.mark_seen(index, &new) {
+            eprintln!("Error: could not save subscriptions: {e}");
+        }
+        state.updates.extend(new);
+    }
+
+    if state.updates.is_empty() {
+        println!("No new items.");
+        return;
+    }
+    for (i, entry) in state.updates.iter().enumerate() {
+        println!("[{}] {} {} - {}", i + 1, entry.date, entry.title, entry.url);
+    }
+}
+
+/// Fetch a feed page and extract its dated entries. Ctrl-C abandons the
+/// fetch, leaving `state.cancel` raised.
+fn fetch_feed(
+    state: &BrowserState,
+    raw_url: &str,
+) -> Result<Vec<subscriptions::FeedEntry>, String> {
+    let url = url_utils::parse_gemini_url(raw_url).map_err(|e| e.to_string())?;
+    let (response, final_url) = cancellable(state, move |client| client.fetch_with_redirects(&url))
+        .map_err(|e| e.to_string())?;
+    if response.class() != Some(StatusClass::Success) {
+        return Err(format!(
+            "server responded {} {}",
+            response.status, response.meta
+        ));
+    }
+    let mime = mime::parse(&response.meta);
+    if !mime.is("text/gemini") {
+        return Err(format!("not a gemtext feed ({})", mime.essence));
+    }
+    let body = mime.decode(&response.body.unwrap_or_default());
+    Ok(subscriptions::parse_feed(&body, &final_url))
+}
+
+/// Move the pager with `scroll` and redisplay, if there is a paged document.
+fn scroll_pager(state: &mut BrowserState, scroll: impl FnOnce(&mut pager::Pager)) {
+    match state.pager.as_mut() {
+        Some(pg) => {
+            scroll(pg);
+            pg.display_current_page();
+        }
+        None => println!("No page to scroll."),
+    }
+}
+
+/// The prompt's line editor, with earlier sessions' commands loaded from
+/// `history_path`.
+fn line_editor(
+    history_path: Option<&Path>,
+) -> rustyline::Result<Editor<completion::Completions, FileHistory>> {
+    let config = rustyline::Config::builder()
+        .max_history_size(MAX_COMMAND_HISTORY)?
+        .history_ignore_dups(true)?
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor = Editor::with_config(config)?;
+    if let Some(path) = history_path.filter(|path| path.exists()) {
+        if let Err(e) = editor.load_history(path) {
+            eprintln!("Warning: cannot read command history: {e}");
+        }
+    }
+    Ok(editor)
+}
+
+/// Add a command to the history and append it to `history_path`. Blank
+/// lines and immediate repeats are skipped.
+fn remember_command(
+    editor: &mut Editor<completion::Completions, FileHistory>,
+    history_path: Option<&Path>,
+    line: &str,
+) {
+    let line = history_entry(line);
+    if line.trim().is_empty() || !editor.add_history_entry(line).unwrap_or(false) {
+        return;
+    }
+    let Some(path) = history_path else {
+        return;
+    };
+    let saved = match path.parent() {
+        Some(dir) => std::fs::create_dir_all(dir).map_err(ReadlineError::from),
+        None => Ok(()),
+    }
+    .and_then(|()| editor.append_history(path));
+    if let Err(e) = saved {
+        eprintln!("Warning: cannot save command history: {e}");
+    }
+}
+
+/// `line` as kept in the command history: an `upload` loses its token, so
+/// the token is never written to disk.
+fn history_entry(line: &str) -> String {
+    match parse_command(line) {
+        Command::Upload {
+            path,
+            url,
+            token: Some(_),
+        } => format!("upload {path} {url}"),
+        _ => line.to_string(),
+    }
+}
+
+/// Fit the pager to the terminal if `resized` was set since the last call.
+/// Returns whether there was a pager to resize.
+fn resize_pager(state: &mut BrowserState, resized: &AtomicBool) -> bool {
+    if !resized.swap(false, Ordering::Relaxed) {
+        return false;
+    }
+    if let Some(ref mut pg) = state.rendered_pager {
+        pg.set_page_height(pager::page_height());
+    }
+    match state.pager {
+        Some(ref mut pg) => {
+            pg.set_page_height(pager::page_height());
+            true
+        }
+        None => false,
+    }
+}
+
+/// Search the pager for `pattern` from the top line on screen and scroll to
+/// the first match. An empty pattern repeats the last search, starting below
+/// the current match, or above it when `backward`.
+fn search_page(state: &mut BrowserState, pattern: String, backward: bool) {
+    let repeat = pattern.is_empty();
+    let pattern = if repeat {
+        match &state.last_search {
+            Some(last) => last.clone(),
+            None => {
+                println!("No previous search.");
+                return;
+            }
+        }
+    } else {
+        pattern
+    };
+    state.last_search = Some(pattern.clone());
+
+    let Some(pg) = state.pager.as_mut() else {
+        println!("No page to search.");
+        return;
+    };
+    let found = if backward {
+        pg.search_back(&pattern, pg.offset())
+    } else if repeat {
+        pg.search(&pattern, pg.offset() + 1)
+    } else {
+        pg.search(&pattern, pg.offset())
+    };
+    match found {
+        Some(line) => {
+            pg.jump_to_line(line);
+            pg.set_highlight(Some(pattern));
+            pg.display_current_page();
+        }
+        None => println!("Pattern not found: {pattern}"),
+    }
+}
+
+fn print_links(links: &[(String, String)]) {
+    if links.is_empty() {
+        println!("No links on this page.");
+        return;
+    }
+    for (i, (url, label)) in links.iter().enumerate() {
+        if label.is_empty() || label == url {
+            println!("[{}] {url}", i + 1);
+        } else {
+            println!("[{}] {url} - {label}", i + 1);
+        }
+    }
+}
+
+/// Write the current page to `path` in `format`.
+fn export_page(state: &BrowserState, format: ExportFormat, path: &str) {
+    if state.document.is_empty() {
+        println!("No page to export.");
+        return;
+    }
+    let contents = match format {
+        ExportFormat::Markdown => render::render_markdown(&state.document),
+        ExportFormat::Html => {
+            let title = match (&state.title, &state.current_url) {
+                (Some(title), _) => title.clone(),
+                (None, Some(url)) => url.to_string(),
+                (None, None) => String::new(),
+            };
+            render::render_html(&state.document, &title, state.current_url.as_ref())
+        }
+    };
+    match std::fs::write(path, contents) {
+        Ok(()) => println!("Exported to {path}"),
+        Err(e) => eprintln!("Error: cannot write {path}: {e}"),
+    }
+}
+
+/// Write the bookmarks to `path` as gemtext. An existing file is only
+/// replaced when `force` is set.
+fn export_bookmarks(bookmarks: &Bookmarks, path: &str, force: bool) {
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(!force)
+        .create(force)
+        .truncate(force)
+        .open(path);
+    let result = file.and_then(|mut file| file.write_all(bookmarks.to_gemtext().as_bytes()));
+    match result {
+        Ok(()) => println!("Exported bookmarks to {path}"),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => eprintln!(
+            "Error: {path} already exists; use bookmark export --force <path> to replace it"
+        ),
+        Err(e) => eprintln!("Error: cannot write {path}: {e}"),
+    }
+}
+
+/// Show where link `n` points, both as written and resolved, without
+/// following it.
+fn peek_link(state: &BrowserState, n: usize) {
+    let Some((raw, _)) = n.checked_sub(1).and_then(|i| state.links.get(i)) else {
+        println!("Invalid link number.");
+        return;
+    };
+    println!("Link:     {raw}");
+
+    let resolved = match state.current_url.as_ref() {
+        Some(base) => url_utils::resolve_url(base, raw),
+        None if Url::parse(raw).is_err() => {
+            println!("Relative link with no current page to resolve it against.");
+            return;
+        }
+        None => url_utils::parse_gemini_url(raw),
+    };
+    match resolved {
+        Ok(url) => println!("Resolved: {url}"),
+        Err(e) => eprintln!("Error: {e}"),
+    }
+}
+
+/// Print the current URL, the last response header, and the server
+/// certificate, warning in red when the certificate is expired or about to be.
+fn print_info(state: &BrowserState) {
+    let Some(url) = &state.current_url else {
+        println!("No page loaded.");
+        return;
+    };
+    println!("URL:         {url}");
+    println!("Host:        {}", url_utils::display_host(url));
+    if let Some((status, meta)) = &state.last_status {
+        println!("Status:      {status} {meta}");
+    }
+    if let Some(m) = &state.last_metrics {
+        println!(
+            "Timing:      connect {}ms, TLS {}ms{}, first byte {}ms, total {}ms ({} bytes)",
+            m.connect_ms,
+            m.tls_ms,
+            if m.resumed { " (resumed)" } else { "" },
+            m.ttfb_ms,
+            m.total_ms,
+            m.body_bytes
+        );
+    }
+    let Some(cert) = &state.certificate else {
+        println!("Certificate: none");
+        return;
+    };
+    println!("Certificate: {}", cert.fingerprint);
+    if let Some(subject) = &cert.subject {
+        println!("Subject:     {subject}");
+    }
+    if let Some(not_after) = cert.not_after {
+        println!("Expires:     {}", cert::format_date(not_after));
+    }
+    if let Some(warning) = cert.expiry_warning(time::OffsetDateTime::now_utc()) {
+        let warning = format!("Warning: {warning}");
+        if state.render_options.color {
+            println!("\x1b[31m{warning}\x1b[0m");
+        } else {
+            println!("{warning}");
+        }
+    }
+}
+
+/// Print the current page's headings, numbered and indented by level.
+fn print_toc(document: &[(usize, parser::GeminiLine)]) {
+    let headings = document.iter().filter_map(|(_, line)| match line {
+        parser::GeminiLine::Heading { level, text } => Some((*level, text)),
+        _ => None,
+    });
+    let mut any = false;
+    for (i, (level, text)) in headings.enumerate() {
+        let indent = "  ".repeat(usize::from(level.saturating_sub(1)));
+        println!("{indent}[{}] {text}", i + 1);
+        any = true;
+    }
+    if !any {
+        println!("No headings on this page.");
+    }
+}
+
+fn print_history(log: &HistoryLog) {
+    let mut entries = log.recent(HISTORY_LIST_LEN).peekable();
+    if entries.peek().is_none() {
+        println!("No history.");
+        return;
+    }
+    for (i, entry) in entries.enumerate() {
+        println!("[{}] {} {}", i + 1, entry.timestamp, short_url(&entry.url));
+    }
+}
+
+/// A URL without its scheme and with the port shown only when it isn't
+/// 1965, e.g. `example.com:1966/log/`. Unparseable URLs are returned as-is.
+fn short_url(raw: &str) -> String {
+    match Url::parse(raw) {
+        Ok(url) if url.has_host() => format!(
+            "{}{}",
+            url_utils::display_authority(&url),
+            &url[url::Position::BeforePath..]
+        ),
+        _ => raw.to_string(),
+    }
+}
+
 fn print_help() {
     println!("Commands:");
-    println!("  <number>       Follow link by number");
-    println!("  back, b        Go to previous page");
-    println!("  go <url>       Navigate to a URL");
-    println!("  gemini://...   Navigate to a Gemini URL");
-    println!("  next, n        Next page (when content is paginated)");
-    println!("  prev, p        Previous page (when content is paginated)");
-    println!("  help, ?        Show this help");
-    println!("  quit, q        Exit the browser");
+    println!("  <number>          Follow link by number");
+    println!("  back, b           Go to previous page");
+    println!("  links, l          List the links on this page");
+    println!("  toc               List the headings on this page");
+    println!("  toc go <n>        Scroll to heading n");
+    println!("  source            Show the current page's raw gemtext, or go back");
+    println!("  info              Show the last response and server certificate");
+    println!("  verbose           Toggle logging of requests and responses");
+    println!("  peek <n>          Show where link n goes without following it");
+    println!("  follow <text>     Follow the link whose label contains text");
+    println!("  last, .           Follow the last link on this page");
+    println!("  reload, r         Fetch the current page again");
+    println!("  home, h           Go to the home page set in config.toml");
+    println!("  go <url>          Navigate to a URL");
+    println!("  go <alias>        Navigate to the URL saved as an alias");
+    println!("  alias add <n> <u> Save URL u under the short name n");
+    println!("  alias list        List aliases");
+    println!("  alias rm <n>      Remove alias n");
+    println!("  gemini://...      Navigate to a Gemini URL");
+    println!("  next, n           Next page (when content is paginated)");
+    println!("  prev, p           Previous page (when content is paginated)");
+    println!("  j, k              Scroll down or up one line");
+    println!("  <space>, d, u     Scroll down or up half a page");
+    println!("  page <n>          Jump to page n");
+    println!("  top, bottom       Jump to the first or last page");
+    println!("  /<pattern>        Search forward in the paginated page");
+    println!("  /                 Repeat the last search");
+    println!("  N                 Repeat the last search backwards");
+    println!("  save <n> <path>   Download link n to a file");
+    println!("  save <path>       Save this page's body to a file (--force replaces)");
+    println!("  save --rendered <path>  Save this page as displayed, without colors");
+    println!("  export <fmt> <f>  Save this page to file f as md or html");
+    println!("  upload <f> <url>  Upload file f to a titan:// URL [token]");
+    println!("  cert new <host>   Create a client certificate for a host");
+    println!("  bookmark, bm      Bookmark the current page");
+    println!("  bookmarks         List bookmarks");
+    println!("  bm go <n>         Open bookmark n");
+    println!("  bm rm <n>         Remove bookmark n");
+    println!("  bm export [--force] <path>");
+    println!("                    Write bookmarks to a gemtext file");
+    println!("  bm import <path>  Bookmark every link in a gemtext file");
+    println!("  history           List recently visited pages");
+    println!("  history go <n>    Open history entry n");
+    println!("  subscribe         Follow the current page as a feed");
+    println!("  updates           List new entries in subscribed feeds");
+    println!("  updates go <n>    Open new entry n");
+    println!("  help, ?           Show this help");
+    println!("  quit, q           Exit the browser");
+    println!("  Ctrl-C            Cancel a page that is loading");
 }
 
 fn main() {
     // Install the default crypto provider for rustls
     let _ = rustls::crypto::ring::default_provider().install_default();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cli = match parse_args(&args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            eprintln!(
+                "Usage: gemini-browser [--color=always|never|auto] [--hyperlinks] \
+                 [--show-urls] [--mark-external] [--number-lists] [--reflow] [--truncate-lines] \
+                 [--center-pre] \
+                 [--history-cap=N] \
+                 [--confirm-redirects] [--verbose] \
+                 [--no-external] [--no-open-media] [--no-highlight] [--no-title] \
+                 [--verify=tofu|ca|insecure] [--min-tls=1.2|1.3] [--retries=N] \
+                 [--record FILE] [--replay FILE] [--quiet] [URL]"
+            );
+            std::process::exit(2);
+        }
+    };
+
+    let config = config::Config::load_default();
+
+    // Ctrl-C raises this to cancel the fetch in progress instead of exiting
+    let cancel = Arc::new(AtomicBool::new(false));
+    #[cfg(unix)]
+    if let Err(e) = signal_hook::flag::register(signal_hook::consts::SIGINT, cancel.clone()) {
+        eprintln!("Warning: Ctrl-C will not cancel page loads: {e}");
+    }
+    let confirming = Arc::new(AtomicBool::new(false));
+    let mut client = GeminiClient::builder()
+        .cancel_flag(cancel.clone())
+        .min_tls_version(cli.min_tls.or(config.min_tls).unwrap_or_default())
+        .lenient_headers(config.lenient_headers.unwrap_or(false))
+        .max_retries(cli.retries.or(config.max_retries).unwrap_or(0))
+        .on_event(|event| eprintln!("{event}"))
+        .known_hosts(KnownHosts::load_default())
+        .verbose(cli.verbose);
+    if let Some(dir) = identity::default_dir() {
+        client = client.identity_dir(dir);
+    }
+    if cli.confirm_redirects {
+        let confirming = confirming.clone();
+        client = client.confirm_cross_host_redirects(move |from, to| {
+            confirming.store(true, Ordering::Relaxed);
+            let follow = confirm(&format!(
+                "{from} redirects to another host: {to}\nFollow it?"
+            ));
+            confirming.store(false, Ordering::Relaxed);
+            follow
+        });
+    }
+    if let Some(bytes) = config.max_body_size {
+        client = client.max_body_size(bytes);
+    }
+    if let Some(timeout) = config.connect_timeout {
+        client = client.connect_timeout(timeout);
+    }
+    if let Some(timeout) = config.io_timeout {
+        client = client.io_timeout(timeout);
+    }
+    if let Some(interval) = config.request_interval {
+        client = client.request_interval(interval);
+    }
+    let mut transport: Arc<dyn gemini::Transport> = Arc::new(gemini::Network);
+    if let Some(path) = &cli.replay {
+        match record::Replayer::load(path) {
+            Ok(replayer) => transport = Arc::new(replayer),
+            Err(e) => {
+                eprintln!("Error: cannot replay {}: {e}", path.display());
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = &cli.record {
+        match record::Recorder::create(path, transport.clone()) {
+            Ok(recorder) => transport = Arc::new(recorder),
+            Err(e) => {
+                eprintln!("Error: cannot record to {}: {e}", path.display());
+                std::process::exit(1);
+            }
+        }
+    }
+    client = client.transport(transport);
+    let verify = cli.verify.or(config.verify).unwrap_or(VerifyMode::Tofu);
+    match verify.verifier() {
+        Ok(Some(verifier)) => client = client.cert_verifier(verifier),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("Error: could not set up certificate verification: {e}");
+            std::process::exit(1);
+        }
+    }
+
     let mut state = BrowserState {
+        client: client.build(),
+        cancel,
+        confirming,
+        render_options: RenderOptions {
+            color: cli
+                .color
+                .or(config.color)
+                .unwrap_or(ColorMode::Auto)
+                .resolve(),
+            hyperlinks: cli.hyperlinks,
+            show_link_urls: cli.show_link_urls,
+            mark_external: cli.mark_external,
+            auto_number_lists: cli.number_lists,
+            reflow: cli.reflow,
+            truncate_long_lines: cli.truncate_lines,
+            center_preformatted: cli.center_preformatted,
+            highlight: cli.highlight,
+            ..RenderOptions::for_locale()
+        },
+        theme: match &config.theme {
+            Some(path) => theme::Theme::load(path),
+            None => theme::Theme::load_default(),
+        }
+        .unwrap_or_else(|e| {
+            eprintln!("Warning: ignoring {e}");
+            theme::Theme::default()
+        }),
+        wrap_width: config.wrap_width,
+        home: config.home.clone(),
         history: Vec::new(),
         current_url: None,
         links: Vec::new(),
         pager: None,
+        viewing_source: false,
+        rendered_pager: None,
+        visited: HashSet::new(),
+        title: None,
+        document: Vec::new(),
+        heading_offsets: Vec::new(),
+        source: None,
+        body: None,
+        bookmarks: Bookmarks::load_default(),
+        aliases: aliases::Aliases::load_default(),
+        history_log: HistoryLog::load_default(),
+        last_search: None,
+        open_external: cli.open_external && config.open_external.unwrap_or(true),
+        open_media: cli.open_media && config.open_media.unwrap_or(true),
+        viewer: viewer::Viewer::new(config.viewer.as_deref()),
+        terminal_title: cli.terminal_title && config.terminal_title.unwrap_or(true),
+        last_status: None,
+        last_metrics: None,
+        certificate: None,
+        favicons: favicon::Favicons::default(),
+        subscriptions: subscriptions::Subscriptions::load_default(),
+        updates: Vec::new(),
+        moved: moved::MovedPages::default(),
+        quiet: cli.quiet,
     };
 
-    // Check if a URL was provided as a command line argument
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() > 1 {
-        match url_utils::parse_gemini_url(&args[1]) {
+    // Seed the back stack from the persisted log, oldest first
+    let mut restored: Vec<(Url, usize)> = state
+        .history_log
+        .recent(cli.history_cap)
+        .filter_map(|entry| url_utils::parse_gemini_url(&entry.url).ok())
+        .map(|url| (url, 0))
+        .collect();
+    restored.reverse();
+    state.history = restored;
+
+    // Open the URL given on the command line, or else the home page
+    if let Some(raw_url) = cli.url.or(config.home) {
+        match url_utils::parse_gemini_url(&raw_url) {
             Ok(url) => navigate(&mut state, url),
             Err(e) => eprintln!("Error: {e}"),
         }
     }
 
-    let stdin = io::stdin();
+    // Set from the SIGWINCH handler. A resize while a command runs redraws
+    // the page before the next prompt; one while the prompt waits for input
+    // only resizes the pager, which the command that follows then draws.
+    let resized = Arc::new(AtomicBool::new(false));
+    #[cfg(unix)]
+    if let Err(e) = signal_hook::flag::register(signal_hook::consts::SIGWINCH, resized.clone()) {
+        eprintln!("Warning: cannot watch for terminal resizes: {e}");
+    }
+
+    let history_path = paths::data_dir().map(|dir| dir.join(COMMAND_HISTORY_FILE));
+    let mut editor = match line_editor(history_path.as_deref()) {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("Error: cannot set up the prompt: {e}");
+            std::process::exit(1);
+        }
+    };
 
     loop {
-        print!("> ");
-        let _ = io::stdout().flush();
+        state.favicons.collect();
+
+        if resize_pager(&mut state, &resized) {
+            if let Some(ref pg) = state.pager {
+                pg.display_current_page();
+            }
+        }
 
-        let mut line = String::new();
-        match stdin.lock().read_line(&mut line) {
-            Ok(0) => break, // EOF
-            Ok(_) => {}
+        let completions = completion::Completions {
+            aliases: state
+                .aliases
+                .list()
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            bookmarks: state
+                .bookmarks
+                .list()
+                .iter()
+                .map(|bookmark| bookmark.url.clone())
+                .collect(),
+            link_count: state.links.len(),
+        };
+        let prompt = if state.quiet {
+            String::new()
+        } else {
+            prompt(&state)
+        };
+        editor.set_helper(Some(completions));
+        let line = match editor.readline(&prompt) {
+            Ok(line) => {
+                remember_command(&mut editor, history_path.as_deref(), &line);
+                line
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
             Err(e) => {
                 eprintln!("Error reading input: {e}");
                 break;
             }
-        }
+        };
+
+        resize_pager(&mut state, &resized);
 
         match parse_command(&line) {
             Command::Empty => continue,
             Command::Quit => break,
             Command::Help => print_help(),
+            Command::Links => print_links(&state.links),
+            Command::Info => print_info(&state),
+            Command::Verbose => {
+                let verbose = !state.client.verbose();
+                state.client.set_verbose(verbose);
+                println!("Verbose logging {}.", if verbose { "on" } else { "off" });
+            }
+            Command::Peek(n) => peek_link(&state, n),
+            Command::Toc => print_toc(&state.document),
+            Command::TocGo(n) => {
+                // Heading offsets are rows of the rendered page
+                leave_source(&mut state);
+                match n.checked_sub(1).and_then(|i| state.heading_offsets.get(i)) {
+                    // A short page is on screen in full, heading included
+                    Some(_) if state.pager.is_none() => {
+                        println!("The whole page is already shown.")
+                    }
+                    Some(&line) => scroll_pager(&mut state, |pg| pg.jump_to_line(line)),
+                    None => println!("Invalid heading number."),
+                }
+            }
+            Command::Source => toggle_source(&mut state),
             Command::Back => {
-                if let Some(prev_url) = state.history.pop() {
-                    let url = prev_url.clone();
+                if let Some((prev_url, scroll)) = state.history.pop() {
                     // Don't push current to history when going back
-                    state.current_url = None;
-                    navigate(&mut state, url);
+                    load(&mut state, prev_url, false, scroll);
                 } else {
                     println!("No previous page.");
                 }
             }
-            Command::Go(raw_url) => match url_utils::parse_gemini_url(&raw_url) {
-                Ok(url) => navigate(&mut state, url),
-                Err(e) => eprintln!("Error: {e}"),
+            Command::Reload => match state.current_url.clone() {
+                Some(url) => load(&mut state, url, false, 0),
+                None => println!("Nothing to reload."),
             },
-            Command::Navigate(raw_url) => match url_utils::parse_gemini_url(&raw_url) {
-                Ok(url) => navigate(&mut state, url),
-                Err(e) => eprintln!("Error: {e}"),
+            Command::Home => match state.home.clone() {
+                Some(raw_url) => match url_utils::parse_gemini_url(&raw_url) {
+                    Ok(url) => navigate(&mut state, url),
+                    Err(e) => eprintln!("Error: invalid home page: {e}"),
+                },
+                None => println!(
+                    "No home page set. Add home = \"gemini://...\" to config.toml to set one."
+                ),
             },
-            Command::FollowLink(n) => {
-                if n < 1 || n > state.links.len() {
-                    println!("Invalid link number.");
-                } else {
-                    let link_url = state.links[n - 1].clone();
-                    match state.current_url.as_ref() {
-                        Some(base) => match url_utils::resolve_url(base, &link_url) {
-                            Ok(url) => navigate(&mut state, url),
-                            Err(e) => eprintln!("Error: {e}"),
-                        },
-                        None => {
-                            // No current URL to resolve against, try parsing as absolute
-                            match url_utils::parse_gemini_url(&link_url) {
-                                Ok(url) => navigate(&mut state, url),
-                                Err(e) => eprintln!("Error: {e}"),
-                            }
-                        }
+            Command::Go(raw_url) | Command::Navigate(raw_url) => {
+                match go_target(&state.aliases, &raw_url) {
+                    Ok(GoTarget::Load(url)) => navigate(&mut state, url),
+                    Ok(GoTarget::External(url)) => offer_external(&state, &url),
+                    Err(e) => eprintln!("Error: {e}"),
+                }
+            }
+            Command::FollowLink(n) => follow_link(&mut state, n),
+            Command::FollowLast => match state.links.len() {
+                0 => println!("This page has no links."),
+                n => follow_link(&mut state, n),
+            },
+            Command::Follow(text) => follow_label(&mut state, &text),
+            Command::Save(n, path) => match resolve_link(&state, n) {
+                Some(Ok(url)) => save_to_file(&state, &url, &path),
+                Some(Err(e)) => eprintln!("Error: {e}"),
+                None => println!("Invalid link number."),
+            },
+            Command::SavePage {
+                path,
+                rendered,
+                force,
+            } => save_page(&state, &path, rendered, force),
+            Command::Export(format, path) => export_page(&state, format, &path),
+            Command::Upload { path, url, token } => {
+                upload_file(&mut state, &path, &url, token.as_deref())
+            }
+            Command::CertNew(host) => {
+                let Some(dir) = identity::default_dir() else {
+                    eprintln!("Error: no config directory to keep identities in.");
+                    continue;
+                };
+                match identity::generate_self_signed(&dir, &host, identity::DEFAULT_VALIDITY_DAYS) {
+                    Ok(id) => {
+                        // Connections to the host must now present it
+                        state.client.clear_tls_configs();
+                        println!(
+                            "Created identity for {host} (fingerprint {}).",
+                            tofu::fingerprint(id.cert.as_ref())
+                        );
                     }
+                    Err(e) => eprintln!("Error: {e}"),
+                }
+            }
+            Command::Bookmark => add_bookmark(&mut state),
+            Command::Bookmarks => print_bookmarks(&state.bookmarks),
+            Command::BookmarkRemove(n) => match state.bookmarks.remove(n.wrapping_sub(1)) {
+                Ok(Some(removed)) => println!("Removed bookmark {}", removed.title),
+                Ok(None) => println!("Invalid bookmark number."),
+                Err(e) => eprintln!("Error: could not save bookmarks: {e}"),
+            },
+            Command::BookmarkExport { path, force } => {
+                export_bookmarks(&state.bookmarks, &path, force)
+            }
+            Command::BookmarkImport(path) => match std::fs::read_to_string(&path) {
+                Ok(gemtext) => match state.bookmarks.import(&gemtext) {
+                    Ok(added) => println!("Imported {added} bookmarks from {path}"),
+                    Err(e) => eprintln!("Error: could not save bookmarks: {e}"),
+                },
+                Err(e) => eprintln!("Error: cannot read {path}: {e}"),
+            },
+            Command::BookmarkGo(n) => {
+                let target = n
+                    .checked_sub(1)
+                    .and_then(|i| state.bookmarks.list().get(i))
+                    .map(|bookmark| bookmark.url.clone());
+                match target {
+                    Some(raw_url) => match url_utils::parse_gemini_url(&raw_url) {
+                        Ok(url) => navigate(&mut state, url),
+                        Err(e) => eprintln!("Error: {e}"),
+                    },
+                    None => println!("Invalid bookmark number."),
+                }
+            }
+            Command::History => print_history(&state.history_log),
+            Command::AliasAdd { name, url } => {
+                if name.contains("://") {
+                    println!("Alias names can't contain \"://\".");
+                    continue;
+                }
+                match state.aliases.add(&name, &url) {
+                    Ok(()) => println!("Alias {name} -> {url}"),
+                    Err(e) => eprintln!("Error: could not save aliases: {e}"),
+                }
+            }
+            Command::AliasList => print_aliases(&state.aliases),
+            Command::AliasRemove(name) => match state.aliases.remove(&name) {
+                Ok(true) => println!("Removed alias {name}"),
+                Ok(false) => println!("No alias named {name}."),
+                Err(e) => eprintln!("Error: could not save aliases: {e}"),
+            },
+            Command::Subscribe => subscribe(&mut state),
+            Command::Updates => check_updates(&mut state),
+            Command::UpdatesGo(n) => {
+                let target = n
+                    .checked_sub(1)
+                    .and_then(|i| state.updates.get(i))
+                    .map(|entry| entry.url.clone());
+                match target {
+                    Some(raw_url) => match url_utils::parse_gemini_url(&raw_url) {
+                        Ok(url) => navigate(&mut state, url),
+                        Err(e) => eprintln!("Error: {e}"),
+                    },
+                    None => println!("Invalid update number."),
                 }
             }
+            Command::HistoryGo(n) => {
+                let target = n
+                    .checked_sub(1)
+                    .and_then(|i| state.history_log.recent(HISTORY_LIST_LEN).nth(i))
+                    .map(|entry| entry.url.clone());
+                match target {
+                    Some(raw_url) => match url_utils::parse_gemini_url(&raw_url) {
+                        Ok(url) => navigate(&mut state, url),
+                        Err(e) => eprintln!("Error: {e}"),
+                    },
+                    None => println!("Invalid history number."),
+                }
+            }
+            Command::GotoPage(n) => scroll_pager(&mut state, |pg| pg.goto_page(n)),
+            Command::ScrollLines(delta) => scroll_pager(&mut state, |pg| {
+                pg.scroll_lines(delta);
+            }),
+            Command::HalfPageDown => scroll_pager(&mut state, |pg| {
+                pg.scroll_lines(pg.half_page() as isize);
+            }),
+            Command::HalfPageUp => scroll_pager(&mut state, |pg| {
+                pg.scroll_lines(-(pg.half_page() as isize));
+            }),
+            Command::Top => scroll_pager(&mut state, pager::Pager::goto_start),
+            Command::Bottom => scroll_pager(&mut state, pager::Pager::goto_end),
+            Command::Search(pattern) => search_page(&mut state, pattern, false),
+            Command::SearchPrev => search_page(&mut state, String::new(), true),
             Command::NextPage => {
                 if let Some(ref mut pg) = state.pager {
                     if pg.next_page() {
@@ -275,12 +2200,195 @@ fn main() {
             }
         }
     }
+
+    if state.terminal_title {
+        set_terminal_title(render::DEFAULT_TERMINAL_TITLE);
+    }
+}
+
+/// Set the terminal title, if stdout is a terminal.
+fn set_terminal_title(title: &str) {
+    if render::stdout_is_tty() {
+        print!("{}", render::terminal_title_escape(title));
+        let _ = io::stdout().flush();
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args_url_and_color() {
+        let cli = parse_args(&args(&["--color=never", "gemini://example.com"])).unwrap();
+        assert_eq!(cli.url.as_deref(), Some("gemini://example.com"));
+        assert_eq!(cli.color, Some(ColorMode::Never));
+        assert!(!cli.hyperlinks);
+    }
+
+    #[test]
+    fn test_parse_args_defaults() {
+        let cli = parse_args(&[]).unwrap();
+        assert!(cli.url.is_none());
+        assert_eq!(cli.color, None);
+        assert!(!cli.quiet);
+    }
+
+    #[test]
+    fn test_parse_args_quiet() {
+        assert!(parse_args(&args(&["--quiet"])).unwrap().quiet);
+    }
+
+    #[test]
+    fn test_parse_args_hyperlinks() {
+        let cli = parse_args(&args(&["--hyperlinks"])).unwrap();
+        assert!(cli.hyperlinks);
+        assert!(!cli.show_link_urls);
+        assert!(parse_args(&args(&["--show-urls"])).unwrap().show_link_urls);
+        assert!(
+            parse_args(&args(&["--mark-external"]))
+                .unwrap()
+                .mark_external
+        );
+        assert!(!cli.number_lists);
+        assert!(parse_args(&args(&["--number-lists"])).unwrap().number_lists);
+        assert!(!cli.reflow);
+        assert!(parse_args(&args(&["--reflow"])).unwrap().reflow);
+        assert!(!cli.truncate_lines);
+        assert!(
+            parse_args(&args(&["--truncate-lines"]))
+                .unwrap()
+                .truncate_lines
+        );
+        assert!(!cli.center_preformatted);
+        assert!(
+            parse_args(&args(&["--center-pre"]))
+                .unwrap()
+                .center_preformatted
+        );
+        assert!(!cli.confirm_redirects);
+    }
+
+    #[test]
+    fn test_parse_args_confirm_redirects() {
+        let cli = parse_args(&args(&["--confirm-redirects"])).unwrap();
+        assert!(cli.confirm_redirects);
+    }
+
+    #[test]
+    fn test_parse_certificate_choice() {
+        assert_eq!(parse_certificate_choice("t\n"), CertificateChoice::Trust);
+        assert_eq!(parse_certificate_choice("Once"), CertificateChoice::Once);
+        assert_eq!(parse_certificate_choice("r"), CertificateChoice::Reject);
+        assert_eq!(parse_certificate_choice(""), CertificateChoice::Reject);
+        assert_eq!(parse_certificate_choice("yes"), CertificateChoice::Reject);
+    }
+
+    #[test]
+    fn test_short_url() {
+        assert_eq!(short_url("gemini://example.com/a?q"), "example.com/a?q");
+        assert_eq!(
+            short_url("gemini://example.com:1966/log/"),
+            "example.com:1966/log/"
+        );
+        assert_eq!(short_url("not a url"), "not a url");
+    }
+
+    #[test]
+    fn test_format_prompt() {
+        assert_eq!(format_prompt(None, None), "> ");
+        assert_eq!(format_prompt(Some("example.com"), None), "[example.com] > ");
+        assert_eq!(
+            format_prompt(Some("My Capsule"), Some((2, 5))),
+            "[My Capsule 2/5] > "
+        );
+        assert_eq!(format_prompt(Some("  "), Some((1, 3))), "[1/3] > ");
+        assert_eq!(
+            format_prompt(Some("bad\x1b[31mtitle"), None),
+            "[bad[31mtitle] > "
+        );
+        let long = "a".repeat(50);
+        assert_eq!(
+            format_prompt(Some(&long), None),
+            format!("[{}...] > ", "a".repeat(27))
+        );
+    }
+
+    #[test]
+    fn test_parse_args_no_title() {
+        assert!(parse_args(&[]).unwrap().terminal_title);
+        assert!(!parse_args(&args(&["--no-title"])).unwrap().terminal_title);
+    }
+
+    #[test]
+    fn test_parse_args_verify() {
+        assert_eq!(parse_args(&[]).unwrap().verify, None);
+        assert_eq!(
+            parse_args(&args(&["--verify=ca"])).unwrap().verify,
+            Some(VerifyMode::Ca)
+        );
+        assert!(parse_args(&args(&["--verify=maybe"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_record_and_replay() {
+        let cli = parse_args(&args(&["--record", "s.log", "gemini://a/"])).unwrap();
+        assert_eq!(cli.record, Some(PathBuf::from("s.log")));
+        assert_eq!(cli.replay, None);
+        assert_eq!(cli.url.as_deref(), Some("gemini://a/"));
+        let cli = parse_args(&args(&["--replay", "s.log"])).unwrap();
+        assert_eq!(cli.replay, Some(PathBuf::from("s.log")));
+        assert!(parse_args(&args(&["--replay"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_min_tls() {
+        assert_eq!(parse_args(&[]).unwrap().min_tls, None);
+        assert_eq!(
+            parse_args(&args(&["--min-tls=1.3"])).unwrap().min_tls,
+            Some(TlsVersion::Tls13)
+        );
+        assert!(parse_args(&args(&["--min-tls=1.0"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_retries() {
+        assert_eq!(parse_args(&[]).unwrap().retries, None);
+        assert_eq!(
+            parse_args(&args(&["--retries=3"])).unwrap().retries,
+            Some(3)
+        );
+        assert!(parse_args(&args(&["--retries=-1"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_verbose() {
+        assert!(!parse_args(&[]).unwrap().verbose);
+        assert!(parse_args(&args(&["--verbose"])).unwrap().verbose);
+    }
+
+    #[test]
+    fn test_parse_args_history_cap() {
+        assert_eq!(
+            parse_args(&[]).unwrap().history_cap,
+            history::DEFAULT_HISTORY_CAP
+        );
+        let cli = parse_args(&args(&["--history-cap=5"])).unwrap();
+        assert_eq!(cli.history_cap, 5);
+        assert!(parse_args(&args(&["--history-cap=lots"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_rejects_bad_options() {
+        assert!(parse_args(&args(&["--color=purple"])).is_err());
+        assert!(parse_args(&args(&["--bogus"])).is_err());
+        assert!(parse_args(&args(&["gemini://a", "gemini://b"])).is_err());
+    }
+
     #[test]
     fn test_parse_quit() {
         assert!(matches!(parse_command("quit"), Command::Quit));
@@ -293,6 +2401,83 @@ mod tests {
         assert!(matches!(parse_command("b"), Command::Back));
     }
 
+    #[test]
+    fn test_parse_home() {
+        assert!(matches!(parse_command("home"), Command::Home));
+        assert!(matches!(parse_command("h"), Command::Home));
+        assert!(matches!(parse_command("history"), Command::History));
+    }
+
+    #[test]
+    fn test_parse_reload() {
+        assert!(matches!(parse_command("reload"), Command::Reload));
+        assert!(matches!(parse_command("r"), Command::Reload));
+    }
+
+    #[test]
+    fn test_parse_links() {
+        assert!(matches!(parse_command("links"), Command::Links));
+        assert!(matches!(parse_command("l"), Command::Links));
+    }
+
+    #[test]
+    fn test_parse_info() {
+        assert!(matches!(parse_command("info"), Command::Info));
+    }
+
+    #[test]
+    fn test_parse_verbose() {
+        assert!(matches!(parse_command("verbose"), Command::Verbose));
+    }
+
+    #[test]
+    fn test_parse_peek() {
+        assert!(matches!(parse_command("peek 2"), Command::Peek(2)));
+        assert!(matches!(parse_command("peek"), Command::Unknown));
+        assert!(matches!(parse_command("peek x"), Command::Unknown));
+    }
+
+    #[test]
+    fn test_parse_follow() {
+        match parse_command("follow  About me ") {
+            Command::Follow(text) => assert_eq!(text, "About me"),
+            _ => panic!("expected Follow command"),
+        }
+        assert!(matches!(parse_command("follow"), Command::Unknown));
+    }
+
+    #[test]
+    fn test_parse_follow_last() {
+        assert!(matches!(parse_command("last"), Command::FollowLast));
+        assert!(matches!(parse_command(" . "), Command::FollowLast));
+    }
+
+    #[test]
+    fn test_links_matching_ignores_case() {
+        let links: Vec<(String, String)> =
+            [("/a", "About"), ("/b", "Blog"), ("/c", "BLOG archive")]
+                .iter()
+                .map(|(url, label)| (url.to_string(), label.to_string()))
+                .collect();
+        assert_eq!(links_matching(&links, "about"), vec![1]);
+        assert_eq!(links_matching(&links, "blog"), vec![2, 3]);
+        assert_eq!(links_matching(&links, "ARCH"), vec![3]);
+        assert!(links_matching(&links, "gopher").is_empty());
+    }
+
+    #[test]
+    fn test_parse_source() {
+        assert!(matches!(parse_command("source"), Command::Source));
+        assert!(matches!(parse_command("view-source"), Command::Source));
+    }
+
+    #[test]
+    fn test_parse_toc() {
+        assert!(matches!(parse_command("toc"), Command::Toc));
+        assert!(matches!(parse_command("toc go 2"), Command::TocGo(2)));
+        assert!(matches!(parse_command("toc go"), Command::Unknown));
+    }
+
     #[test]
     fn test_parse_help() {
         assert!(matches!(parse_command("help"), Command::Help));
@@ -315,6 +2500,104 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_upload() {
+        match parse_command("upload post.gmi titan://example.com/post.gmi") {
+            Command::Upload { path, url, token } => {
+                assert_eq!(path, "post.gmi");
+                assert_eq!(url, "titan://example.com/post.gmi");
+                assert_eq!(token, None);
+            }
+            _ => panic!("expected Upload command"),
+        }
+        match parse_command("upload a.txt titan://example.com/a.txt s3cret") {
+            Command::Upload { token, .. } => assert_eq!(token.as_deref(), Some("s3cret")),
+            _ => panic!("expected Upload command"),
+        }
+        assert!(matches!(parse_command("upload a.txt"), Command::Unknown));
+        assert!(matches!(parse_command("upload a b c d"), Command::Unknown));
+    }
+
+    #[test]
+    fn test_upload_token_is_not_saved_in_history() {
+        let path =
+            std::env::temp_dir().join(format!("gemini-browser-cmdhistory-{}", std::process::id()));
+        let mut editor = line_editor(None).unwrap();
+        remember_command(
+            &mut editor,
+            Some(&path),
+            "upload a.txt titan://example.com/a.txt s3cret",
+        );
+        remember_command(&mut editor, Some(&path), "links");
+        let saved = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(!saved.contains("s3cret"));
+        assert!(saved.contains("upload a.txt titan://example.com/a.txt\n"));
+        assert!(saved.contains("links\n"));
+    }
+
+    #[test]
+    fn test_parse_navigate_other_schemes() {
+        match parse_command("https://example.com/") {
+            Command::Navigate(url) => assert_eq!(url, "https://example.com/"),
+            _ => panic!("expected Navigate command"),
+        }
+    }
+
+    #[test]
+    fn test_external_url() {
+        let base = Url::parse("gemini://example.com/dir/").unwrap();
+        assert_eq!(
+            external_url(Some(&base), "https://example.org/x")
+                .unwrap()
+                .as_str(),
+            "https://example.org/x"
+        );
+        assert!(external_url(Some(&base), "gopher://example.org/").is_some());
+        assert!(external_url(Some(&base), "page.gmi").is_none());
+        assert!(external_url(Some(&base), "gemini://other.example/").is_none());
+        assert!(external_url(None, "relative").is_none());
+        assert!(external_url(None, "example.com:1965/").is_none());
+        assert!(external_url(None, "mailto:someone@example.com").is_some());
+        assert!(external_url(None, "https://example.org/").is_some());
+    }
+
+    #[test]
+    fn test_go_host_and_port_loads_gemini() {
+        let aliases = aliases::Aliases::load(
+            std::env::temp_dir().join(format!("gemini-browser-no-aliases-{}", std::process::id())),
+        );
+        let Command::Go(raw) = parse_command("go example.com:1965/") else {
+            panic!("expected Go command");
+        };
+        assert_eq!(
+            go_target(&aliases, &raw).unwrap(),
+            GoTarget::Load(Url::parse("gemini://example.com:1965/").unwrap())
+        );
+        assert_eq!(
+            go_target(&aliases, "localhost:1965").unwrap(),
+            GoTarget::Load(Url::parse("gemini://localhost:1965/").unwrap())
+        );
+        assert!(matches!(
+            go_target(&aliases, "https://example.org/"),
+            Ok(GoTarget::External(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_args_no_highlight() {
+        assert!(parse_args(&[]).unwrap().highlight);
+        assert!(!parse_args(&args(&["--no-highlight"])).unwrap().highlight);
+    }
+
+    #[test]
+    fn test_parse_args_no_external() {
+        assert!(parse_args(&[]).unwrap().open_external);
+        assert!(!parse_args(&args(&["--no-external"])).unwrap().open_external);
+        assert!(parse_args(&[]).unwrap().open_media);
+        assert!(!parse_args(&args(&["--no-open-media"])).unwrap().open_media);
+    }
+
     #[test]
     fn test_parse_follow_link() {
         match parse_command("3") {
@@ -334,6 +2617,202 @@ mod tests {
         assert!(matches!(parse_command("foo"), Command::Unknown));
     }
 
+    #[test]
+    fn test_pages_count_wrapped_rows() {
+        // 40 four-letter words wrap to 10 rows of 4 words at 20 columns, and
+        // the 50-column preformatted line folds onto 3 more
+        let source = format!("{}\n```\n{}\n```\n", "word ".repeat(40), "=".repeat(50));
+        let opts = RenderOptions {
+            color: false,
+            ..RenderOptions::default()
+        };
+        let page = render::render(
+            &parser::parse_gemini(&source),
+            None,
+            &HashSet::new(),
+            20,
+            &opts,
+            &theme::Theme::default(),
+        );
+        assert_eq!(page.lines.len(), 11);
+
+        let rows = pager::fold_lines(page.lines, 20, &mut []);
+        assert_eq!(rows.len(), 13);
+        let pg = pager::Pager::new(rows, 4);
+        assert_eq!(pg.total_pages(), 4);
+    }
+
+    #[test]
+    fn test_parse_save() {
+        match parse_command("save 2 /tmp/image.png") {
+            Command::Save(n, path) => {
+                assert_eq!(n, 2);
+                assert_eq!(path, "/tmp/image.png");
+            }
+            _ => panic!("expected Save command"),
+        }
+        assert!(matches!(parse_command("save 2"), Command::Unknown));
+        assert!(matches!(parse_command("save x out.bin"), Command::Unknown));
+    }
+
+    #[test]
+    fn test_parse_save_page() {
+        match parse_command("save page.gmi") {
+            Command::SavePage {
+                path,
+                rendered,
+                force,
+            } => {
+                assert_eq!(path, "page.gmi");
+                assert!(!rendered && !force);
+            }
+            _ => panic!("expected SavePage command"),
+        }
+        match parse_command("save --force --rendered out.txt") {
+            Command::SavePage {
+                path,
+                rendered,
+                force,
+            } => {
+                assert_eq!(path, "out.txt");
+                assert!(rendered && force);
+            }
+            _ => panic!("expected SavePage command"),
+        }
+        assert!(matches!(parse_command("save --force"), Command::Unknown));
+        assert!(matches!(
+            parse_command("save --force 2 out.bin"),
+            Command::Unknown
+        ));
+    }
+
+    #[test]
+    fn test_parse_export() {
+        match parse_command("export md /tmp/page.md") {
+            Command::Export(format, path) => {
+                assert_eq!(format, ExportFormat::Markdown);
+                assert_eq!(path, "/tmp/page.md");
+            }
+            _ => panic!("expected Export command"),
+        }
+        assert!(matches!(
+            parse_command("export html out.html"),
+            Command::Export(ExportFormat::Html, _)
+        ));
+        assert!(matches!(parse_command("export md"), Command::Unknown));
+        assert!(matches!(
+            parse_command("export pdf out.pdf"),
+            Command::Unknown
+        ));
+    }
+
+    #[test]
+    fn test_parse_cert_new() {
+        match parse_command("cert new example.com") {
+            Command::CertNew(host) => assert_eq!(host, "example.com"),
+            _ => panic!("expected CertNew command"),
+        }
+        assert!(matches!(parse_command("cert new "), Command::Unknown));
+    }
+
+    #[test]
+    fn test_parse_bookmark_commands() {
+        assert!(matches!(parse_command("bookmark"), Command::Bookmark));
+        assert!(matches!(parse_command("bm"), Command::Bookmark));
+        assert!(matches!(parse_command("bookmarks"), Command::Bookmarks));
+        assert!(matches!(
+            parse_command("bookmark rm 2"),
+            Command::BookmarkRemove(2)
+        ));
+        assert!(matches!(parse_command("bm go 1"), Command::BookmarkGo(1)));
+        assert!(matches!(parse_command("subscribe"), Command::Subscribe));
+        assert!(matches!(parse_command("updates"), Command::Updates));
+        assert!(matches!(
+            parse_command("updates go 3"),
+            Command::UpdatesGo(3)
+        ));
+        assert!(matches!(parse_command("bm go x"), Command::Unknown));
+        assert!(matches!(parse_command("bm delete 1"), Command::Unknown));
+        match parse_command("bookmark export  ~/marks.gmi ") {
+            Command::BookmarkExport { path, force } => {
+                assert_eq!(path, "~/marks.gmi");
+                assert!(!force);
+            }
+            _ => panic!("expected BookmarkExport command"),
+        }
+        match parse_command("bm export --force marks.gmi") {
+            Command::BookmarkExport { path, force } => {
+                assert_eq!(path, "marks.gmi");
+                assert!(force);
+            }
+            _ => panic!("expected BookmarkExport command"),
+        }
+        assert!(matches!(
+            parse_command("bm import marks.gmi"),
+            Command::BookmarkImport(_)
+        ));
+        assert!(matches!(parse_command("bm export"), Command::Unknown));
+    }
+
+    #[test]
+    fn test_parse_alias_commands() {
+        match parse_command("alias add cap gemini://capsule.example/") {
+            Command::AliasAdd { name, url } => {
+                assert_eq!(name, "cap");
+                assert_eq!(url, "gemini://capsule.example/");
+            }
+            _ => panic!("expected AliasAdd"),
+        }
+        assert!(matches!(parse_command("alias"), Command::AliasList));
+        assert!(matches!(parse_command("alias list"), Command::AliasList));
+        assert!(matches!(parse_command("alias rm cap"), Command::AliasRemove(n) if n == "cap"));
+        assert!(matches!(parse_command("alias add cap"), Command::Unknown));
+        assert!(matches!(parse_command("alias rm"), Command::Unknown));
+    }
+
+    #[test]
+    fn test_parse_history_commands() {
+        assert!(matches!(parse_command("history"), Command::History));
+        assert!(matches!(
+            parse_command("history go 3"),
+            Command::HistoryGo(3)
+        ));
+        assert!(matches!(parse_command("history go"), Command::Unknown));
+    }
+
+    #[test]
+    fn test_parse_search() {
+        match parse_command("/needle in haystack") {
+            Command::Search(pattern) => assert_eq!(pattern, "needle in haystack"),
+            _ => panic!("expected Search command"),
+        }
+        match parse_command("/") {
+            Command::Search(pattern) => assert!(pattern.is_empty()),
+            _ => panic!("expected Search command"),
+        }
+        assert!(matches!(parse_command("N"), Command::SearchPrev));
+        // `n` stays next-page rather than next-match
+        assert!(matches!(parse_command("n"), Command::NextPage));
+    }
+
+    #[test]
+    fn test_parse_page_jumps() {
+        assert!(matches!(parse_command("page 4"), Command::GotoPage(4)));
+        assert!(matches!(parse_command("page four"), Command::Unknown));
+        assert!(matches!(parse_command("top"), Command::Top));
+        assert!(matches!(parse_command("bottom"), Command::Bottom));
+    }
+
+    #[test]
+    fn test_parse_scroll_commands() {
+        assert!(matches!(parse_command("j"), Command::ScrollLines(1)));
+        assert!(matches!(parse_command("k"), Command::ScrollLines(-1)));
+        assert!(matches!(parse_command("d"), Command::HalfPageDown));
+        assert!(matches!(parse_command("u"), Command::HalfPageUp));
+        assert!(matches!(parse_command(" \n"), Command::HalfPageDown));
+        assert!(matches!(parse_command("  \n"), Command::Empty));
+    }
+
     #[test]
     fn test_parse_next() {
         assert!(matches!(parse_command("next"), Command::NextPage));
@@ -345,4 +2824,32 @@ mod tests {
         assert!(matches!(parse_command("prev"), Command::PrevPage));
         assert!(matches!(parse_command("p"), Command::PrevPage));
     }
+
+    #[test]
+    fn test_back_restores_clamped_scroll() {
+        let page = |n: usize| (0..n).map(|i| format!("Line {i}")).collect::<Vec<_>>();
+        let mut history: Vec<(Url, usize)> = Vec::new();
+
+        // Scroll down a long page, then follow a link away from it
+        let mut shown = pager::show_to(page(50), Some(10), 0, true, &mut Vec::new())
+            .unwrap()
+            .unwrap();
+        shown.jump_to_line(35);
+        let url = Url::parse("gemini://example.com/long").unwrap();
+        history.push((url.clone(), shown.offset()));
+
+        // Going back lands where the page was left
+        let (back_url, scroll) = history.pop().unwrap();
+        assert_eq!(back_url, url);
+        let restored = pager::show_to(page(50), Some(10), scroll, true, &mut Vec::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(restored.offset(), 35);
+
+        // If the page got shorter meanwhile, the offset stays inside it
+        let restored = pager::show_to(page(20), Some(10), scroll, true, &mut Vec::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(restored.offset(), 19);
+    }
 }
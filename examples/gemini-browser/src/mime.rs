@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+
+use encoding_rs::{Encoding, UTF_8};
+
+/// MIME type assumed when a success response has an empty meta.
+pub const DEFAULT_MIME: &str = "text/gemini; charset=utf-8";
+
+/// A parsed MIME type such as `text/gemini; charset=utf-8; lang=en`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MimeType {
+    /// `type/subtype`, lowercased.
+    pub essence: String,
+    /// Parameters keyed by lowercased name, with quotes removed from values.
+    pub params: BTreeMap<String, String>,
+}
+
+impl MimeType {
+    /// Whether the essence is exactly `essence` (compared case-insensitively).
+    pub fn is(&self, essence: &str) -> bool {
+        self.essence.eq_ignore_ascii_case(essence)
+    }
+
+    /// Whether the essence is a well-formed `type/subtype`, each part a
+    /// non-empty run of token characters.
+    pub fn is_valid(&self) -> bool {
+        let token = |s: &str| {
+            !s.is_empty()
+                && s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+        };
+        self.essence
+            .split_once('/')
+            .is_some_and(|(kind, subtype)| token(kind) && token(subtype))
+    }
+
+    pub fn charset(&self) -> Option<&str> {
+        self.params.get("charset").map(String::as_str)
+    }
+
+    pub fn lang(&self) -> Option<&str> {
+        self.params.get("lang").map(String::as_str)
+    }
+
+    /// Decode `body` using the declared charset, falling back to UTF-8 when
+    /// none is given or the label is unknown. Malformed sequences become
+    /// U+FFFD.
+    pub fn decode(&self, body: &[u8]) -> String {
+        self.encoding()
+            .decode_without_bom_handling(body)
+            .0
+            .into_owned()
+    }
+
+    /// `decode`, plus the byte offset of the first malformed sequence when
+    /// the body is UTF-8 (declared or by default) and has one, so the
+    /// caller can say the page was mangled.
+    pub fn decode_checked(&self, body: &[u8]) -> (String, Option<usize>) {
+        if self.encoding() != UTF_8 {
+            return (self.decode(body), None);
+        }
+        match std::str::from_utf8(body) {
+            Ok(text) => (text.to_string(), None),
+            Err(e) => (
+                String::from_utf8_lossy(body).into_owned(),
+                Some(e.valid_up_to()),
+            ),
+        }
+    }
+
+    fn encoding(&self) -> &'static Encoding {
+        self.charset()
+            .and_then(|label| Encoding::for_label(label.as_bytes()))
+            .unwrap_or(UTF_8)
+    }
+}
+
+/// Parse a success response's meta into a MIME type. An empty meta means
+/// `DEFAULT_MIME`. Whitespace around `;` and `=` is ignored, and parameters
+/// without a `=` are skipped.
+pub fn parse(meta: &str) -> MimeType {
+    let meta = if meta.trim().is_empty() {
+        DEFAULT_MIME
+    } else {
+        meta
+    };
+
+    let mut parts = meta.split(';');
+    let essence = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+    let params = parts
+        .filter_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            let name = name.trim().to_ascii_lowercase();
+            let value = value.trim().trim_matches('"');
+            if name.is_empty() {
+                return None;
+            }
+            Some((name, value.to_string()))
+        })
+        .collect();
+
+    MimeType { essence, params }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_with_params() {
+        let mime = parse("text/gemini; charset=utf-8; lang=en");
+        assert_eq!(mime.essence, "text/gemini");
+        assert_eq!(mime.charset(), Some("utf-8"));
+        assert_eq!(mime.lang(), Some("en"));
+    }
+
+    #[test]
+    fn test_parse_tolerates_whitespace_and_case() {
+        let mime = parse("Text/Gemini ;Charset = UTF-8 ;  lang=\"fr\"");
+        assert!(mime.is("text/gemini"));
+        assert_eq!(mime.charset(), Some("UTF-8"));
+        assert_eq!(mime.lang(), Some("fr"));
+    }
+
+    #[test]
+    fn test_parse_empty_meta_is_gemini() {
+        let mime = parse("");
+        assert!(mime.is("text/gemini"));
+        assert_eq!(mime.charset(), Some("utf-8"));
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(parse("image/png").is_valid());
+        assert!(parse("application/vnd.foo+json; x=1").is_valid());
+        assert!(parse("").is_valid());
+        assert!(!parse("png").is_valid());
+        assert!(!parse("text/").is_valid());
+        assert!(!parse("text/plain/extra").is_valid());
+        assert!(!parse("<html>").is_valid());
+    }
+
+    #[test]
+    fn test_parse_skips_malformed_params() {
+        let mime = parse("image/png; junk; =x");
+        assert_eq!(mime.essence, "image/png");
+        assert!(mime.params.is_empty());
+    }
+
+    #[test]
+    fn test_decode_latin1() {
+        let mime = parse("text/gemini; charset=iso-8859-1");
+        assert_eq!(mime.decode(b"caf\xe9"), "caf\u{e9}");
+    }
+
+    #[test]
+    fn test_decode_defaults_to_utf8() {
+        assert_eq!(
+            parse("text/gemini").decode("caf\u{e9}".as_bytes()),
+            "caf\u{e9}"
+        );
+        let unknown = parse("text/gemini; charset=klingon");
+        assert_eq!(unknown.decode(b"ok\xff"), "ok\u{fffd}");
+    }
+
+    #[test]
+    fn test_decode_checked_reports_first_bad_byte() {
+        let mime = parse("text/gemini");
+        assert_eq!(
+            mime.decode_checked("caf\u{e9}".as_bytes()),
+            ("caf\u{e9}".to_string(), None)
+        );
+        assert_eq!(
+            mime.decode_checked(b"ok\xff then \xfe"),
+            ("ok\u{fffd} then \u{fffd}".to_string(), Some(2))
+        );
+        let latin1 = parse("text/gemini; charset=iso-8859-1");
+        assert_eq!(latin1.decode_checked(b"\xff"), ("\u{ff}".to_string(), None));
+    }
+
+    #[test]
+    fn test_is_does_not_match_prefix() {
+        assert!(!parse("text/gemini-extended").is("text/gemini"));
+    }
+}
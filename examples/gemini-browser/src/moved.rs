@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use url::Url;
+
+/// Most moves followed from one URL, so a cycle of moves can't hang
+/// `resolve`.
+const MAX_MOVES: usize = 5;
+
+/// Pages reported as permanently moved (status 31) this session, and where
+/// they went. Later visits to an old URL go straight to its new home.
+#[derive(Debug, Default)]
+pub struct MovedPages {
+    targets: HashMap<String, Url>,
+}
+
+impl MovedPages {
+    /// Remember that `from` has moved to `to`.
+    pub fn record(&mut self, from: &Url, to: &Url) {
+        if from != to {
+            self.targets.insert(from.to_string(), to.clone());
+        }
+    }
+
+    /// Where `url` lives now, following recorded moves; `url` itself if it
+    /// hasn't moved.
+    pub fn resolve(&self, url: &Url) -> Url {
+        let mut current = url.clone();
+        for _ in 0..MAX_MOVES {
+            match self.targets.get(current.as_str()) {
+                Some(target) if target != url => current = target.clone(),
+                _ => break,
+            }
+        }
+        current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_follows_moves() {
+        let mut moved = MovedPages::default();
+        moved.record(&url("gemini://a/1"), &url("gemini://a/2"));
+        moved.record(&url("gemini://a/2"), &url("gemini://b/3"));
+        assert_eq!(moved.resolve(&url("gemini://a/1")), url("gemini://b/3"));
+        assert_eq!(moved.resolve(&url("gemini://a/2")), url("gemini://b/3"));
+        assert_eq!(moved.resolve(&url("gemini://a/9")), url("gemini://a/9"));
+    }
+
+    #[test]
+    fn test_resolve_stops_on_cycles() {
+        let mut moved = MovedPages::default();
+        moved.record(&url("gemini://a/1"), &url("gemini://a/2"));
+        moved.record(&url("gemini://a/2"), &url("gemini://a/1"));
+        assert_eq!(moved.resolve(&url("gemini://a/1")), url("gemini://a/2"));
+        moved.record(&url("gemini://a/3"), &url("gemini://a/3"));
+        assert_eq!(moved.resolve(&url("gemini://a/3")), url("gemini://a/3"));
+    }
+}
@@ -1,3 +1,10 @@
+use std::io::{self, Write};
+
+use crate::text::{self, strip_ansi};
+
+const REVERSE: &str = "\x1b[7m";
+const REVERSE_OFF: &str = "\x1b[27m";
+
 /// Paginated display for rendered output lines.
 ///
 /// When a page's content exceeds the terminal height, the Pager shows one
@@ -7,6 +14,10 @@ pub struct Pager {
     lines: Vec<String>,
     offset: usize,
     page_height: usize,
+    /// Search pattern whose matches are shown in reverse video.
+    highlight: Option<String>,
+    /// Whether a `[Page X/Y]` line follows each page.
+    status_line: bool,
 }
 
 impl Pager {
@@ -18,24 +29,45 @@ impl Pager {
             lines,
             offset: 0,
             page_height: page_height.max(1),
+            highlight: None,
+            status_line: true,
         }
     }
 
-    /// Print the current page of lines to stdout.
-    /// After the content lines, if there is more content beyond the current
-    /// page, prints a status line: `[Page X/Y — 'n':next 'p':prev]`
+    /// Show or hide the `[Page X/Y]` line below each page.
+    pub fn set_status_line(&mut self, show: bool) {
+        self.status_line = show;
+    }
+
+    /// Print the current page of lines to stdout. See
+    /// `display_current_page_to`.
     pub fn display_current_page(&self) {
+        // Nothing useful to do if stdout has gone away
+        let _ = self.display_current_page_to(&mut io::stdout().lock());
+    }
+
+    /// Write the current page of lines to `w`.
+    /// After the content lines, if there is more content beyond the current
+    /// page, writes a status line: `[Page X/Y N% — 'n':next 'p':prev]`,
+    /// unless it has been turned off with `set_status_line`.
+    pub fn display_current_page_to(&self, w: &mut dyn Write) -> io::Result<()> {
         let end = std::cmp::min(self.offset + self.page_height, self.lines.len());
         for line in &self.lines[self.offset..end] {
-            println!("{line}");
+            match &self.highlight {
+                Some(pattern) => writeln!(w, "{}", highlight_matches(line, pattern))?,
+                None => writeln!(w, "{line}")?,
+            }
         }
-        if self.needs_pagination() {
-            println!(
-                "\x1b[2m[Page {}/{} \u{2014} 'n':next 'p':prev]\x1b[0m",
+        if self.needs_pagination() && self.status_line {
+            writeln!(
+                w,
+                "\x1b[2m[Page {}/{} {}% \u{2014} 'n':next 'p':prev]\x1b[0m",
                 self.current_page(),
-                self.total_pages()
-            );
+                self.total_pages(),
+                self.percent()
+            )?;
         }
+        w.flush()
     }
 
     /// Advance to the next page. Returns true if the page changed,
@@ -64,6 +96,86 @@ impl Pager {
         true
     }
 
+    /// Move the view by `delta` lines (negative scrolls up). Stops at the top
+    /// and once the last line is on screen. Returns true if the view moved.
+    pub fn scroll_lines(&mut self, delta: isize) -> bool {
+        let old = self.offset;
+        if delta < 0 {
+            self.offset = self.offset.saturating_sub(delta.unsigned_abs());
+        } else {
+            let last_screen = self.lines.len().saturating_sub(self.page_height);
+            let limit = last_screen.max(self.offset);
+            self.offset = (self.offset + delta as usize).min(limit);
+        }
+        self.offset != old
+    }
+
+    /// Half the page height, the step for half-page scrolling.
+    pub fn half_page(&self) -> usize {
+        (self.page_height / 2).max(1)
+    }
+
+    /// Change the number of lines per page, e.g. after the terminal was
+    /// resized. The offset moves back to the start of the page containing the
+    /// current top line, clamped to the last page.
+    pub fn set_page_height(&mut self, page_height: usize) {
+        let top_line = self.offset;
+        self.page_height = page_height.max(1);
+        self.goto_page(top_line / self.page_height + 1);
+    }
+
+    /// Jump to page `n` (1-indexed), clamped to `[1, total_pages]`.
+    pub fn goto_page(&mut self, n: usize) {
+        let page = n.clamp(1, self.total_pages());
+        self.offset = (page - 1) * self.page_height;
+    }
+
+    /// Jump to the first page.
+    pub fn goto_start(&mut self) {
+        self.offset = 0;
+    }
+
+    /// Jump to the last page.
+    pub fn goto_end(&mut self) {
+        self.goto_page(self.total_pages());
+    }
+
+    /// Find the first line at or after `from_offset` whose text (ignoring
+    /// ANSI escapes) contains `pattern`. Returns the line index.
+    pub fn search(&self, pattern: &str, from_offset: usize) -> Option<usize> {
+        self.lines
+            .iter()
+            .enumerate()
+            .skip(from_offset)
+            .find(|(_, line)| strip_ansi(line).contains(pattern))
+            .map(|(i, _)| i)
+    }
+
+    /// Find the last line before `before` whose text (ignoring ANSI
+    /// escapes) contains `pattern`. Returns the line index.
+    pub fn search_back(&self, pattern: &str, before: usize) -> Option<usize> {
+        self.lines
+            .iter()
+            .take(before)
+            .rposition(|line| strip_ansi(line).contains(pattern))
+    }
+
+    /// Scroll so that `line` is the first line shown.
+    pub fn jump_to_line(&mut self, line: usize) {
+        self.offset = line.min(self.lines.len().saturating_sub(1));
+    }
+
+    /// The index of the first line currently shown.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Highlight occurrences of `pattern` when displaying, or stop
+    /// highlighting with None.
+    pub fn set_highlight(&mut self, pattern: Option<String>) {
+        self.highlight = pattern.filter(|p| !p.is_empty());
+    }
+
     /// Returns true if the content requires pagination (more lines than page_height).
     pub fn needs_pagination(&self) -> bool {
         self.lines.len() > self.page_height
@@ -81,16 +193,40 @@ impl Pager {
     pub fn current_page(&self) -> usize {
         (self.offset / self.page_height) + 1
     }
+
+    /// How far through the content the view is, from `offset` and the total
+    /// line count. 100 once the last line is visible, 0 anywhere on page 1.
+    pub fn percent(&self) -> usize {
+        if self.offset + self.page_height >= self.lines.len() {
+            return 100;
+        }
+        if self.current_page() == 1 {
+            return 0;
+        }
+        self.offset * 100 / self.lines.len()
+    }
 }
 
 /// Detect the terminal height in rows.
 ///
 /// Strategy:
-/// 1. Try the LINES environment variable (set by many terminals/shells).
-/// 2. Try reading from the terminal via libc ioctl TIOCGWINSZ on stdout.
+/// 1. Try reading from the terminal via libc ioctl TIOCGWINSZ on stdout,
+///    which follows resizes.
+/// 2. Try the LINES environment variable (set by many shells, but only
+///    when the program starts).
 /// 3. Fall back to a default of 24 rows.
 pub fn terminal_height() -> usize {
-    // 1. Check LINES env var
+    // 1. ioctl TIOCGWINSZ
+    #[cfg(unix)]
+    {
+        use libc::{ioctl, winsize, STDOUT_FILENO, TIOCGWINSZ};
+        let mut ws: winsize = unsafe { std::mem::zeroed() };
+        if unsafe { ioctl(STDOUT_FILENO, TIOCGWINSZ, &mut ws) } == 0 && ws.ws_row > 0 {
+            return ws.ws_row as usize;
+        }
+    }
+
+    // 2. Check LINES env var
     if let Ok(val) = std::env::var("LINES") {
         if let Ok(n) = val.parse::<usize>() {
             if n > 0 {
@@ -99,18 +235,119 @@ pub fn terminal_height() -> usize {
         }
     }
 
-    // 2. ioctl TIOCGWINSZ
+    // 3. Default
+    24
+}
+
+/// Lines of content per page: the terminal height minus one line reserved
+/// for the status line.
+pub fn page_height() -> usize {
+    std::cmp::max(terminal_height().saturating_sub(1), 1)
+}
+
+/// Write `lines` to `w`, a page at a time starting at row `offset` when
+/// `page_height` is given and they don't fit, or all at once otherwise.
+/// Returns the pager to scroll through the rest with, if paginating;
+/// `status_line` says whether it shows `[Page X/Y]` under each page.
+pub fn show_to(
+    lines: Vec<String>,
+    page_height: Option<usize>,
+    offset: usize,
+    status_line: bool,
+    w: &mut dyn Write,
+) -> io::Result<Option<Pager>> {
+    match page_height {
+        Some(page_height) if lines.len() > page_height => {
+            let mut pager = Pager::new(lines, page_height);
+            pager.jump_to_line(offset);
+            pager.set_status_line(status_line);
+            pager.display_current_page_to(w)?;
+            Ok(Some(pager))
+        }
+        _ => {
+            for line in &lines {
+                writeln!(w, "{line}")?;
+            }
+            w.flush()?;
+            Ok(None)
+        }
+    }
+}
+
+/// Detect the terminal width in columns.
+///
+/// Mirrors `terminal_height`: TIOCGWINSZ on stdout, then the COLUMNS
+/// environment variable, then a default of 80 columns.
+pub fn terminal_width() -> usize {
+    // 1. ioctl TIOCGWINSZ
     #[cfg(unix)]
     {
         use libc::{ioctl, winsize, STDOUT_FILENO, TIOCGWINSZ};
         let mut ws: winsize = unsafe { std::mem::zeroed() };
-        if unsafe { ioctl(STDOUT_FILENO, TIOCGWINSZ, &mut ws) } == 0 && ws.ws_row > 0 {
-            return ws.ws_row as usize;
+        if unsafe { ioctl(STDOUT_FILENO, TIOCGWINSZ, &mut ws) } == 0 && ws.ws_col > 0 {
+            return ws.ws_col as usize;
+        }
+    }
+
+    // 2. Check COLUMNS env var
+    if let Ok(val) = std::env::var("COLUMNS") {
+        if let Ok(n) = val.parse::<usize>() {
+            if n > 0 {
+                return n;
+            }
         }
     }
 
     // 3. Default
-    24
+    80
+}
+
+/// Split `lines` into terminal rows `width` columns wide, so that paging
+/// counts what actually fits on screen. Each of `offsets` (indices into
+/// `lines`, such as heading positions) is moved to the row its line now
+/// starts on.
+pub fn fold_lines(lines: Vec<String>, width: usize, offsets: &mut [usize]) -> Vec<String> {
+    let mut rows = Vec::with_capacity(lines.len());
+    let mut starts = Vec::with_capacity(lines.len());
+    for line in &lines {
+        starts.push(rows.len());
+        rows.extend(text::fold(line, width));
+    }
+    for offset in offsets {
+        *offset = starts.get(*offset).copied().unwrap_or(rows.len());
+    }
+    rows
+}
+
+/// Wrap each occurrence of `pattern` in `line` in reverse video. Matches
+/// are found in the text without escape sequences, as `search` does, and
+/// reverse video is turned back on after any escape inside a match.
+fn highlight_matches(line: &str, pattern: &str) -> String {
+    if pattern.is_empty() {
+        return line.to_string();
+    }
+    let (plain, raw_at) = text::strip_ansi_offsets(line);
+    let mut out = String::with_capacity(line.len());
+    let mut copied = 0;
+    for (start, _) in plain.match_indices(pattern) {
+        let end = start + pattern.len();
+        out.push_str(&line[copied..raw_at[start]]);
+        out.push_str(REVERSE);
+        copied = raw_at[start];
+        for i in start + 1..end {
+            if raw_at[i] != raw_at[i - 1] + 1 {
+                out.push_str(&line[copied..raw_at[i]]);
+                out.push_str(REVERSE);
+                copied = raw_at[i];
+            }
+        }
+        let match_end = raw_at[end - 1] + 1;
+        out.push_str(&line[copied..match_end]);
+        out.push_str(REVERSE_OFF);
+        copied = match_end;
+    }
+    out.push_str(&line[copied..]);
+    out
 }
 
 #[cfg(test)]
@@ -202,9 +439,246 @@ mod tests {
         assert_eq!(pager.current_page(), 1);
     }
 
+    #[test]
+    fn test_set_page_height_realigns_offset() {
+        let mut pager = Pager::new(make_lines(50), 10);
+        pager.goto_page(3);
+        assert_eq!(pager.offset(), 20);
+
+        pager.set_page_height(15);
+        assert_eq!(pager.offset(), 15);
+        assert_eq!(pager.current_page(), 2);
+        assert_eq!(pager.total_pages(), 4);
+    }
+
+    #[test]
+    fn test_set_page_height_clamps_to_last_page() {
+        let mut pager = Pager::new(make_lines(50), 5);
+        pager.goto_end();
+        pager.set_page_height(40);
+        assert_eq!(pager.current_page(), 2);
+        assert_eq!(pager.offset(), 40);
+        pager.set_page_height(0);
+        assert_eq!(pager.page_height, 1);
+    }
+
+    #[test]
+    fn test_scroll_lines() {
+        let mut pager = Pager::new(make_lines(25), 10);
+        assert!(pager.scroll_lines(1));
+        assert_eq!(pager.offset(), 1);
+        assert!(pager.scroll_lines(5));
+        assert_eq!(pager.offset(), 6);
+        assert!(pager.scroll_lines(-2));
+        assert_eq!(pager.offset(), 4);
+    }
+
+    #[test]
+    fn test_scroll_lines_past_top() {
+        let mut pager = Pager::new(make_lines(25), 10);
+        pager.scroll_lines(3);
+        assert!(pager.scroll_lines(-10));
+        assert_eq!(pager.offset(), 0);
+        assert!(!pager.scroll_lines(-1));
+    }
+
+    #[test]
+    fn test_scroll_lines_past_bottom() {
+        let mut pager = Pager::new(make_lines(25), 10);
+        assert!(pager.scroll_lines(100));
+        assert_eq!(pager.offset(), 15);
+        assert!(!pager.scroll_lines(1));
+
+        // Page-aligned last page sits beyond the last full screen; stay put
+        pager.goto_end();
+        assert!(!pager.scroll_lines(1));
+        assert_eq!(pager.offset(), 20);
+    }
+
+    #[test]
+    fn test_half_page() {
+        assert_eq!(Pager::new(make_lines(25), 10).half_page(), 5);
+        assert_eq!(Pager::new(make_lines(25), 1).half_page(), 1);
+    }
+
+    #[test]
+    fn test_goto_page() {
+        let mut pager = Pager::new(make_lines(25), 10);
+        pager.goto_page(2);
+        assert_eq!(pager.current_page(), 2);
+        assert_eq!(pager.offset(), 10);
+    }
+
+    #[test]
+    fn test_goto_page_past_end_clamps() {
+        let mut pager = Pager::new(make_lines(25), 10);
+        pager.goto_page(99);
+        assert_eq!(pager.current_page(), 3);
+        assert!(!pager.next_page());
+        pager.goto_page(0);
+        assert_eq!(pager.current_page(), 1);
+    }
+
+    #[test]
+    fn test_goto_on_empty_document() {
+        let mut pager = Pager::new(vec![], 10);
+        pager.goto_page(5);
+        assert_eq!(pager.current_page(), 1);
+        pager.goto_end();
+        assert_eq!(pager.current_page(), 1);
+        assert_eq!(pager.offset(), 0);
+    }
+
+    #[test]
+    fn test_goto_start_and_end() {
+        let mut pager = Pager::new(make_lines(25), 10);
+        pager.goto_end();
+        assert_eq!(pager.current_page(), 3);
+        assert_eq!(pager.offset(), 20);
+        pager.goto_start();
+        assert_eq!(pager.current_page(), 1);
+    }
+
+    #[test]
+    fn test_search_finds_next_match() {
+        let mut lines = make_lines(30);
+        lines[12] = "\x1b[36m[1] needle link\x1b[0m".to_string();
+        lines[25] = "another needle".to_string();
+        let pager = Pager::new(lines, 10);
+
+        assert_eq!(pager.search("needle", 0), Some(12));
+        assert_eq!(pager.search("needle", 13), Some(25));
+        assert_eq!(pager.search("needle", 26), None);
+        assert_eq!(pager.search("missing", 0), None);
+
+        assert_eq!(pager.search_back("needle", 25), Some(12));
+        assert_eq!(pager.search_back("needle", 30), Some(25));
+        assert_eq!(pager.search_back("needle", 12), None);
+    }
+
+    #[test]
+    fn test_search_ignores_ansi_codes() {
+        let pager = Pager::new(vec!["\x1b[1mbold\x1b[0m text".to_string()], 10);
+        assert_eq!(pager.search("bold text", 0), Some(0));
+        assert_eq!(pager.search("[1m", 0), None);
+    }
+
+    #[test]
+    fn test_jump_to_line_clamps() {
+        let mut pager = Pager::new(make_lines(25), 10);
+        pager.jump_to_line(12);
+        assert_eq!(pager.offset(), 12);
+        assert_eq!(pager.current_page(), 2);
+        pager.jump_to_line(100);
+        assert_eq!(pager.offset(), 24);
+    }
+
+    #[test]
+    fn test_highlight_matches() {
+        assert_eq!(
+            highlight_matches("a cat and a cat", "cat"),
+            "a \x1b[7mcat\x1b[27m and a \x1b[7mcat\x1b[27m"
+        );
+        assert_eq!(highlight_matches("plain", ""), "plain");
+    }
+
+    #[test]
+    fn test_highlight_matches_across_escapes() {
+        assert_eq!(
+            highlight_matches("\x1b[1mbold\x1b[0m text", "bold text"),
+            "\x1b[1m\x1b[7mbold\x1b[0m\x1b[7m text\x1b[27m"
+        );
+        assert_eq!(
+            highlight_matches("\x1b[36mlink\x1b[0m", "link"),
+            "\x1b[36m\x1b[7mlink\x1b[27m\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_percent() {
+        let mut pager = Pager::new(make_lines(100), 10);
+        assert_eq!(pager.percent(), 0);
+        pager.goto_page(5);
+        assert_eq!(pager.percent(), 40);
+        pager.goto_end();
+        assert_eq!(pager.percent(), 100);
+    }
+
+    #[test]
+    fn test_percent_first_page_partially_scrolled() {
+        let mut pager = Pager::new(make_lines(100), 10);
+        pager.jump_to_line(5);
+        assert_eq!(pager.percent(), 0);
+    }
+
+    #[test]
+    fn test_percent_last_page_is_full() {
+        let mut pager = Pager::new(make_lines(25), 10);
+        pager.next_page();
+        pager.next_page();
+        assert_eq!(pager.percent(), 100);
+    }
+
     #[test]
     fn test_page_height_clamp() {
         let pager = Pager::new(make_lines(5), 0);
         assert_eq!(pager.page_height, 1);
     }
+
+    #[test]
+    fn test_fold_lines_moves_offsets() {
+        let lines = vec!["x".repeat(25), "# Heading".to_string(), "y".repeat(5)];
+        let mut offsets = [1, 2];
+        let rows = fold_lines(lines, 10, &mut offsets);
+        assert_eq!(rows.len(), 5);
+        assert_eq!(offsets, [3, 4]);
+        assert_eq!(rows[3], "# Heading");
+    }
+
+    fn displayed(pager: &Pager) -> String {
+        let mut out = Vec::new();
+        pager.display_current_page_to(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_display_current_page_to() {
+        let mut pager = Pager::new(make_lines(5), 2);
+        assert_eq!(
+            displayed(&pager),
+            "Line 0\nLine 1\n\x1b[2m[Page 1/3 0% \u{2014} 'n':next 'p':prev]\x1b[0m\n"
+        );
+        pager.goto_end();
+        assert!(displayed(&pager).starts_with("Line 4\n\x1b[2m[Page 3/3 100%"));
+    }
+
+    #[test]
+    fn test_status_line_can_be_hidden() {
+        let mut pager = Pager::new(make_lines(5), 2);
+        pager.set_status_line(false);
+        assert_eq!(displayed(&pager), "Line 0\nLine 1\n");
+    }
+
+    #[test]
+    fn test_show_to_dumps_everything_without_page_height() {
+        let mut out = Vec::new();
+        let pager = show_to(make_lines(50), None, 0, true, &mut out).unwrap();
+        assert!(pager.is_none());
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 50);
+        assert!(!out.contains("[Page"));
+
+        let mut out = Vec::new();
+        let pager = show_to(make_lines(50), Some(10), 0, true, &mut out).unwrap();
+        assert!(pager.is_some());
+        assert!(String::from_utf8(out).unwrap().contains("[Page 1/5"));
+    }
+
+    #[test]
+    fn test_display_short_document_has_no_status_line() {
+        let mut pager = Pager::new(make_lines(2), 10);
+        assert_eq!(displayed(&pager), "Line 0\nLine 1\n");
+        pager.set_highlight(Some("1".to_string()));
+        assert_eq!(displayed(&pager), "Line 0\nLine \x1b[7m1\x1b[27m\n");
+    }
 }
@@ -2,96 +2,192 @@
 #[derive(Debug, PartialEq)]
 pub enum GeminiLine {
     Text(String),
-    Link { url: String, label: String },
-    Heading { level: u8, text: String },
-    ListItem(String),
+    /// An empty line.
+    Blank,
+    Link {
+        url: String,
+        label: String,
+    },
+    Heading {
+        level: u8,
+        text: String,
+    },
+    /// A run of consecutive `* ` lines, one entry per item.
+    List(Vec<String>),
     Quote(String),
-    PreformattedToggle { alt_text: String },
+    PreformattedToggle {
+        alt_text: String,
+    },
     PreformattedText(String),
 }
 
-/// Parse a text/gemini document body into a Vec<GeminiLine>.
+/// Parse a text/gemini document body into `(line_no, GeminiLine)` pairs,
+/// where `line_no` is the 1-based line of the body the item came from (for a
+/// List, the line of its first item).
 /// Tracks preformatted toggle state: lines between ``` markers become
 /// PreformattedText; the ``` lines themselves become PreformattedToggle.
-pub fn parse_gemini(body: &str) -> Vec<GeminiLine> {
+/// Consecutive `* ` lines are gathered into a single List. A leading UTF-8
+/// byte order mark is ignored.
+pub fn parse_gemini(body: &str) -> Vec<(usize, GeminiLine)> {
+    let body = body.strip_prefix('\u{FEFF}').unwrap_or(body);
     let mut lines = Vec::new();
+    let mut list_items: Vec<String> = Vec::new();
+    let mut list_start = 0;
     let mut in_preformatted = false;
 
-    for raw_line in body.lines() {
-        if let Some(after_toggle) = raw_line.strip_prefix("```") {
-            let alt_text = after_toggle.trim().to_string();
-            lines.push(GeminiLine::PreformattedToggle { alt_text });
-            in_preformatted = !in_preformatted;
-            continue;
+    for (index, raw_line) in body.lines().enumerate() {
+        let line_no = index + 1;
+        if !in_preformatted {
+            if let Some(rest) = raw_line.strip_prefix("* ") {
+                if list_items.is_empty() {
+                    list_start = line_no;
+                }
+                list_items.push(rest.to_string());
+                continue;
+            }
         }
-
-        if in_preformatted {
-            lines.push(GeminiLine::PreformattedText(raw_line.to_string()));
-            continue;
+        // Any other line ends the current list
+        if !list_items.is_empty() {
+            lines.push((
+                list_start,
+                GeminiLine::List(std::mem::take(&mut list_items)),
+            ));
         }
 
-        if let Some(rest) = raw_line.strip_prefix("=>") {
-            let rest = rest.trim_start();
-            if rest.is_empty() {
-                lines.push(GeminiLine::Link {
-                    url: String::new(),
-                    label: String::new(),
-                });
-                continue;
+        let line = if let Some(after_toggle) = raw_line.strip_prefix("```") {
+            in_preformatted = !in_preformatted;
+            GeminiLine::PreformattedToggle {
+                alt_text: after_toggle.trim().to_string(),
             }
-            // Split at first whitespace to get URL and optional label
-            let (url, label) = match rest.find(|c: char| c.is_whitespace()) {
-                Some(pos) => {
-                    let url = &rest[..pos];
-                    let label = rest[pos..].trim().to_string();
-                    (url.to_string(), label)
-                }
-                None => (rest.to_string(), rest.to_string()),
-            };
-            lines.push(GeminiLine::Link { url, label });
-        } else if let Some(rest) = raw_line.strip_prefix("### ") {
-            lines.push(GeminiLine::Heading {
-                level: 3,
-                text: rest.to_string(),
-            });
-        } else if let Some(rest) = raw_line.strip_prefix("## ") {
-            lines.push(GeminiLine::Heading {
-                level: 2,
-                text: rest.to_string(),
-            });
-        } else if let Some(rest) = raw_line.strip_prefix("# ") {
-            lines.push(GeminiLine::Heading {
-                level: 1,
-                text: rest.to_string(),
-            });
-        } else if let Some(rest) = raw_line.strip_prefix("* ") {
-            lines.push(GeminiLine::ListItem(rest.to_string()));
-        } else if let Some(rest) = raw_line.strip_prefix("> ") {
-            lines.push(GeminiLine::Quote(rest.to_string()));
-        } else if let Some(rest) = raw_line.strip_prefix(">") {
-            // Quote with no space after >
-            lines.push(GeminiLine::Quote(rest.to_string()));
+        } else if in_preformatted {
+            GeminiLine::PreformattedText(raw_line.to_string())
         } else {
-            lines.push(GeminiLine::Text(raw_line.to_string()));
-        }
+            parse_line(raw_line)
+        };
+        lines.push((line_no, line));
     }
 
+    if !list_items.is_empty() {
+        lines.push((list_start, GeminiLine::List(list_items)));
+    }
     lines
 }
 
+/// Parse one line outside a preformatted block. List items are handled by
+/// the caller.
+fn parse_line(raw_line: &str) -> GeminiLine {
+    if let Some(rest) = raw_line.strip_prefix("=>") {
+        let rest = rest.trim_start();
+        if rest.is_empty() {
+            // Nothing to follow, so not a link; show the line as written
+            return GeminiLine::Text(raw_line.to_string());
+        }
+        let url_end = link_url_end(rest);
+        let raw_url = &rest[..url_end];
+        let label = rest[url_end..].trim();
+        let label = if label.is_empty() { raw_url } else { label };
+        GeminiLine::Link {
+            url: raw_url.replace(' ', "%20").replace('\t', "%09"),
+            label: label.to_string(),
+        }
+    } else if let Some(rest) = raw_line.strip_prefix("### ") {
+        GeminiLine::Heading {
+            level: 3,
+            text: rest.to_string(),
+        }
+    } else if let Some(rest) = raw_line.strip_prefix("## ") {
+        GeminiLine::Heading {
+            level: 2,
+            text: rest.to_string(),
+        }
+    } else if let Some(rest) = raw_line.strip_prefix("# ") {
+        GeminiLine::Heading {
+            level: 1,
+            text: rest.to_string(),
+        }
+    } else if let Some(rest) = raw_line.strip_prefix("> ") {
+        GeminiLine::Quote(rest.to_string())
+    } else if let Some(rest) = raw_line.strip_prefix(">") {
+        // Quote with no space after >
+        GeminiLine::Quote(rest.to_string())
+    } else if raw_line.is_empty() {
+        GeminiLine::Blank
+    } else {
+        GeminiLine::Text(raw_line.to_string())
+    }
+}
+
+/// Find where the URL of a link line ends. Normally that is the first
+/// whitespace, but capsules sometimes put unencoded spaces in paths
+/// (`=> /a b/c.gmi Label`). When the URL is a relative path, a following
+/// word that looks like the rest of one (see `continues_path`) is kept in
+/// the URL.
+fn link_url_end(rest: &str) -> usize {
+    let mut end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let first = &rest[..end];
+    let relative_path = ["/", "./", "../"]
+        .iter()
+        .any(|prefix| first.starts_with(prefix))
+        && !first.starts_with("//");
+    if !relative_path {
+        return end;
+    }
+    while !rest[..end].contains(['?', '#']) {
+        let after = &rest[end..];
+        let token_start = end + (after.len() - after.trim_start().len());
+        let token_len = rest[token_start..]
+            .find(char::is_whitespace)
+            .unwrap_or(rest.len() - token_start);
+        let token = &rest[token_start..token_start + token_len];
+        if !continues_path(token) {
+            break;
+        }
+        end = token_start + token_len;
+    }
+    end
+}
+
+/// Whether a label word reads as the tail of a path rather than prose:
+/// it has a `/` after some text, only path characters, and ends in a
+/// directory (`b/`) or a file with an extension (`b/c.gmi`). Words like
+/// `and/or` don't qualify.
+fn continues_path(token: &str) -> bool {
+    let path_char = |c: char| c.is_alphanumeric() || "-._~%!$&'()*+,;=:@/".contains(c);
+    if token.starts_with('/')
+        || !token.contains('/')
+        || token.contains("://")
+        || !token.chars().all(path_char)
+    {
+        return false;
+    }
+    let last = token.rsplit('/').next().unwrap_or_default();
+    last.is_empty()
+        || last
+            .rfind('.')
+            .is_some_and(|dot| dot > 0 && dot + 1 < last.len())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Parse and drop the line numbers.
+    fn parse_lines(body: &str) -> Vec<GeminiLine> {
+        parse_gemini(body)
+            .into_iter()
+            .map(|(_, line)| line)
+            .collect()
+    }
+
     #[test]
     fn test_text_line() {
-        let lines = parse_gemini("Hello, world!");
+        let lines = parse_lines("Hello, world!");
         assert_eq!(lines, vec![GeminiLine::Text("Hello, world!".to_string())]);
     }
 
     #[test]
     fn test_link_with_label() {
-        let lines = parse_gemini("=> gemini://x/y Link text");
+        let lines = parse_lines("=> gemini://x/y Link text");
         assert_eq!(
             lines,
             vec![GeminiLine::Link {
@@ -103,7 +199,7 @@ mod tests {
 
     #[test]
     fn test_link_without_label() {
-        let lines = parse_gemini("=> gemini://x/y");
+        let lines = parse_lines("=> gemini://x/y");
         assert_eq!(
             lines,
             vec![GeminiLine::Link {
@@ -113,9 +209,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_bare_link_marker_is_text() {
+        assert_eq!(parse_lines("=>"), vec![GeminiLine::Text("=>".to_string())]);
+        assert_eq!(
+            parse_lines("=>   "),
+            vec![GeminiLine::Text("=>   ".to_string())]
+        );
+    }
+
     #[test]
     fn test_link_extra_whitespace() {
-        let lines = parse_gemini("=>   gemini://x   some label");
+        let lines = parse_lines("=>   gemini://x   some label");
         assert_eq!(
             lines,
             vec![GeminiLine::Link {
@@ -125,9 +230,70 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_link_with_space_in_path() {
+        let lines = parse_lines("=> /a b/c.gmi Label");
+        assert_eq!(
+            lines,
+            vec![GeminiLine::Link {
+                url: "/a%20b/c.gmi".to_string(),
+                label: "Label".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_link_with_space_in_path_without_label() {
+        let lines = parse_lines("=> /a b/");
+        assert_eq!(
+            lines,
+            vec![GeminiLine::Link {
+                url: "/a%20b/".to_string(),
+                label: "/a b/".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_link_label_words_stay_in_label() {
+        let lines = parse_lines(
+            "=> /docs Read the docs\n=> /a?q=1 b/c\n=> /x gemini://y/z\n\
+             => /faq and/or help\n=> /posts 2024/03/01\n=> gemini://x/a notes/todo.gmi",
+        );
+        assert_eq!(
+            lines,
+            vec![
+                GeminiLine::Link {
+                    url: "/docs".to_string(),
+                    label: "Read the docs".to_string(),
+                },
+                GeminiLine::Link {
+                    url: "/a?q=1".to_string(),
+                    label: "b/c".to_string(),
+                },
+                GeminiLine::Link {
+                    url: "/x".to_string(),
+                    label: "gemini://y/z".to_string(),
+                },
+                GeminiLine::Link {
+                    url: "/faq".to_string(),
+                    label: "and/or help".to_string(),
+                },
+                GeminiLine::Link {
+                    url: "/posts".to_string(),
+                    label: "2024/03/01".to_string(),
+                },
+                GeminiLine::Link {
+                    url: "gemini://x/a".to_string(),
+                    label: "notes/todo.gmi".to_string(),
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_heading_h1() {
-        let lines = parse_gemini("# Title");
+        let lines = parse_lines("# Title");
         assert_eq!(
             lines,
             vec![GeminiLine::Heading {
@@ -139,7 +305,7 @@ mod tests {
 
     #[test]
     fn test_heading_h2() {
-        let lines = parse_gemini("## Subtitle");
+        let lines = parse_lines("## Subtitle");
         assert_eq!(
             lines,
             vec![GeminiLine::Heading {
@@ -151,7 +317,7 @@ mod tests {
 
     #[test]
     fn test_heading_h3() {
-        let lines = parse_gemini("### Sub-sub");
+        let lines = parse_lines("### Sub-sub");
         assert_eq!(
             lines,
             vec![GeminiLine::Heading {
@@ -163,20 +329,70 @@ mod tests {
 
     #[test]
     fn test_list_item() {
-        let lines = parse_gemini("* Item");
-        assert_eq!(lines, vec![GeminiLine::ListItem("Item".to_string())]);
+        let lines = parse_lines("* Item");
+        assert_eq!(lines, vec![GeminiLine::List(vec!["Item".to_string()])]);
+    }
+
+    #[test]
+    fn test_list_interrupted_by_other_line() {
+        let lines = parse_lines("* a\n* b\ntext\n* c\n=> gemini://x/\n* d\n```\n* e");
+        assert_eq!(
+            lines,
+            vec![
+                GeminiLine::List(vec!["a".to_string(), "b".to_string()]),
+                GeminiLine::Text("text".to_string()),
+                GeminiLine::List(vec!["c".to_string()]),
+                GeminiLine::Link {
+                    url: "gemini://x/".to_string(),
+                    label: "gemini://x/".to_string(),
+                },
+                GeminiLine::List(vec!["d".to_string()]),
+                GeminiLine::PreformattedToggle {
+                    alt_text: String::new()
+                },
+                GeminiLine::PreformattedText("* e".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_at_end_of_document() {
+        let lines = parse_lines("# Items\n* one\n* two\n");
+        assert_eq!(
+            lines,
+            vec![
+                GeminiLine::Heading {
+                    level: 1,
+                    text: "Items".to_string()
+                },
+                GeminiLine::List(vec!["one".to_string(), "two".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blank_line_separates_lists() {
+        let lines = parse_lines("* a\n\n* b");
+        assert_eq!(
+            lines,
+            vec![
+                GeminiLine::List(vec!["a".to_string()]),
+                GeminiLine::Blank,
+                GeminiLine::List(vec!["b".to_string()]),
+            ]
+        );
     }
 
     #[test]
     fn test_quote_line() {
-        let lines = parse_gemini("> Quoted");
+        let lines = parse_lines("> Quoted");
         assert_eq!(lines, vec![GeminiLine::Quote("Quoted".to_string())]);
     }
 
     #[test]
     fn test_preformatted_block() {
         let input = "```\nsome code\n```";
-        let lines = parse_gemini(input);
+        let lines = parse_lines(input);
         assert_eq!(
             lines,
             vec![
@@ -194,7 +410,7 @@ mod tests {
     #[test]
     fn test_preformatted_no_parsing() {
         let input = "```\n=> gemini://x/y Link\n```";
-        let lines = parse_gemini(input);
+        let lines = parse_lines(input);
         assert_eq!(
             lines,
             vec![
@@ -211,7 +427,7 @@ mod tests {
 
     #[test]
     fn test_preformatted_alt_text() {
-        let lines = parse_gemini("```alt");
+        let lines = parse_lines("```alt");
         assert_eq!(
             lines,
             vec![GeminiLine::PreformattedToggle {
@@ -223,7 +439,7 @@ mod tests {
     #[test]
     fn test_preformatted_unclosed() {
         let input = "```\nline1\nline2";
-        let lines = parse_gemini(input);
+        let lines = parse_lines(input);
         assert_eq!(
             lines,
             vec![
@@ -236,16 +452,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_blank_line() {
+        let lines = parse_lines("a\n\n b");
+        assert_eq!(
+            lines,
+            vec![
+                GeminiLine::Text("a".to_string()),
+                GeminiLine::Blank,
+                GeminiLine::Text(" b".to_string()),
+            ]
+        );
+    }
+
     #[test]
     fn test_empty_document() {
-        let lines = parse_gemini("");
+        let lines = parse_lines("");
         assert!(lines.is_empty());
     }
 
     #[test]
     fn test_mixed_content() {
         let input = "# Welcome\n\nSome text here.\n=> gemini://example.com/ Home\n* Item one\n* Item two\n> A quote\n```code\nfn main() {}\n```";
-        let lines = parse_gemini(input);
+        let lines = parse_lines(input);
         assert_eq!(
             lines,
             vec![
@@ -253,14 +482,13 @@ mod tests {
                     level: 1,
                     text: "Welcome".to_string()
                 },
-                GeminiLine::Text(String::new()),
+                GeminiLine::Blank,
                 GeminiLine::Text("Some text here.".to_string()),
                 GeminiLine::Link {
                     url: "gemini://example.com/".to_string(),
                     label: "Home".to_string()
                 },
-                GeminiLine::ListItem("Item one".to_string()),
-                GeminiLine::ListItem("Item two".to_string()),
+                GeminiLine::List(vec!["Item one".to_string(), "Item two".to_string()]),
                 GeminiLine::Quote("A quote".to_string()),
                 GeminiLine::PreformattedToggle {
                     alt_text: "code".to_string()
@@ -272,4 +500,26 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_leading_bom_is_stripped() {
+        let lines = parse_lines("\u{FEFF}# Title");
+        assert_eq!(
+            lines,
+            vec![GeminiLine::Heading {
+                level: 1,
+                text: "Title".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_line_numbers() {
+        let numbers: Vec<usize> = parse_gemini("# T\n\n* a\n* b\n```\ncode\nmore\n```\ntext")
+            .iter()
+            .map(|(line_no, _)| *line_no)
+            .collect();
+        // The list starts on line 3; preformatted lines keep their own numbers
+        assert_eq!(numbers, vec![1, 2, 3, 5, 6, 7, 8, 9]);
+    }
 }
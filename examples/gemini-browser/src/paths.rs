@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+const APP_DIR: &str = "gemini-browser";
+
+/// Directory holding the browser's persistent configuration.
+///
+/// Uses `$XDG_CONFIG_HOME/gemini-browser` when set, otherwise
+/// `~/.config/gemini-browser`. Returns None if neither variable is available.
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = non_empty_env("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(dir).join(APP_DIR));
+    }
+    non_empty_env("HOME").map(|home| PathBuf::from(home).join(".config").join(APP_DIR))
+}
+
+/// Directory holding data the browser accumulates, such as history.
+///
+/// Uses `$XDG_DATA_HOME/gemini-browser` when set, otherwise
+/// `~/.local/share/gemini-browser`. Returns None if neither variable is
+/// available.
+pub fn data_dir() -> Option<PathBuf> {
+    if let Some(dir) = non_empty_env("XDG_DATA_HOME") {
+        return Some(PathBuf::from(dir).join(APP_DIR));
+    }
+    non_empty_env("HOME").map(|home| {
+        PathBuf::from(home)
+            .join(".local")
+            .join("share")
+            .join(APP_DIR)
+    })
+}
+
+fn non_empty_env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
@@ -0,0 +1,275 @@
+//! Recording browsing sessions to a file and replaying them offline.
+//!
+//! A session file holds one entry per request, in the order they were made:
+//!
+//! ```text
+//! > gemini://example.com/
+//! < 27
+//! 20 text/gemini\r\n# Example\n
+//! ```
+//!
+//! The `>` line is the request line without its CRLF and the `<` line gives
+//! the length of the raw response (header and body) that follows, which is
+//! ended by a newline of its own.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use url::Url;
+
+use crate::gemini::{
+    ClientEvent, Connection, EventHandler, FetchMetrics, GeminiClient, GeminiError, Transport,
+};
+
+/// Recorded responses to one request line, and how many have been served.
+type Responses = (Vec<Vec<u8>>, usize);
+
+/// Append one session entry to `w`.
+fn write_entry(w: &mut impl Write, request: &str, response: &[u8]) -> io::Result<()> {
+    writeln!(w, "> {request}")?;
+    writeln!(w, "< {}", response.len())?;
+    w.write_all(response)?;
+    writeln!(w)?;
+    w.flush()
+}
+
+/// Read every entry of a session file, oldest first.
+fn read_entries(r: &mut impl BufRead) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+    let mut entries = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if r.read_line(&mut line)? == 0 {
+            return Ok(entries);
+        }
+        let request = line
+            .trim_end_matches('\n')
+            .strip_prefix("> ")
+            .ok_or_else(|| invalid("expected a request line"))?
+            .to_string();
+
+        line.clear();
+        r.read_line(&mut line)?;
+        let len: usize = line
+            .trim_end_matches('\n')
+            .strip_prefix("< ")
+            .and_then(|len| len.parse().ok())
+            .ok_or_else(|| invalid("expected a response length"))?;
+
+        let mut response = vec![0; len];
+        r.read_exact(&mut response)?;
+        let mut end = [0; 1];
+        r.read_exact(&mut end)?;
+        if end != *b"\n" {
+            return Err(invalid("response longer than its length"));
+        }
+        entries.push((request, response));
+    }
+}
+
+/// A transport that passes requests to another one and appends each
+/// exchange to a session file.
+///
+/// A response is written out once the client is done reading it, with
+/// whatever was received by then. Failing to write it is reported to the
+/// client's event handler as a `ClientEvent::Warning`.
+#[derive(Debug)]
+pub struct Recorder {
+    inner: Arc<dyn Transport>,
+    file: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl Recorder {
+    /// Record requests sent through `inner` to a new file at `path`,
+    /// replacing any file already there.
+    pub fn create(path: &Path, inner: Arc<dyn Transport>) -> io::Result<Self> {
+        Ok(Self {
+            inner,
+            file: Arc::new(Mutex::new(BufWriter::new(File::create(path)?))),
+        })
+    }
+}
+
+impl Transport for Recorder {
+    fn send(
+        &self,
+        client: &GeminiClient,
+        url: &Url,
+        request: &str,
+        payload: &[u8],
+    ) -> Result<Connection, GeminiError> {
+        let mut connection = self.inner.send(client, url, request, payload)?;
+        connection.stream = Box::new(Tee {
+            inner: connection.stream,
+            request: request.trim_end().to_string(),
+            received: Vec::new(),
+            file: self.file.clone(),
+            on_event: client.event_handler(),
+        });
+        Ok(connection)
+    }
+}
+
+/// A response stream that keeps a copy of everything read from it, saved
+/// to the session file when the stream is dropped.
+struct Tee {
+    inner: Box<dyn Read + Send>,
+    request: String,
+    received: Vec<u8>,
+    file: Arc<Mutex<BufWriter<File>>>,
+    on_event: Option<EventHandler>,
+}
+
+impl Read for Tee {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.received.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl Drop for Tee {
+    fn drop(&mut self) {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(e) = write_entry(&mut *file, &self.request, &self.received) {
+            if let Some(handler) = &self.on_event {
+                handler.call(&ClientEvent::Warning(format!(
+                    "could not record {}: {e}",
+                    self.request
+                )));
+            }
+        }
+    }
+}
+
+/// A transport that answers requests from a session file instead of the
+/// network, matching them by request line.
+///
+/// A request made several times gets the recorded responses in turn, then
+/// the last one again.
+#[derive(Debug)]
+pub struct Replayer {
+    responses: Mutex<HashMap<String, Responses>>,
+}
+
+impl Replayer {
+    /// Load the session recorded at `path`.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let entries = read_entries(&mut BufReader::new(File::open(path)?))?;
+        let mut responses: HashMap<String, Responses> = HashMap::new();
+        for (request, response) in entries {
+            responses.entry(request).or_default().0.push(response);
+        }
+        Ok(Self {
+            responses: Mutex::new(responses),
+        })
+    }
+}
+
+impl Transport for Replayer {
+    fn send(
+        &self,
+        _client: &GeminiClient,
+        _url: &Url,
+        request: &str,
+        _payload: &[u8],
+    ) -> Result<Connection, GeminiError> {
+        let request = request.trim_end();
+        let mut responses = self
+            .responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some((recorded, served)) = responses.get_mut(request) else {
+            return Err(GeminiError::ConnectionFailed(format!(
+                "no recorded response for {request}"
+            )));
+        };
+        let response = recorded[(*served).min(recorded.len() - 1)].clone();
+        *served += 1;
+        Ok(Connection {
+            stream: Box::new(Cursor::new(response)),
+            certificate: None,
+            metrics: FetchMetrics::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini::Network;
+    use crate::test_support::MockServer;
+
+    fn session_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "gemini-browser-session-{name}-{}",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_entries_round_trip() {
+        let mut file = Vec::new();
+        write_entry(&mut file, "gemini://a/", b"20 text/gemini\r\n# A\n").unwrap();
+        write_entry(&mut file, "gemini://a/b", b"51 Not found\r\n").unwrap();
+        write_entry(&mut file, "gemini://a/", b"").unwrap();
+        let entries = read_entries(&mut Cursor::new(file)).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    "gemini://a/".to_string(),
+                    b"20 text/gemini\r\n# A\n".to_vec()
+                ),
+                ("gemini://a/b".to_string(), b"51 Not found\r\n".to_vec()),
+                ("gemini://a/".to_string(), Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_entries_rejects_bad_framing() {
+        assert!(read_entries(&mut Cursor::new("gemini://a/\n")).is_err());
+        assert!(read_entries(&mut Cursor::new("> gemini://a/\n< x\n")).is_err());
+        assert!(read_entries(&mut Cursor::new("> gemini://a/\n< 10\nshort\n")).is_err());
+        assert!(read_entries(&mut Cursor::new("> gemini://a/\n< 2\nlong\n")).is_err());
+    }
+
+    #[test]
+    fn test_record_then_replay_offline() {
+        let server = MockServer::new()
+            .route("/", "20 text/gemini\r\n# Recorded\n")
+            .route("/missing", "51 Not found\r\n")
+            .start();
+        let path = session_path("replay");
+        let client = |transport: Arc<dyn Transport>| {
+            GeminiClient::builder()
+                .cert_verifier(Arc::new(crate::verify::InsecureVerifier))
+                .transport(transport)
+                .build()
+        };
+
+        let recorder = Recorder::create(&path, Arc::new(Network)).unwrap();
+        let recording = client(Arc::new(recorder));
+        assert_eq!(recording.fetch(&server.url("/")).unwrap().status, 20);
+        assert_eq!(recording.fetch(&server.url("/missing")).unwrap().status, 51);
+        drop(recording);
+
+        let replaying = client(Arc::new(Replayer::load(&path).unwrap()));
+        let response = replaying.fetch(&server.url("/")).unwrap();
+        assert_eq!(response.meta, "text/gemini");
+        assert_eq!(response.body.as_deref(), Some(&b"# Recorded\n"[..]));
+        let response = replaying.fetch(&server.url("/missing")).unwrap();
+        assert_eq!((response.status, response.meta.as_str()), (51, "Not found"));
+        assert!(matches!(
+            replaying.fetch(&server.url("/other")),
+            Err(GeminiError::ConnectionFailed(_))
+        ));
+        assert_eq!(server.requests().len(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+}
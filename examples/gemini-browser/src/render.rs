@@ -1,63 +1,638 @@
+use std::collections::HashSet;
+
+use url::Url;
+
+use crate::gemini::{self, StatusClass};
+use crate::highlight::BlockHighlighter;
 use crate::parser::GeminiLine;
+use crate::text::{char_width, display_width, expand_tabs, fold};
+use crate::theme::Theme;
+use crate::url_utils;
 
 const RESET: &str = "\x1b[0m";
-const BOLD_BRIGHT_CYAN: &str = "\x1b[1;96m";
-const BOLD: &str = "\x1b[1m";
-const CYAN: &str = "\x1b[36m";
-const DIM_ITALIC: &str = "\x1b[2;3m";
+const DIM: &str = "\x1b[2m";
+
+/// Ends a line cut short by `RenderOptions::truncate_long_lines`.
+const TRUNCATION_MARKER: &str = "\u{203a}";
+
+/// When to emit ANSI escape sequences.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    Always,
+    Never,
+    /// Color only when stdout is a terminal and `NO_COLOR` is not set.
+    Auto,
+}
+
+impl ColorMode {
+    /// Parse a `--color` value: `always`, `never`, or `auto`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "always" => Some(ColorMode::Always),
+            "never" => Some(ColorMode::Never),
+            "auto" => Some(ColorMode::Auto),
+            _ => None,
+        }
+    }
+
+    /// Decide whether color should be used in the current environment.
+    pub fn resolve(self) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+                !no_color && stdout_is_tty()
+            }
+        }
+    }
+}
+
+/// Wrap a status message in the color for its response class: cyan for
+/// input prompts (1x), yellow for temporary failures (4x), red for permanent
+/// ones (5x). Other statuses, or `color` off, leave it plain.
+pub fn status_color(status: u8, message: &str, color: bool) -> String {
+    let code = match StatusClass::of(status) {
+        Some(StatusClass::Input) => "\x1b[36m",
+        Some(StatusClass::TemporaryFailure) => "\x1b[33m",
+        Some(StatusClass::PermanentFailure) => "\x1b[31m",
+        _ => "",
+    };
+    if color && !code.is_empty() {
+        format!("{code}{message}{RESET}")
+    } else {
+        message.to_string()
+    }
+}
+
+/// Whether stdout is a terminal rather than a pipe or file.
+pub fn stdout_is_tty() -> bool {
+    #[cfg(unix)]
+    {
+        unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+/// Terminal title restored when the browser exits or a page has no title.
+pub const DEFAULT_TERMINAL_TITLE: &str = "gemini-browser";
+
+/// The xterm escape that sets the window/tab title, for the caller to
+/// write when stdout is a terminal. Control characters are dropped so page
+/// text cannot end the sequence early.
+pub fn terminal_title_escape(title: &str) -> String {
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    format!("\x1b]0;{title}\x07")
+}
+
+/// Options controlling how `render` formats its output.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Emit ANSI styling. When false, output is plain text.
+    pub color: bool,
+    /// Make link labels clickable with OSC 8 escape sequences. Only
+    /// applies when `color` is set, so plain output stays free of escapes.
+    pub hyperlinks: bool,
+    /// Follow each link label with its URL, e.g. `[1] Home (/index.gmi)`.
+    pub show_link_urls: bool,
+    /// Flag links to another host with `⇗` and that host, e.g.
+    /// `[2] Friend ⇗ other.example`. Needs the page's base URL.
+    pub mark_external: bool,
+    /// Syntax-highlight preformatted blocks whose alt text names a language.
+    /// Only applies when `color` is set.
+    pub highlight: bool,
+    /// Marker shown before each list item.
+    pub bullet: String,
+    /// Show a list whose items all start like `1. ` or `2) ` as a numbered
+    /// list, with the numbers in place of the bullets.
+    pub auto_number_lists: bool,
+    /// Join runs of text lines into one paragraph before wrapping, undoing
+    /// the author's hard wrapping. Paragraphs end at blank lines and at any
+    /// other kind of line.
+    pub reflow: bool,
+    /// Tab stops in preformatted text are this many columns apart. Tabs
+    /// are expanded to spaces so they line up the same in any terminal.
+    pub tab_width: usize,
+    /// Cut preformatted lines, and text lines when `reflow` is off, that
+    /// are wider than the page at one column short of it and end them with
+    /// `›`, instead of wrapping text and letting the terminal break code.
+    pub truncate_long_lines: bool,
+    /// Center each preformatted block on the page as a whole, by its
+    /// widest line, for ASCII-art banners. Blocks wider than the page are
+    /// left alone.
+    pub center_preformatted: bool,
+    /// Shown before every line of a quote, including wrapped lines.
+    pub quote_prefix: String,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            color: true,
+            hyperlinks: false,
+            show_link_urls: false,
+            mark_external: false,
+            highlight: true,
+            bullet: "\u{2022}".to_string(),
+            auto_number_lists: false,
+            reflow: false,
+            tab_width: 8,
+            truncate_long_lines: false,
+            center_preformatted: false,
+            quote_prefix: "\u{2502} ".to_string(),
+        }
+    }
+}
+
+impl RenderOptions {
+    /// The defaults, with `ASCII_BULLET` and `ASCII_QUOTE_PREFIX` in place of
+    /// the Unicode markers when the locale isn't UTF-8.
+    pub fn for_locale() -> Self {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty());
+        if is_utf8_locale(locale.as_deref()) {
+            Self::default()
+        } else {
+            Self {
+                bullet: ASCII_BULLET.to_string(),
+                quote_prefix: ASCII_QUOTE_PREFIX.to_string(),
+                ..Self::default()
+            }
+        }
+    }
+}
+
+/// List bullet for terminals that can't show `•`.
+pub const ASCII_BULLET: &str = "*";
+/// Quote prefix for terminals that can't show `│`.
+pub const ASCII_QUOTE_PREFIX: &str = "| ";
+
+/// Whether a locale name such as `en_US.UTF-8` uses UTF-8. An unset locale
+/// is given the benefit of the doubt.
+fn is_utf8_locale(locale: Option<&str>) -> bool {
+    locale.is_none_or(|locale| {
+        let locale = locale.to_ascii_lowercase();
+        locale.contains("utf-8") || locale.contains("utf8")
+    })
+}
+
+/// Wrap `text` in `style` … `RESET` when color is enabled.
+fn styled(opts: &RenderOptions, style: &str, text: &str) -> String {
+    if opts.color && !style.is_empty() {
+        format!("{style}{text}{RESET}")
+    } else {
+        text.to_string()
+    }
+}
+
+const BULLET_INDENT: &str = "  ";
+
+/// `line` if it fits in `width` columns, or else its first `width - 1`
+/// columns followed by a dim `TRUNCATION_MARKER`.
+fn truncate_line(line: &str, width: usize, opts: &RenderOptions) -> String {
+    if display_width(line) <= width {
+        return line.to_string();
+    }
+    // Escapes don't take up columns, and the styling they open is closed
+    // before the marker
+    let kept = match width - 1 {
+        0 => String::new(),
+        columns => fold(line, columns).swap_remove(0),
+    };
+    let reset = if kept.contains('\x1b') { RESET } else { "" };
+    format!("{kept}{reset}{}", styled(opts, DIM, TRUNCATION_MARKER))
+}
+
+/// Split an item such as `3. foo` or `3) foo` into its number and text.
+fn numbered_item(item: &str) -> Option<(&str, &str)> {
+    let digits = item.len() - item.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let (number, rest) = item.split_at(digits);
+    let text = rest.strip_prefix(['.', ')'])?;
+    let trimmed = text.trim_start();
+    let separated = trimmed.len() < text.len();
+    (!number.is_empty() && separated && !trimmed.is_empty()).then_some((number, trimmed))
+}
+
+/// Wrap `label` in an OSC 8 hyperlink escape pointing at `url`.
+fn hyperlink(url: &Url, label: &str) -> String {
+    format!("\x1b]8;;{url}\x1b\\{label}\x1b]8;;\x1b\\")
+}
+
+/// Resolve a link target to an absolute URL of any scheme.
+fn absolute_link(base: Option<&Url>, target: &str) -> Option<Url> {
+    match base {
+        Some(base) => base.join(target).ok(),
+        None => Url::parse(target).ok(),
+    }
+}
+
+/// `⇗ host` for a link leaving `base`'s host, or just `⇗` when the target
+/// has no host (e.g. `mailto:`). None for same-host links, and for every
+/// link when there is no base to compare against.
+fn external_marker(base: Option<&Url>, target: &str) -> Option<String> {
+    let base = base?;
+    let target = base.join(target).ok()?;
+    if gemini::same_host(base, &target) {
+        return None;
+    }
+    let authority = url_utils::display_authority(&target);
+    Some(if authority.is_empty() {
+        "\u{21d7}".to_string()
+    } else {
+        format!("\u{21d7} {authority}")
+    })
+}
+
+/// Word-wrap `text` so no line exceeds `width` columns once `prefix` (first
+/// line) or `continuation` (later lines) is prepended. Widths are measured in
+/// display columns, so wide glyphs count double. A `width` of 0 disables
+/// wrapping. Words longer than a full line are split.
+fn wrap(text: &str, width: usize, prefix: &str, continuation: &str) -> Vec<String> {
+    let prefix_width = display_width(prefix);
+    let continuation_width = display_width(continuation);
+    if width == 0 || prefix_width + display_width(text) <= width {
+        return vec![format!("{prefix}{text}")];
+    }
+
+    let mut lines = Vec::new();
+    let mut current = prefix.to_string();
+    let mut current_width = prefix_width;
+    let mut line_has_word = false;
+
+    for word in text.split_whitespace() {
+        let indent_width = if lines.is_empty() {
+            prefix_width
+        } else {
+            continuation_width
+        };
+        let available = width.saturating_sub(indent_width).max(1);
+        let word_width = display_width(word);
+        let needed = if line_has_word {
+            word_width + 1
+        } else {
+            word_width
+        };
+
+        if line_has_word && current_width + needed > width {
+            lines.push(std::mem::replace(&mut current, continuation.to_string()));
+            current_width = continuation_width;
+            line_has_word = false;
+        }
+
+        if word_width > available {
+            // Hard-break a word that cannot fit on any line.
+            for ch in word.chars() {
+                let ch_width = char_width(ch);
+                if current_width + ch_width > width && line_has_word {
+                    lines.push(std::mem::replace(&mut current, continuation.to_string()));
+                    current_width = continuation_width;
+                }
+                current.push(ch);
+                current_width += ch_width;
+                line_has_word = true;
+            }
+            continue;
+        }
+
+        if line_has_word {
+            current.push(' ');
+            current_width += 1;
+        }
+        current.push_str(word);
+        current_width += word_width;
+        line_has_word = true;
+    }
+
+    lines.push(current);
+    lines
+}
+
+/// The output of `render`.
+#[derive(Debug)]
+pub struct RenderedPage {
+    /// Formatted lines ready for display (one per output line).
+    pub lines: Vec<String>,
+    /// `(url, label)` for each link found on the page (indexed from 1).
+    pub links: Vec<(String, String)>,
+    /// Index into `lines` of each heading, in document order.
+    pub heading_offsets: Vec<usize>,
+}
 
 /// Render parsed gemini lines into ANSI-formatted strings.
-/// Returns a tuple of (output_lines, link_urls):
-/// - output_lines: Vec<String> of formatted lines ready for display (one per output line)
-/// - link_urls: Vec<String> of link URLs found on the page (indexed from 1)
+///
+/// Text, quote, and list lines are word-wrapped to `width` columns (0 disables
+/// wrapping); headings and preformatted text are never wrapped. `base` is the
+/// page URL, used to resolve relative links for OSC 8 hyperlinks and to
+/// match them against `visited` (absolute URLs), which get the theme's
+/// visited-link style. All styles come from `theme`.
 ///
 /// Does NOT print to stdout — the caller (via the pager) handles display.
-pub fn render(lines: &[GeminiLine]) -> (Vec<String>, Vec<String>) {
+pub fn render(
+    lines: &[(usize, GeminiLine)],
+    base: Option<&Url>,
+    visited: &HashSet<String>,
+    width: usize,
+    opts: &RenderOptions,
+    theme: &Theme,
+) -> RenderedPage {
     let mut output_lines: Vec<String> = Vec::new();
-    let mut links: Vec<String> = Vec::new();
+    let mut links: Vec<(String, String)> = Vec::new();
+    let mut heading_offsets: Vec<usize> = Vec::new();
     let mut in_preformatted = false;
+    let mut highlighter: Option<BlockHighlighter> = None;
+    // Spaces before each line of the open preformatted block
+    let mut block_indent = String::new();
+    let mut lines = lines.iter().peekable();
 
-    for line in lines {
+    while let Some((_, line)) = lines.next() {
         match line {
+            GeminiLine::Text(text) if opts.reflow && !text.trim().is_empty() => {
+                let mut paragraph = text.trim_end().to_string();
+                while let Some((_, GeminiLine::Text(next))) = lines.peek() {
+                    if next.trim().is_empty() {
+                        break;
+                    }
+                    paragraph.push(' ');
+                    paragraph.push_str(next.trim());
+                    lines.next();
+                }
+                output_lines.extend(wrap(&paragraph, width, "", ""));
+            }
+            GeminiLine::Text(text) if opts.truncate_long_lines && width > 0 => {
+                output_lines.push(truncate_line(text, width, opts));
+            }
             GeminiLine::Text(text) => {
-                output_lines.push(text.clone());
+                output_lines.extend(wrap(text, width, "", ""));
             }
+            GeminiLine::Blank => output_lines.push(String::new()),
             GeminiLine::Link { url, label } => {
-                links.push(url.clone());
+                links.push((url.clone(), label.clone()));
                 let index = links.len();
-                output_lines.push(format!("{CYAN}[{index}] {label}{RESET}"));
+                let shown = match absolute_link(base, url) {
+                    Some(target) if opts.hyperlinks && opts.color => hyperlink(&target, label),
+                    _ => label.clone(),
+                };
+                let was_visited = base
+                    .and_then(|base| url_utils::resolve_url(base, url).ok())
+                    .is_some_and(|target| {
+                        visited.contains(url_utils::canonicalize(&target).as_str())
+                    });
+                let style = if was_visited {
+                    &theme.visited_link
+                } else {
+                    &theme.link
+                };
+                let mut line = styled(opts, style, &format!("[{index}] {shown}"));
+                if opts.mark_external {
+                    if let Some(marker) = external_marker(base, url) {
+                        line.push(' ');
+                        line.push_str(&styled(opts, &theme.link_url, &marker));
+                    }
+                }
+                // A link without a label already shows its URL
+                if opts.show_link_urls && label != url {
+                    line.push(' ');
+                    line.push_str(&styled(opts, &theme.link_url, &format!("({url})")));
+                }
+                output_lines.push(line);
+            }
+            GeminiLine::Heading { level, text } => {
+                heading_offsets.push(output_lines.len());
+                let style = match level {
+                    1 => &theme.h1,
+                    2 => &theme.h2,
+                    _ => &theme.h3,
+                };
+                output_lines.push(styled(opts, style, text));
+            }
+            GeminiLine::List(items) => {
+                // Items written as `1. foo` can trade their bullets for the numbers
+                let numbered: Option<Vec<_>> = if opts.auto_number_lists {
+                    items.iter().map(|item| numbered_item(item)).collect()
+                } else {
+                    None
+                };
+                let entries: Vec<(String, &str)> = match numbered {
+                    Some(numbered) => {
+                        // Right-align the numbers so the text starts in one column
+                        let digits = numbered.iter().map(|(n, _)| n.len()).max().unwrap_or(0);
+                        numbered
+                            .into_iter()
+                            .map(|(n, text)| {
+                                (styled(opts, &theme.bullet, &format!("{n:>digits$}.")), text)
+                            })
+                            .collect()
+                    }
+                    None => {
+                        let bullet = styled(opts, &theme.bullet, &opts.bullet);
+                        items
+                            .iter()
+                            .map(|item| (bullet.clone(), item.as_str()))
+                            .collect()
+                    }
+                };
+                for (marker, text) in entries {
+                    let prefix = format!("{BULLET_INDENT}{marker} ");
+                    // Wrapped lines line up with the item text
+                    let continuation = " ".repeat(display_width(&prefix));
+                    output_lines.extend(wrap(text, width, &prefix, &continuation));
+                }
+            }
+            GeminiLine::Quote(text) => {
+                // Every wrapped line keeps the bar so the quote reads as a block
+                let marker = opts.quote_prefix.as_str();
+                let bar = styled(opts, &theme.quote_bar, marker);
+                for line in wrap(text, width, marker, marker) {
+                    let text = line.strip_prefix(marker).unwrap_or(&line);
+                    output_lines.push(format!("{bar}{}", styled(opts, &theme.quote, text)));
+                }
+            }
+            GeminiLine::PreformattedToggle { alt_text } => {
+                in_preformatted = !in_preformatted;
+                // The opening toggle's alt text may name the block's language
+                highlighter = if in_preformatted && opts.color && opts.highlight {
+                    BlockHighlighter::for_alt_text(alt_text)
+                } else {
+                    None
+                };
+                block_indent.clear();
+                if in_preformatted && opts.center_preformatted && width > 0 {
+                    let block_width = lines
+                        .clone()
+                        .map_while(|(_, line)| match line {
+                            GeminiLine::PreformattedText(text) => {
+                                Some(display_width(&expand_tabs(text, opts.tab_width)))
+                            }
+                            _ => None,
+                        })
+                        .max()
+                        .unwrap_or(0);
+                    if block_width <= width {
+                        block_indent = " ".repeat((width - block_width) / 2);
+                    }
+                }
+                // Only an opening toggle with alt text produces output
+                if in_preformatted && !alt_text.trim().is_empty() {
+                    let caption = format!("\u{2500}\u{2500} {} \u{2500}\u{2500}", alt_text.trim());
+                    output_lines.push(styled(opts, &theme.caption, &caption));
+                }
+            }
+            GeminiLine::PreformattedText(text) => {
+                let text = expand_tabs(text, opts.tab_width);
+                // The highlighter sees whole lines, so a string or comment
+                // cut off by truncation still ends where it should
+                let mut line = highlighter
+                    .as_mut()
+                    .and_then(|h| h.highlight(&text))
+                    .unwrap_or(text);
+                if opts.truncate_long_lines && width > 0 {
+                    line = truncate_line(&line, width, opts);
+                }
+                output_lines.push(format!("{block_indent}{line}"));
+            }
+        }
+    }
+
+    RenderedPage {
+        lines: output_lines,
+        links,
+        heading_offsets,
+    }
+}
+
+/// Convert parsed gemini lines to Markdown, one Markdown line per gemtext
+/// line. Preformatted blocks become fenced code blocks tagged with their alt
+/// text. The output contains no ANSI escapes.
+pub fn render_markdown(lines: &[(usize, GeminiLine)]) -> String {
+    let mut out = String::new();
+    let mut in_preformatted = false;
+
+    for (_, line) in lines {
+        match line {
+            GeminiLine::Text(text) => out.push_str(text),
+            GeminiLine::Blank => {}
+            GeminiLine::Link { url, label } => {
+                let label = if label.is_empty() { url } else { label };
+                out.push_str(&format!("[{label}]({url})"));
             }
             GeminiLine::Heading { level, text } => {
-                if *level == 1 {
-                    output_lines.push(format!("{BOLD_BRIGHT_CYAN}{text}{RESET}"));
+                out.push_str(&format!("{} {text}", "#".repeat(usize::from(*level))));
+            }
+            GeminiLine::List(items) => {
+                let items: Vec<String> = items.iter().map(|item| format!("- {item}")).collect();
+                out.push_str(&items.join("\n"));
+            }
+            GeminiLine::Quote(text) => out.push_str(&format!("> {text}")),
+            GeminiLine::PreformattedToggle { alt_text } => {
+                in_preformatted = !in_preformatted;
+                if in_preformatted {
+                    out.push_str(&format!("```{alt_text}"));
                 } else {
-                    output_lines.push(format!("{BOLD}{text}{RESET}"));
+                    out.push_str("```");
                 }
             }
-            GeminiLine::ListItem(text) => {
-                output_lines.push(format!("  \u{2022} {text}"));
+            GeminiLine::PreformattedText(text) => out.push_str(text),
+        }
+        out.push('\n');
+    }
+
+    // Close a block the document left open
+    if in_preformatted {
+        out.push_str("```\n");
+    }
+    out
+}
+
+/// Escape text for use in HTML content and double-quoted attributes.
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Convert parsed gemini lines to a minimal standalone HTML document.
+/// Each list becomes one `<ul>`, and link targets are made
+/// absolute against `base` when possible. All text is HTML-escaped.
+pub fn render_html(lines: &[(usize, GeminiLine)], title: &str, base: Option<&Url>) -> String {
+    let mut body = String::new();
+    let mut in_preformatted = false;
+
+    for (_, line) in lines {
+        match line {
+            GeminiLine::Blank => {}
+            GeminiLine::Text(text) if text.trim().is_empty() => {}
+            GeminiLine::Text(text) => body.push_str(&format!("<p>{}</p>\n", escape_html(text))),
+            GeminiLine::Link { url, label } => {
+                let href = absolute_link(base, url).map_or_else(|| url.clone(), String::from);
+                let label = if label.is_empty() { url } else { label };
+                body.push_str(&format!(
+                    "<p><a href=\"{}\">{}</a></p>\n",
+                    escape_html(&href),
+                    escape_html(label)
+                ));
+            }
+            GeminiLine::Heading { level, text } => {
+                let level = (*level).clamp(1, 3);
+                body.push_str(&format!("<h{level}>{}</h{level}>\n", escape_html(text)));
+            }
+            GeminiLine::List(items) => {
+                body.push_str("<ul>\n");
+                for item in items {
+                    body.push_str(&format!("<li>{}</li>\n", escape_html(item)));
+                }
+                body.push_str("</ul>\n");
             }
             GeminiLine::Quote(text) => {
-                output_lines.push(format!("{DIM_ITALIC}{text}{RESET}"));
+                body.push_str(&format!("<blockquote>{}</blockquote>\n", escape_html(text)));
             }
             GeminiLine::PreformattedToggle { .. } => {
                 in_preformatted = !in_preformatted;
-                // Toggle lines produce no output
+                body.push_str(if in_preformatted { "<pre>" } else { "</pre>\n" });
             }
             GeminiLine::PreformattedText(text) => {
-                output_lines.push(text.clone());
+                body.push_str(&escape_html(text));
+                body.push('\n');
             }
         }
     }
 
-    let _ = in_preformatted;
+    if in_preformatted {
+        body.push_str("</pre>\n");
+    }
 
-    (output_lines, links)
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape_html(title)
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::text::strip_ansi;
+
+    /// Number lines as if they came straight from a document.
+    fn numbered(lines: Vec<GeminiLine>) -> Vec<(usize, GeminiLine)> {
+        lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line))
+            .collect()
+    }
 
     #[test]
     fn test_render_returns_links() {
@@ -77,14 +652,44 @@ mod tests {
             },
         ];
 
-        let (output_lines, links) = render(&lines);
+        let RenderedPage {
+            lines: output_lines,
+            links,
+            ..
+        } = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            0,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
         assert_eq!(links.len(), 3);
-        assert_eq!(links[0], "gemini://a.com/");
-        assert_eq!(links[1], "gemini://b.com/");
-        assert_eq!(links[2], "gemini://c.com/page");
+        assert_eq!(links[0].0, "gemini://a.com/");
+        assert_eq!(links[1].0, "gemini://b.com/");
+        assert_eq!(links[2].0, "gemini://c.com/page");
+        assert_eq!(links[0].1, "Link A");
         assert_eq!(output_lines.len(), 4);
     }
 
+    #[test]
+    fn test_render_bare_link_marker_registers_no_link() {
+        let parsed = crate::parser::parse_gemini("=>\n=> /a A\n");
+        let page = render(
+            &parsed,
+            None,
+            &HashSet::new(),
+            0,
+            &RenderOptions {
+                color: false,
+                ..RenderOptions::default()
+            },
+            &Theme::default(),
+        );
+        assert_eq!(page.links, vec![("/a".to_string(), "A".to_string())]);
+        assert_eq!(page.lines, vec!["=>", "[1] A"]);
+    }
+
     #[test]
     fn test_render_link_numbering() {
         let lines = vec![
@@ -98,10 +703,21 @@ mod tests {
             },
         ];
 
-        let (output_lines, links) = render(&lines);
+        let RenderedPage {
+            lines: output_lines,
+            links,
+            ..
+        } = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            0,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
         assert_eq!(links.len(), 2);
-        assert_eq!(links[0], "gemini://first.com/");
-        assert_eq!(links[1], "gemini://second.com/");
+        assert_eq!(links[0].0, "gemini://first.com/");
+        assert_eq!(links[1].0, "gemini://second.com/");
         // Check numbering in output
         assert!(output_lines[0].contains("[1]"));
         assert!(output_lines[1].contains("[2]"));
@@ -110,7 +726,18 @@ mod tests {
     #[test]
     fn test_render_empty_page() {
         let lines: Vec<GeminiLine> = vec![];
-        let (output_lines, links) = render(&lines);
+        let RenderedPage {
+            lines: output_lines,
+            links,
+            ..
+        } = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            0,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
         assert!(links.is_empty());
         assert!(output_lines.is_empty());
     }
@@ -132,7 +759,17 @@ mod tests {
             },
         ];
 
-        let (output_lines, _) = render(&lines);
+        let RenderedPage {
+            lines: output_lines,
+            ..
+        } = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            0,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
         // 3 output lines: text, heading, preformatted text (toggles produce no output)
         assert_eq!(output_lines.len(), 3);
     }
@@ -144,10 +781,948 @@ mod tests {
             text: "My Title".to_string(),
         }];
 
-        let (output_lines, _) = render(&lines);
+        let RenderedPage {
+            lines: output_lines,
+            ..
+        } = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            0,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
         assert_eq!(output_lines.len(), 1);
         assert!(output_lines[0].contains("My Title"));
-        assert!(output_lines[0].contains(BOLD_BRIGHT_CYAN));
+        assert!(output_lines[0].contains(&Theme::default().h1));
+    }
+
+    #[test]
+    fn test_render_heading_levels_have_distinct_styles() {
+        let lines = vec![
+            GeminiLine::Heading {
+                level: 2,
+                text: "Section".to_string(),
+            },
+            GeminiLine::Heading {
+                level: 3,
+                text: "Subsection".to_string(),
+            },
+        ];
+
+        let theme = Theme::default();
+        let page = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            0,
+            &RenderOptions::default(),
+            &theme,
+        );
+        assert!(page.lines[0].contains(&theme.h2));
+        assert!(page.lines[1].contains(&theme.h3));
+        assert!(!page.lines[1].contains(&theme.h2));
+        assert!(!page.lines[1].contains(&theme.h1));
+    }
+
+    #[test]
+    fn test_render_without_color_is_plain() {
+        let lines = vec![
+            GeminiLine::Heading {
+                level: 1,
+                text: "Title".to_string(),
+            },
+            GeminiLine::Link {
+                url: "gemini://a.com/".to_string(),
+                label: "Link A".to_string(),
+            },
+            GeminiLine::Quote("Quoted".to_string()),
+        ];
+
+        let opts = RenderOptions {
+            color: false,
+            ..RenderOptions::default()
+        };
+        let RenderedPage {
+            lines: output_lines,
+            ..
+        } = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            0,
+            &opts,
+            &Theme::default(),
+        );
+        assert_eq!(output_lines, vec!["Title", "[1] Link A", "\u{2502} Quoted"]);
+        assert!(output_lines.iter().all(|line| !line.contains('\x1b')));
+    }
+
+    #[test]
+    fn test_wrap_short_line_untouched() {
+        assert_eq!(wrap("  keep  spacing", 40, "", ""), vec!["  keep  spacing"]);
+    }
+
+    #[test]
+    fn test_wrap_at_word_boundaries() {
+        let lines = wrap("the quick brown fox jumps over the lazy dog", 15, "", "");
+        assert_eq!(
+            lines,
+            vec!["the quick brown", "fox jumps over", "the lazy dog"]
+        );
+    }
+
+    #[test]
+    fn test_wrap_counts_columns_not_bytes() {
+        // Each word is 5 chars but 10 bytes; both fit on one 11-column line.
+        let lines = wrap("ééééé ééééé", 11, "", "");
+        assert_eq!(lines, vec!["ééééé ééééé"]);
+    }
+
+    #[test]
+    fn test_wrap_counts_wide_glyphs_as_two_columns() {
+        // Each CJK word is 4 columns wide; two don't fit in 8 with a space.
+        let lines = wrap("日本 語句 テスト", 9, "", "");
+        assert_eq!(lines, vec!["日本 語句", "テスト"]);
+        let lines = wrap("日本 語句", 8, "", "");
+        assert_eq!(lines, vec!["日本", "語句"]);
+    }
+
+    #[test]
+    fn test_wrap_splits_wide_word_on_column_boundary() {
+        let lines = wrap("日本語日本", 5, "", "");
+        assert_eq!(lines, vec!["日本", "語日", "本"]);
+    }
+
+    #[test]
+    fn test_wrap_splits_long_word() {
+        let lines = wrap("abcdefghij", 4, "", "");
+        assert_eq!(lines, vec!["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn test_render_wraps_list_items_with_indent() {
+        let lines = vec![GeminiLine::List(vec!["one two three four".to_string()])];
+        let RenderedPage {
+            lines: output_lines,
+            ..
+        } = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            14,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
+        assert_eq!(output_lines, vec!["  \u{2022} one two", "    three four"]);
+    }
+
+    #[test]
+    fn test_render_blank_is_empty_line() {
+        let lines = vec![
+            GeminiLine::Text("a".to_string()),
+            GeminiLine::Blank,
+            GeminiLine::Text("b".to_string()),
+        ];
+        let page = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            0,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
+        assert_eq!(page.lines, vec!["a", "", "b"]);
+    }
+
+    #[test]
+    fn test_render_show_link_urls() {
+        let lines = numbered(vec![
+            GeminiLine::Link {
+                url: "/about.gmi".to_string(),
+                label: "About".to_string(),
+            },
+            GeminiLine::Link {
+                url: "gemini://other.example/".to_string(),
+                label: "gemini://other.example/".to_string(),
+            },
+        ]);
+        let render_with = |opts: &RenderOptions| {
+            render(&lines, None, &HashSet::new(), 0, opts, &Theme::default()).lines
+        };
+        let plain = RenderOptions {
+            color: false,
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            render_with(&plain),
+            vec!["[1] About", "[2] gemini://other.example/"]
+        );
+
+        let show = RenderOptions {
+            show_link_urls: true,
+            ..plain
+        };
+        assert_eq!(
+            render_with(&show),
+            vec!["[1] About (/about.gmi)", "[2] gemini://other.example/"]
+        );
+
+        let theme = Theme::default();
+        let colored = RenderOptions {
+            show_link_urls: true,
+            ..RenderOptions::default()
+        };
+        assert!(
+            render_with(&colored)[0].ends_with(&format!(" {}(/about.gmi){RESET}", theme.link_url))
+        );
+    }
+
+    #[test]
+    fn test_render_mark_external_links() {
+        let base = Url::parse("gemini://example.com/dir/").unwrap();
+        let lines = numbered(vec![
+            GeminiLine::Link {
+                url: "page.gmi".to_string(),
+                label: "Local".to_string(),
+            },
+            GeminiLine::Link {
+                url: "gemini://EXAMPLE.com:1965/x".to_string(),
+                label: "Same host".to_string(),
+            },
+            GeminiLine::Link {
+                url: "gemini://other.example:1966/".to_string(),
+                label: "Friend".to_string(),
+            },
+            GeminiLine::Link {
+                url: "mailto:me@example.com".to_string(),
+                label: "Mail".to_string(),
+            },
+        ]);
+        let opts = RenderOptions {
+            color: false,
+            mark_external: true,
+            ..RenderOptions::default()
+        };
+        let page = render(
+            &lines,
+            Some(&base),
+            &HashSet::new(),
+            0,
+            &opts,
+            &Theme::default(),
+        );
+        assert_eq!(
+            page.lines,
+            vec![
+                "[1] Local",
+                "[2] Same host",
+                "[3] Friend \u{21d7} other.example:1966",
+                "[4] Mail \u{21d7}",
+            ]
+        );
+
+        let unmarked = render(
+            &lines,
+            Some(&base),
+            &HashSet::new(),
+            0,
+            &RenderOptions {
+                color: false,
+                ..RenderOptions::default()
+            },
+            &Theme::default(),
+        );
+        assert_eq!(unmarked.lines[2], "[3] Friend");
+    }
+
+    #[test]
+    fn test_render_list_gives_each_item_a_bullet() {
+        let lines = vec![GeminiLine::List(vec!["one".to_string(), "two".to_string()])];
+        let page = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            0,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
+        assert_eq!(page.lines, vec!["  \u{2022} one", "  \u{2022} two"]);
+    }
+
+    #[test]
+    fn test_numbered_item() {
+        assert_eq!(numbered_item("1. foo"), Some(("1", "foo")));
+        assert_eq!(numbered_item("12)  bar baz"), Some(("12", "bar baz")));
+        assert_eq!(numbered_item("1.foo"), None);
+        assert_eq!(numbered_item("1. "), None);
+        assert_eq!(numbered_item("foo 1. bar"), None);
+        assert_eq!(numbered_item(". foo"), None);
+        assert_eq!(numbered_item("3.5 apples"), None);
+    }
+
+    #[test]
+    fn test_render_auto_numbered_list() {
+        let render_items = |items: &[&str], opts: &RenderOptions| {
+            let items = items.iter().map(|item| item.to_string()).collect();
+            let lines = numbered(vec![GeminiLine::List(items)]);
+            render(&lines, None, &HashSet::new(), 14, opts, &Theme::default()).lines
+        };
+        let opts = RenderOptions {
+            color: false,
+            auto_number_lists: true,
+            ..RenderOptions::default()
+        };
+
+        let list: Vec<String> = (1..=10).map(|n| format!("{n}. item")).collect();
+        let list: Vec<&str> = list.iter().map(String::as_str).collect();
+        let lines = render_items(&list, &opts);
+        assert_eq!(lines[0], "   1. item");
+        assert_eq!(lines[9], "  10. item");
+
+        // Wrapped text lines up after the number
+        assert_eq!(
+            render_items(&["1) one two three"], &opts),
+            vec!["  1. one two", "     three"]
+        );
+
+        // One plain item keeps the whole list bulleted
+        assert_eq!(
+            render_items(&["1. one", "two"], &opts),
+            vec!["  \u{2022} 1. one", "  \u{2022} two"]
+        );
+
+        // Off by default
+        let default = RenderOptions {
+            color: false,
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            render_items(&["1. one"], &default),
+            vec!["  \u{2022} 1. one"]
+        );
+    }
+
+    #[test]
+    fn test_render_reflow_joins_paragraphs() {
+        let opts = RenderOptions {
+            color: false,
+            reflow: true,
+            ..RenderOptions::default()
+        };
+        let lines = numbered(vec![
+            GeminiLine::Text("one two".to_string()),
+            GeminiLine::Text("three".to_string()),
+            GeminiLine::Blank,
+            GeminiLine::Text("four".to_string()),
+            GeminiLine::Text("five".to_string()),
+        ]);
+        let page = render(&lines, None, &HashSet::new(), 40, &opts, &Theme::default());
+        assert_eq!(page.lines, vec!["one two three", "", "four five"]);
+
+        // Re-wrapped to the given width
+        let page = render(&lines, None, &HashSet::new(), 9, &opts, &Theme::default());
+        assert_eq!(page.lines, vec!["one two", "three", "", "four five"]);
+    }
+
+    #[test]
+    fn test_render_reflow_stops_at_structure() {
+        let opts = RenderOptions {
+            color: false,
+            reflow: true,
+            ..RenderOptions::default()
+        };
+        let lines = numbered(vec![
+            GeminiLine::Text("intro".to_string()),
+            GeminiLine::Heading {
+                level: 2,
+                text: "Heading".to_string(),
+            },
+            GeminiLine::Text("a".to_string()),
+            GeminiLine::List(vec!["item".to_string()]),
+            GeminiLine::Text("b".to_string()),
+            GeminiLine::Quote("quoted".to_string()),
+            GeminiLine::Text("c".to_string()),
+            GeminiLine::Link {
+                url: "/x".to_string(),
+                label: "x".to_string(),
+            },
+            GeminiLine::PreformattedToggle {
+                alt_text: String::new(),
+            },
+            GeminiLine::PreformattedText("keep".to_string()),
+            GeminiLine::PreformattedText("  as is".to_string()),
+            GeminiLine::PreformattedToggle {
+                alt_text: String::new(),
+            },
+            GeminiLine::Text("d".to_string()),
+            GeminiLine::Text("e".to_string()),
+        ]);
+        let page = render(&lines, None, &HashSet::new(), 40, &opts, &Theme::default());
+        assert_eq!(
+            page.lines,
+            vec![
+                "intro",
+                "Heading",
+                "a",
+                "  \u{2022} item",
+                "b",
+                "\u{2502} quoted",
+                "c",
+                "[1] x",
+                "keep",
+                "  as is",
+                "d e",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_custom_bullet_and_quote_prefix() {
+        let lines = vec![
+            GeminiLine::List(vec!["one two three".to_string()]),
+            GeminiLine::Quote("four five".to_string()),
+        ];
+        let opts = RenderOptions {
+            color: false,
+            bullet: ASCII_BULLET.to_string(),
+            quote_prefix: ">> ".to_string(),
+            ..RenderOptions::default()
+        };
+        let page = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            11,
+            &opts,
+            &Theme::default(),
+        );
+        assert_eq!(
+            page.lines,
+            vec!["  * one two", "    three", ">> four", ">> five"]
+        );
+    }
+
+    #[test]
+    fn test_is_utf8_locale() {
+        assert!(is_utf8_locale(Some("en_US.UTF-8")));
+        assert!(is_utf8_locale(Some("de_DE.utf8")));
+        assert!(is_utf8_locale(None));
+        assert!(!is_utf8_locale(Some("C")));
+        assert!(!is_utf8_locale(Some("en_US.ISO-8859-1")));
+    }
+
+    #[test]
+    fn test_render_does_not_wrap_headings_or_preformatted() {
+        let long = "word ".repeat(10);
+        let lines = vec![
+            GeminiLine::Heading {
+                level: 2,
+                text: long.clone(),
+            },
+            GeminiLine::PreformattedToggle {
+                alt_text: String::new(),
+            },
+            GeminiLine::PreformattedText(long.clone()),
+        ];
+        let opts = RenderOptions {
+            color: false,
+            ..RenderOptions::default()
+        };
+        let RenderedPage {
+            lines: output_lines,
+            ..
+        } = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            10,
+            &opts,
+            &Theme::default(),
+        );
+        assert_eq!(output_lines, vec![long.clone(), long]);
+    }
+
+    #[test]
+    fn test_render_wraps_text_and_quotes() {
+        let lines = vec![
+            GeminiLine::Text("alpha beta gamma".to_string()),
+            GeminiLine::Quote("delta epsilon".to_string()),
+        ];
+        let opts = RenderOptions {
+            color: false,
+            ..RenderOptions::default()
+        };
+        let RenderedPage {
+            lines: output_lines,
+            ..
+        } = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            10,
+            &opts,
+            &Theme::default(),
+        );
+        assert_eq!(
+            output_lines,
+            vec!["alpha beta", "gamma", "\u{2502} delta", "\u{2502} epsilon"]
+        );
+    }
+
+    #[test]
+    fn test_render_quote_bar_on_every_line() {
+        let lines = vec![
+            GeminiLine::Quote("one two three four".to_string()),
+            GeminiLine::Quote("five".to_string()),
+        ];
+        let theme = Theme::default();
+        let RenderedPage {
+            lines: output_lines,
+            ..
+        } = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            12,
+            &RenderOptions::default(),
+            &theme,
+        );
+        let bar = format!("{}\u{2502} {RESET}", theme.quote_bar);
+        assert_eq!(output_lines.len(), 3);
+        for line in &output_lines {
+            assert!(line.starts_with(&bar), "{line:?}");
+            assert!(display_width(line) <= 12);
+        }
+        assert_eq!(
+            output_lines[0],
+            format!("{bar}{}one two{RESET}", theme.quote)
+        );
+    }
+
+    #[test]
+    fn test_render_hyperlinks_use_absolute_url() {
+        let base = Url::parse("gemini://example.com/dir/page").unwrap();
+        let lines = vec![GeminiLine::Link {
+            url: "other".to_string(),
+            label: "Other".to_string(),
+        }];
+        let opts = RenderOptions {
+            color: true,
+            hyperlinks: true,
+            ..RenderOptions::default()
+        };
+        let lines = numbered(lines);
+        let RenderedPage {
+            lines: output_lines,
+            links,
+            ..
+        } = render(
+            &lines,
+            Some(&base),
+            &HashSet::new(),
+            0,
+            &opts,
+            &Theme::default(),
+        );
+        assert!(output_lines[0]
+            .contains("[1] \x1b]8;;gemini://example.com/dir/other\x1b\\Other\x1b]8;;\x1b\\"));
+        // The stored link stays as written so FollowLink resolves it as before.
+        assert_eq!(links[0].0, "other");
+
+        // Plain output never carries the escapes
+        let plain = RenderOptions {
+            color: false,
+            ..opts
+        };
+        let page = render(
+            &lines,
+            Some(&base),
+            &HashSet::new(),
+            0,
+            &plain,
+            &Theme::default(),
+        );
+        assert_eq!(page.lines[0], "[1] Other");
+    }
+
+    #[test]
+    fn test_render_hyperlinks_off_by_default() {
+        let base = Url::parse("gemini://example.com/").unwrap();
+        let lines = vec![GeminiLine::Link {
+            url: "gemini://example.com/a".to_string(),
+            label: "A".to_string(),
+        }];
+        let RenderedPage {
+            lines: output_lines,
+            ..
+        } = render(
+            &numbered(lines),
+            Some(&base),
+            &HashSet::new(),
+            0,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
+        assert!(!output_lines[0].contains("\x1b]8;;"));
+    }
+
+    #[test]
+    fn test_render_bullet_style_from_theme() {
+        let lines = vec![GeminiLine::List(vec!["one two three four".to_string()])];
+        let theme = Theme {
+            bullet: "\x1b[33m".to_string(),
+            ..Theme::default()
+        };
+        let page = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            14,
+            &RenderOptions::default(),
+            &theme,
+        );
+        assert_eq!(page.lines[0], format!("  \x1b[33m\u{2022}{RESET} one two"));
+        assert_eq!(page.lines[1], "    three four");
+    }
+
+    fn code_block(alt_text: &str) -> Vec<(usize, GeminiLine)> {
+        numbered(vec![
+            GeminiLine::PreformattedToggle {
+                alt_text: alt_text.to_string(),
+            },
+            GeminiLine::PreformattedText("let x = 1;".to_string()),
+            GeminiLine::PreformattedToggle {
+                alt_text: String::new(),
+            },
+        ])
+    }
+
+    #[test]
+    fn test_render_highlights_preformatted_by_language() {
+        let page = render(
+            &code_block("rust"),
+            None,
+            &HashSet::new(),
+            0,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
+        assert!(page.lines[1].contains("\x1b[38;2;"));
+    }
+
+    #[test]
+    fn test_render_captions_preformatted_alt_text() {
+        let theme = Theme::default();
+        let page = render(
+            &code_block("rust"),
+            None,
+            &HashSet::new(),
+            0,
+            &RenderOptions::default(),
+            &theme,
+        );
+        assert_eq!(page.lines.len(), 2);
+        assert_eq!(
+            page.lines[0],
+            format!(
+                "{}\u{2500}\u{2500} rust \u{2500}\u{2500}{RESET}",
+                theme.caption
+            )
+        );
+
+        let no_color = RenderOptions {
+            color: false,
+            ..RenderOptions::default()
+        };
+        let page = render(&code_block(""), None, &HashSet::new(), 0, &no_color, &theme);
+        assert_eq!(page.lines, vec!["let x = 1;"]);
+    }
+
+    #[test]
+    fn test_render_preformatted_plain_without_language_or_highlight() {
+        // The code itself, without the caption
+        let plain = |lines: &[(usize, GeminiLine)], opts: &RenderOptions| {
+            let mut lines = render(lines, None, &HashSet::new(), 0, opts, &Theme::default()).lines;
+            lines.split_off(lines.len() - 1)
+        };
+        let no_highlight = RenderOptions {
+            highlight: false,
+            ..RenderOptions::default()
+        };
+        let no_color = RenderOptions {
+            color: false,
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            plain(&code_block(""), &RenderOptions::default()),
+            vec!["let x = 1;"]
+        );
+        assert_eq!(
+            plain(&code_block("diagram"), &RenderOptions::default()),
+            vec!["let x = 1;"]
+        );
+        assert_eq!(
+            plain(&code_block("rust"), &no_highlight),
+            vec!["let x = 1;"]
+        );
+        assert_eq!(plain(&code_block("rust"), &no_color), vec!["let x = 1;"]);
+    }
+
+    #[test]
+    fn test_render_heading_offsets() {
+        let lines = vec![
+            GeminiLine::Heading {
+                level: 1,
+                text: "Title".to_string(),
+            },
+            GeminiLine::Text("one two three four".to_string()),
+            GeminiLine::Heading {
+                level: 2,
+                text: "Section".to_string(),
+            },
+        ];
+        let page = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            10,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
+        // The text wraps onto two lines, pushing the second heading to line 3
+        assert_eq!(page.heading_offsets, vec![0, 3]);
+        assert_eq!(
+            page.lines[3],
+            format!("{}Section{RESET}", Theme::default().h2)
+        );
+    }
+
+    #[test]
+    fn test_render_dims_visited_links() {
+        let base = Url::parse("gemini://example.com/dir/").unwrap();
+        let lines = vec![
+            GeminiLine::Link {
+                url: "seen".to_string(),
+                label: "Seen".to_string(),
+            },
+            GeminiLine::Link {
+                url: "gemini://example.com/unseen".to_string(),
+                label: "Unseen".to_string(),
+            },
+        ];
+        let visited: HashSet<String> = ["gemini://example.com/dir/seen".to_string()].into();
+
+        let RenderedPage {
+            lines: output_lines,
+            ..
+        } = render(
+            &numbered(lines),
+            Some(&base),
+            &visited,
+            0,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
+        let theme = Theme::default();
+        assert!(output_lines[0].starts_with(&theme.visited_link));
+        assert!(!output_lines[0].contains(&theme.link));
+        assert!(output_lines[1].starts_with(&theme.link));
+    }
+
+    #[test]
+    fn test_render_visited_ignores_percent_encoding() {
+        let base = Url::parse("gemini://example.com/").unwrap();
+        let lines = vec![GeminiLine::Link {
+            url: "/%7euser/".to_string(),
+            label: "Home".to_string(),
+        }];
+        let visited: HashSet<String> = ["gemini://example.com/~user/".to_string()].into();
+        let page = render(
+            &numbered(lines),
+            Some(&base),
+            &visited,
+            0,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
+        assert!(page.lines[0].starts_with(&Theme::default().visited_link));
+    }
+
+    #[test]
+    fn test_color_mode_parse() {
+        assert_eq!(ColorMode::parse("always"), Some(ColorMode::Always));
+        assert_eq!(ColorMode::parse("never"), Some(ColorMode::Never));
+        assert_eq!(ColorMode::parse("auto"), Some(ColorMode::Auto));
+        assert_eq!(ColorMode::parse("sometimes"), None);
+    }
+
+    #[test]
+    fn test_color_mode_explicit_resolve() {
+        assert!(ColorMode::Always.resolve());
+        assert!(!ColorMode::Never.resolve());
+    }
+
+    #[test]
+    fn test_status_color_by_class() {
+        assert_eq!(status_color(10, "Name?", true), "\x1b[36mName?\x1b[0m");
+        assert_eq!(status_color(44, "slow", true), "\x1b[33mslow\x1b[0m");
+        assert_eq!(status_color(51, "gone", true), "\x1b[31mgone\x1b[0m");
+        assert_eq!(status_color(20, "ok", true), "ok");
+        assert_eq!(status_color(51, "gone", false), "gone");
+    }
+
+    #[test]
+    fn test_render_preformatted_expands_tabs() {
+        let opts = RenderOptions {
+            color: false,
+            tab_width: 4,
+            ..RenderOptions::default()
+        };
+        let lines = numbered(vec![
+            GeminiLine::PreformattedToggle {
+                alt_text: String::new(),
+            },
+            GeminiLine::PreformattedText("fn main() {".to_string()),
+            GeminiLine::PreformattedText("\tlet x\t= 1;".to_string()),
+            GeminiLine::PreformattedToggle {
+                alt_text: String::new(),
+            },
+            GeminiLine::Text("a\tb".to_string()),
+        ]);
+        let page = render(&lines, None, &HashSet::new(), 0, &opts, &Theme::default());
+        assert_eq!(page.lines, vec!["fn main() {", "    let x   = 1;", "a\tb"]);
+    }
+
+    #[test]
+    fn test_render_center_preformatted() {
+        let opts = RenderOptions {
+            color: false,
+            center_preformatted: true,
+            ..RenderOptions::default()
+        };
+        let toggle = || GeminiLine::PreformattedToggle {
+            alt_text: String::new(),
+        };
+        let lines = numbered(vec![
+            toggle(),
+            GeminiLine::PreformattedText(" /\\".to_string()),
+            GeminiLine::PreformattedText("/__\\".to_string()),
+            toggle(),
+            GeminiLine::Text("after".to_string()),
+            toggle(),
+            GeminiLine::PreformattedText("x".repeat(12)),
+            GeminiLine::PreformattedText("y".to_string()),
+            toggle(),
+        ]);
+        let page = render(&lines, None, &HashSet::new(), 10, &opts, &Theme::default());
+        assert_eq!(
+            page.lines,
+            vec!["    /\\", "   /__\\", "after", "xxxxxxxxxxxx", "y"]
+        );
+    }
+
+    #[test]
+    fn test_render_truncate_long_lines_at_boundary() {
+        let opts = RenderOptions {
+            color: false,
+            truncate_long_lines: true,
+            ..RenderOptions::default()
+        };
+        let lines = numbered(vec![
+            GeminiLine::PreformattedToggle {
+                alt_text: String::new(),
+            },
+            GeminiLine::PreformattedText("abcdefghij".to_string()),
+            GeminiLine::PreformattedText("abcdefghijk".to_string()),
+            GeminiLine::PreformattedText("日本語日本語".to_string()),
+            GeminiLine::PreformattedToggle {
+                alt_text: String::new(),
+            },
+            GeminiLine::Text("one two three four".to_string()),
+        ]);
+        let page = render(&lines, None, &HashSet::new(), 10, &opts, &Theme::default());
+        assert_eq!(
+            page.lines,
+            vec![
+                "abcdefghij",
+                "abcdefghi\u{203a}",
+                "日本語日\u{203a}",
+                "one two t\u{203a}",
+            ]
+        );
+
+        // The marker is dim when color is on
+        let colored = RenderOptions {
+            color: true,
+            highlight: false,
+            ..opts.clone()
+        };
+        let page = render(
+            &lines,
+            None,
+            &HashSet::new(),
+            10,
+            &colored,
+            &Theme::default(),
+        );
+        assert_eq!(page.lines[1], "abcdefghi\x1b[2m\u{203a}\x1b[0m");
+
+        // Reflowed text is wrapped as usual
+        let reflow = RenderOptions {
+            reflow: true,
+            ..opts
+        };
+        let page = render(
+            &lines,
+            None,
+            &HashSet::new(),
+            10,
+            &reflow,
+            &Theme::default(),
+        );
+        assert_eq!(page.lines[3..], ["one two", "three four"]);
+    }
+
+    #[test]
+    fn test_render_truncate_highlights_whole_lines() {
+        let block = |first: &str| {
+            numbered(vec![
+                GeminiLine::PreformattedToggle {
+                    alt_text: "rust".to_string(),
+                },
+                GeminiLine::PreformattedText(first.to_string()),
+                GeminiLine::PreformattedText("let x = 1;".to_string()),
+                GeminiLine::PreformattedToggle {
+                    alt_text: String::new(),
+                },
+            ])
+        };
+        let opts = RenderOptions {
+            truncate_long_lines: true,
+            ..RenderOptions::default()
+        };
+        let theme = Theme::default();
+        let cut = render(
+            &block("let s = \"a long string literal\";"),
+            None,
+            &HashSet::new(),
+            12,
+            &opts,
+            &theme,
+        );
+        let whole = render(
+            &block("let s = \"a\";"),
+            None,
+            &HashSet::new(),
+            12,
+            &opts,
+            &theme,
+        );
+        assert_eq!(display_width(&cut.lines[1]), 12);
+        assert!(cut.lines[1].ends_with("\x1b[0m\x1b[2m\u{203a}\x1b[0m"));
+        // The cut-off string literal doesn't run on into the next line
+        assert_eq!(cut.lines[2], whole.lines[2]);
     }
 
     #[test]
@@ -162,9 +1737,69 @@ mod tests {
             },
         ];
 
-        let (output_lines, _) = render(&lines);
-        // Only the preformatted text line should appear
-        assert_eq!(output_lines.len(), 1);
-        assert_eq!(output_lines[0], "fn main() {}");
+        let RenderedPage {
+            lines: output_lines,
+            ..
+        } = render(
+            &numbered(lines),
+            None,
+            &HashSet::new(),
+            0,
+            &RenderOptions::default(),
+            &Theme::default(),
+        );
+        // The toggles themselves don't appear, just the caption and the code
+        assert_eq!(output_lines.len(), 2);
+        assert_eq!(
+            strip_ansi(&output_lines[0]),
+            "\u{2500}\u{2500} rust \u{2500}\u{2500}"
+        );
+        assert_eq!(strip_ansi(&output_lines[1]), "fn main() {}");
+    }
+
+    #[test]
+    fn test_terminal_title_escape() {
+        assert_eq!(terminal_title_escape("Home"), "\x1b]0;Home\x07");
+        assert_eq!(
+            terminal_title_escape("evil\x07\x1b[2Jtitle"),
+            "\x1b]0;evil[2Jtitle\x07"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let lines = crate::parser::parse_gemini(
+            "# Title\n## Sub\n### Minor\nPlain text\n=> gemini://a.example/ A link\n=> /bare\n* item\n* more\n> quoted\n```rust\nfn main() {}\n```",
+        );
+        assert_eq!(
+            render_markdown(&lines),
+            "# Title\n## Sub\n### Minor\nPlain text\n[A link](gemini://a.example/)\n[/bare](/bare)\n- item\n- more\n> quoted\n```rust\nfn main() {}\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown_closes_open_block() {
+        let lines = crate::parser::parse_gemini("```\ncode");
+        assert_eq!(render_markdown(&lines), "```\ncode\n```\n");
+    }
+
+    #[test]
+    fn test_render_html() {
+        let base = Url::parse("gemini://example.com/dir/").unwrap();
+        let lines = crate::parser::parse_gemini(
+            "# A & B\ntext <b>\n=> page.gmi \"Quoted\"\n* one\n* two\n> q\n```\n<pre>\n```",
+        );
+        let html = render_html(&lines, "A & B", Some(&base));
+        assert!(html.starts_with("<!DOCTYPE html>\n<html>"));
+        assert!(html.contains("<title>A &amp; B</title>"));
+        assert!(html.contains("<h1>A &amp; B</h1>\n<p>text &lt;b&gt;</p>\n"));
+        assert!(html.contains(
+            "<p><a href=\"gemini://example.com/dir/page.gmi\">&quot;Quoted&quot;</a></p>\n"
+        ));
+        assert!(
+            html.contains("<ul>\n<li>one</li>\n<li>two</li>\n</ul>\n<blockquote>q</blockquote>")
+        );
+        assert!(html.contains("<pre>&lt;pre&gt;\n</pre>\n"));
+        assert!(html.ends_with("</body>\n</html>\n"));
     }
 }
@@ -0,0 +1,319 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use url::Url;
+
+use crate::parser::{self, GeminiLine};
+use crate::paths;
+use crate::url_utils;
+
+const SUBSCRIPTIONS_FILE: &str = "subscriptions.tsv";
+
+/// A subscribed feed and the date of the newest entry already shown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subscription {
+    pub url: String,
+    /// `YYYY-MM-DD`, or None if `updates` has never seen an entry.
+    pub last_seen: Option<String>,
+    /// URLs of the entries dated `last_seen` that have been shown, so
+    /// posts added later the same day still count as new.
+    pub seen_urls: Vec<String>,
+}
+
+/// A dated link line from a feed page, e.g. `=> post.gmi 2024-03-01 Title`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedEntry {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    /// Absolute URL of the entry.
+    pub url: String,
+    pub title: String,
+}
+
+/// Feeds followed with the Gemini subscription convention, in the order
+/// they were added.
+///
+/// Backed by a TSV file with one `url<TAB>last-seen-date<TAB>seen-urls`
+/// entry per line, the seen URLs separated by spaces, written back on
+/// every change. When no path is available (no home
+/// directory), subscriptions are kept in memory only.
+#[derive(Debug)]
+pub struct Subscriptions {
+    path: Option<PathBuf>,
+    entries: Vec<Subscription>,
+}
+
+impl Subscriptions {
+    /// Load the subscriptions file from the data directory.
+    pub fn load_default() -> Self {
+        match paths::data_dir() {
+            Some(dir) => Self::load(dir.join(SUBSCRIPTIONS_FILE)),
+            None => Self {
+                path: None,
+                entries: Vec::new(),
+            },
+        }
+    }
+
+    /// Load subscriptions from `path`. A missing or unreadable file yields
+    /// an empty list.
+    pub fn load(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .map(|contents| parse_subscriptions(&contents))
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            entries,
+        }
+    }
+
+    /// Subscribe to `url` and persist the file. Returns false if it was
+    /// already subscribed.
+    pub fn subscribe(&mut self, url: &str) -> io::Result<bool> {
+        if self.entries.iter().any(|s| s.url == url) {
+            return Ok(false);
+        }
+        self.entries.push(Subscription {
+            url: url.to_string(),
+            last_seen: None,
+            seen_urls: Vec::new(),
+        });
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Record the entries in `shown`, as returned by `new_entries`, as seen
+    /// in the feed at `index` (zero-based) and persist the file.
+    pub fn mark_seen(&mut self, index: usize, shown: &[FeedEntry]) -> io::Result<()> {
+        let Some(subscription) = self.entries.get_mut(index) else {
+            return Ok(());
+        };
+        let Some(newest) = shown.iter().map(|entry| entry.date.as_str()).max() else {
+            return Ok(());
+        };
+        if subscription.last_seen.as_deref() != Some(newest) {
+            subscription.last_seen = Some(newest.to_string());
+            subscription.seen_urls.clear();
+        }
+        subscription.seen_urls.extend(
+            shown
+                .iter()
+                .filter(|entry| entry.date == newest)
+                .map(|entry| entry.url.clone()),
+        );
+        self.save()
+    }
+
+    pub fn list(&self) -> &[Subscription] {
+        &self.entries
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, format_subscriptions(&self.entries))
+    }
+}
+
+/// Parse subscriptions file contents. Blank lines are skipped.
+fn parse_subscriptions(contents: &str) -> Vec<Subscription> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.split('\t');
+            let url = fields.next().unwrap_or_default().trim();
+            let last_seen = fields.next().unwrap_or_default().trim();
+            let seen_urls = fields.next().unwrap_or_default();
+            Subscription {
+                url: url.to_string(),
+                last_seen: (!last_seen.is_empty()).then(|| last_seen.to_string()),
+                seen_urls: seen_urls.split_whitespace().map(String::from).collect(),
+            }
+        })
+        .collect()
+}
+
+fn format_subscriptions(entries: &[Subscription]) -> String {
+    entries
+        .iter()
+        .map(|s| {
+            format!(
+                "{}\t{}\t{}\n",
+                s.url,
+                s.last_seen.as_deref().unwrap_or(""),
+                s.seen_urls.join(" ")
+            )
+        })
+        .collect()
+}
+
+/// Extract the dated links from a feed page fetched from `base`. Link
+/// labels must start with a `YYYY-MM-DD` date; other lines are ignored.
+pub fn parse_feed(body: &str, base: &Url) -> Vec<FeedEntry> {
+    parser::parse_gemini(body)
+        .into_iter()
+        .filter_map(|(_, line)| match line {
+            GeminiLine::Link { url, label } => {
+                let date = label.get(..10).filter(|date| is_date(date))?;
+                let title = label[10..].trim_start_matches([' ', '-', ':']).trim();
+                let url = url_utils::resolve_url(base, &url).ok()?;
+                Some(FeedEntry {
+                    date: date.to_string(),
+                    url: url.to_string(),
+                    title: title.to_string(),
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Entries of `subscription`'s feed not shown before, newest first: those
+/// dated after its `last_seen`, and those dated the same day but not among
+/// its `seen_urls`. Every entry is new when nothing has been seen yet.
+pub fn new_entries(mut entries: Vec<FeedEntry>, subscription: &Subscription) -> Vec<FeedEntry> {
+    entries.retain(|entry| match subscription.last_seen.as_deref() {
+        None => true,
+        Some(seen) => {
+            entry.date.as_str() > seen
+                || (entry.date == seen && !subscription.seen_urls.contains(&entry.url))
+        }
+    });
+    entries.sort_by(|a, b| b.date.cmp(&a.date));
+    entries
+}
+
+/// Whether `s` has the shape `YYYY-MM-DD`.
+fn is_date(s: &str) -> bool {
+    s.len() == 10
+        && s.bytes().enumerate().all(|(i, b)| match i {
+            4 | 7 => b == b'-',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: &str, url: &str, title: &str) -> FeedEntry {
+        FeedEntry {
+            date: date.to_string(),
+            url: url.to_string(),
+            title: title.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_feed() {
+        let base = Url::parse("gemini://example.com/log/").unwrap();
+        let feed = "# My log\n\
+                    => 2024-03-01.gmi 2024-03-01 - Spring\n\
+                    => /about About me\n\
+                    => gemini://other.example/x 2023-12-25 Holidays\n";
+        assert_eq!(
+            parse_feed(feed, &base),
+            vec![
+                entry(
+                    "2024-03-01",
+                    "gemini://example.com/log/2024-03-01.gmi",
+                    "Spring"
+                ),
+                entry("2023-12-25", "gemini://other.example/x", "Holidays"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_new_entries() {
+        let entries = vec![
+            entry("2024-01-01", "gemini://a/1", "one"),
+            entry("2024-03-01", "gemini://a/3", "three"),
+            entry("2024-02-01", "gemini://a/2", "two"),
+        ];
+        let titles = |entries: Vec<FeedEntry>| -> Vec<String> {
+            entries.into_iter().map(|e| e.title).collect()
+        };
+        let seen = |date: Option<&str>, urls: &[&str]| Subscription {
+            url: "gemini://a/".to_string(),
+            last_seen: date.map(String::from),
+            seen_urls: urls.iter().map(|url| url.to_string()).collect(),
+        };
+        assert_eq!(
+            titles(new_entries(entries.clone(), &seen(None, &[]))),
+            vec!["three", "two", "one"]
+        );
+        assert_eq!(
+            titles(new_entries(
+                entries.clone(),
+                &seen(Some("2024-01-01"), &["gemini://a/1"])
+            )),
+            vec!["three", "two"]
+        );
+        assert!(new_entries(entries, &seen(Some("2024-03-01"), &["gemini://a/3"])).is_empty());
+    }
+
+    #[test]
+    fn test_same_day_posts_stay_new_until_seen() {
+        let mut subscriptions = Subscriptions {
+            path: None,
+            entries: Vec::new(),
+        };
+        subscriptions.subscribe("gemini://a/").unwrap();
+        let morning = vec![entry("2024-03-01", "gemini://a/am", "morning")];
+        let shown = new_entries(morning.clone(), &subscriptions.list()[0]);
+        assert_eq!(shown.len(), 1);
+        subscriptions.mark_seen(0, &shown).unwrap();
+
+        // A second post the same day is new; the first isn't
+        let mut later = morning;
+        later.push(entry("2024-03-01", "gemini://a/pm", "evening"));
+        let shown = new_entries(later.clone(), &subscriptions.list()[0]);
+        assert_eq!(shown, vec![entry("2024-03-01", "gemini://a/pm", "evening")]);
+        subscriptions.mark_seen(0, &shown).unwrap();
+        assert!(new_entries(later, &subscriptions.list()[0]).is_empty());
+        assert_eq!(
+            subscriptions.list()[0].seen_urls,
+            vec!["gemini://a/am", "gemini://a/pm"]
+        );
+    }
+
+    #[test]
+    fn test_subscriptions_roundtrip() {
+        let path = std::env::temp_dir().join(format!(
+            "gemini-browser-subscriptions-{}",
+            std::process::id()
+        ));
+        let mut subscriptions = Subscriptions::load(path.clone());
+        assert!(subscriptions.subscribe("gemini://a.example/log/").unwrap());
+        assert!(!subscriptions.subscribe("gemini://a.example/log/").unwrap());
+        subscriptions.subscribe("gemini://b.example/").unwrap();
+        subscriptions
+            .mark_seen(0, &[entry("2024-03-01", "gemini://a.example/log/1", "one")])
+            .unwrap();
+
+        let reloaded = Subscriptions::load(path.clone());
+        assert_eq!(
+            reloaded.list(),
+            &[
+                Subscription {
+                    url: "gemini://a.example/log/".to_string(),
+                    last_seen: Some("2024-03-01".to_string()),
+                    seen_urls: vec!["gemini://a.example/log/1".to_string()],
+                },
+                Subscription {
+                    url: "gemini://b.example/".to_string(),
+                    last_seen: None,
+                    seen_urls: Vec::new(),
+                },
+            ]
+        );
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,159 @@
+//! A scripted Gemini server for tests that exercise the client end to end.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use url::Url;
+
+/// Response sent for requests that match no route.
+const NOT_FOUND: &[u8] = b"51 Not found\r\n";
+
+/// A Gemini server on `127.0.0.1` with a fresh self-signed certificate.
+///
+/// Each route maps a request path (plus `?query`, if any) to the raw bytes
+/// sent back. Adding several responses for one path serves them in turn,
+/// repeating the last one once the others are used up.
+#[derive(Default)]
+pub struct MockServer {
+    routes: HashMap<String, Vec<Vec<u8>>>,
+    tls12_only: bool,
+}
+
+/// A running `MockServer`. It serves until the test process exits.
+pub struct RunningServer {
+    addr: SocketAddr,
+    /// Every request line received, without its CRLF.
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer requests for `path` with `response` (header and body). Any
+    /// `{port}` in a response becomes the port the server listens on, so a
+    /// redirect can name it under another host name such as `localhost`.
+    pub fn route(mut self, path: &str, response: impl Into<Vec<u8>>) -> Self {
+        self.routes
+            .entry(path.to_string())
+            .or_default()
+            .push(response.into());
+        self
+    }
+
+    /// Refuse TLS 1.3, like an older server would.
+    pub fn tls12_only(mut self) -> Self {
+        self.tls12_only = true;
+        self
+    }
+
+    /// Bind to an ephemeral port and serve connections on a background
+    /// thread, one at a time.
+    pub fn start(self) -> RunningServer {
+        // Both ends of the connection need a provider, as main installs one
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = Arc::new(server_config(self.tls12_only));
+        let port = addr.port().to_string();
+        let routes: HashMap<String, Vec<Vec<u8>>> = self
+            .routes
+            .into_iter()
+            .map(|(path, responses)| {
+                let responses = responses
+                    .into_iter()
+                    .map(|response| match String::from_utf8(response) {
+                        Ok(text) => text.replace("{port}", &port).into_bytes(),
+                        // Binary bodies are sent as given
+                        Err(e) => e.into_bytes(),
+                    })
+                    .collect();
+                (path, responses)
+            })
+            .collect();
+        let mut served: HashMap<String, usize> = HashMap::new();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+
+        thread::spawn(move || {
+            for tcp in listener.incoming() {
+                let Ok(tcp) = tcp else { continue };
+                let Ok(conn) = ServerConnection::new(config.clone()) else {
+                    continue;
+                };
+                let mut stream = BufReader::new(StreamOwned::new(conn, tcp));
+                let mut request = String::new();
+                if stream.read_line(&mut request).is_err() {
+                    continue;
+                }
+
+                let request = request.trim_end();
+                received.lock().unwrap().push(request.to_string());
+
+                let key = request_key(request);
+                let response = match routes.get(&key) {
+                    Some(responses) => {
+                        let count = served.entry(key).or_default();
+                        let response = &responses[(*count).min(responses.len() - 1)];
+                        *count += 1;
+                        response.clone()
+                    }
+                    None => NOT_FOUND.to_vec(),
+                };
+
+                let stream = stream.get_mut();
+                let _ = stream.write_all(&response);
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+            }
+        });
+
+        RunningServer { addr, requests }
+    }
+}
+
+impl RunningServer {
+    /// The gemini:// URL for `path` on this server.
+    pub fn url(&self, path: &str) -> Url {
+        Url::parse(&format!("gemini://127.0.0.1:{}{path}", self.addr.port())).unwrap()
+    }
+
+    /// The request lines received so far, oldest first.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// The route key for a request line: its path and query.
+fn request_key(request: &str) -> String {
+    match Url::parse(request) {
+        Ok(url) => match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        },
+        Err(_) => String::new(),
+    }
+}
+
+fn server_config(tls12_only: bool) -> ServerConfig {
+    let versions: &[_] = if tls12_only {
+        &[&rustls::version::TLS12]
+    } else {
+        rustls::ALL_VERSIONS
+    };
+    let certified =
+        rcgen::generate_simple_self_signed(vec!["localhost".to_string(), "127.0.0.1".to_string()])
+            .unwrap();
+    let cert = certified.cert.der().clone();
+    let key = PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der());
+    ServerConfig::builder_with_protocol_versions(versions)
+        .with_no_client_auth()
+        .with_single_cert(vec![cert], PrivateKeyDer::Pkcs8(key))
+        .unwrap()
+}
@@ -0,0 +1,240 @@
+use unicode_width::UnicodeWidthChar;
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+
+/// Remove ANSI escape sequences from `s`.
+///
+/// Handles CSI sequences (`ESC [ ... final`) used for styling and OSC
+/// sequences (`ESC ] ... BEL` or `ESC ] ... ESC \`) used for hyperlinks.
+pub fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find(ESC) {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + escape_len(&rest[i..])..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `strip_ansi`, also returning the byte offset in `s` of each byte of the
+/// stripped text, so matches in it can be mapped back to `s`.
+pub fn strip_ansi_offsets(s: &str) -> (String, Vec<usize>) {
+    let mut out = String::with_capacity(s.len());
+    let mut offsets = Vec::with_capacity(s.len());
+    let mut pos = 0;
+    while let Some(i) = s[pos..].find(ESC) {
+        out.push_str(&s[pos..pos + i]);
+        offsets.extend(pos..pos + i);
+        pos += i + escape_len(&s[pos + i..]);
+    }
+    out.push_str(&s[pos..]);
+    offsets.extend(pos..s.len());
+    (out, offsets)
+}
+
+/// Byte length of the escape sequence at the start of `s`, which begins
+/// with ESC. An unterminated sequence runs to the end of `s`.
+fn escape_len(s: &str) -> usize {
+    let mut chars = s.char_indices().skip(1);
+    match chars.next() {
+        Some((_, '[')) => {
+            // CSI: parameters and intermediates, ended by a byte in 0x40..=0x7E
+            chars
+                .find(|(_, c)| ('\x40'..='\x7e').contains(c))
+                .map_or(s.len(), |(i, _)| i + 1)
+        }
+        Some((_, ']')) => {
+            // OSC: ended by BEL or ST (ESC \)
+            let mut after_esc = false;
+            for (i, c) in chars {
+                if c == BEL || (after_esc && c == '\\') {
+                    return i + 1;
+                }
+                after_esc = c == ESC;
+            }
+            s.len()
+        }
+        // Two-character escape
+        Some((i, c)) => i + c.len_utf8(),
+        None => s.len(),
+    }
+}
+
+/// Split `line` into the rows a terminal `width` columns wide would show it
+/// on. Escape sequences take no room and stay with the text after them, so
+/// styling carries over from row to row. A `width` of 0 leaves the line
+/// whole.
+pub fn fold(line: &str, width: usize) -> Vec<String> {
+    if width == 0 || display_width(line) <= width {
+        return vec![line.to_string()];
+    }
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    let mut used = 0;
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let len = if c == ESC {
+            escape_len(rest)
+        } else {
+            let w = char_width(c);
+            if used + w > width && used > 0 {
+                rows.push(std::mem::take(&mut row));
+                used = 0;
+            }
+            used += w;
+            c.len_utf8()
+        };
+        row.push_str(&rest[..len]);
+        rest = &rest[len..];
+    }
+    rows.push(row);
+    rows
+}
+
+/// Display width of a single character in terminal columns.
+/// Control characters count as zero; wide (CJK, emoji) glyphs count as two.
+pub fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// Visible width of `s` in terminal columns, ignoring ANSI escape sequences.
+pub fn display_width(s: &str) -> usize {
+    strip_ansi(s).chars().map(char_width).sum()
+}
+
+/// Replace each tab in plain text with spaces up to the next multiple of
+/// `tab_width` columns. A `tab_width` of 0 leaves tabs alone.
+pub fn expand_tabs(s: &str, tab_width: usize) -> String {
+    if tab_width == 0 || !s.contains('\t') {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut column = 0;
+    for c in s.chars() {
+        if c == '\t' {
+            let spaces = tab_width - column % tab_width;
+            out.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            out.push(c);
+            column += char_width(c);
+        }
+    }
+    out
+}
+
+/// The longest prefix of plain text (no ANSI escapes) that fits in
+/// `width` columns. A wide glyph that would straddle the limit is left out.
+pub fn take_width(s: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in s.char_indices() {
+        used += char_width(c);
+        if used > width {
+            return &s[..i];
+        }
+    }
+    s
+}
+
+/// Shorten plain text (no ANSI escapes) to at most `width` columns, ending
+/// in `...` when anything was cut.
+pub fn truncate(s: &str, width: usize) -> String {
+    if s.chars().map(char_width).sum::<usize>() <= width {
+        return s.to_string();
+    }
+    format!("{}...", take_width(s, width.saturating_sub(3)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi_sgr() {
+        assert_eq!(strip_ansi("\x1b[1;96mTitle\x1b[0m"), "Title");
+    }
+
+    #[test]
+    fn test_strip_ansi_osc8_hyperlink() {
+        let link = "\x1b]8;;gemini://a/\x1b\\label\x1b]8;;\x1b\\";
+        assert_eq!(strip_ansi(link), "label");
+        assert_eq!(strip_ansi("\x1b]0;title\x07text"), "text");
+    }
+
+    #[test]
+    fn test_strip_ansi_offsets() {
+        let (plain, offsets) = strip_ansi_offsets("a\x1b[1mbc\x1b[0m");
+        assert_eq!(plain, "abc");
+        assert_eq!(offsets, vec![0, 5, 6]);
+    }
+
+    #[test]
+    fn test_strip_ansi_unterminated() {
+        assert_eq!(strip_ansi("ok\x1b[31"), "ok");
+        assert_eq!(strip_ansi("ok\x1b]8;;x\x1bq"), "ok");
+        assert_eq!(strip_ansi("a\x1bMb"), "ab");
+    }
+
+    #[test]
+    fn test_expand_tabs() {
+        assert_eq!(expand_tabs("\tx", 8), "        x");
+        assert_eq!(expand_tabs("ab\tc", 4), "ab  c");
+        assert_eq!(expand_tabs("abcd\tc", 4), "abcd    c");
+        assert_eq!(expand_tabs("a\tb\tc", 2), "a b c");
+        // Wide glyphs take two columns
+        assert_eq!(expand_tabs("\u{4e2d}\tx", 4), "\u{4e2d}  x");
+        assert_eq!(expand_tabs("a\tb", 0), "a\tb");
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        assert_eq!(fold("abc", 3), vec!["abc"]);
+        assert_eq!(fold("abcdef", 0), vec!["abcdef"]);
+        // Wide glyphs never straddle rows
+        assert_eq!(fold("日本語", 5), vec!["日本", "語"]);
+    }
+
+    #[test]
+    fn test_fold_keeps_escapes() {
+        let rows = fold("\x1b[36mabcd\x1b[0mef", 3);
+        assert_eq!(rows, vec!["\x1b[36mabc", "d\x1b[0mef"]);
+        assert_eq!(rows.iter().map(|r| display_width(r)).max(), Some(3));
+    }
+
+    #[test]
+    fn test_display_width_ascii() {
+        assert_eq!(display_width("hello"), 5);
+        assert_eq!(display_width("\x1b[36m[1] hello\x1b[0m"), 9);
+    }
+
+    #[test]
+    fn test_display_width_wide_glyphs() {
+        assert_eq!(display_width("日本語"), 6);
+        assert_eq!(display_width("a🎉b"), 4);
+    }
+
+    #[test]
+    fn test_take_width() {
+        assert_eq!(take_width("abcdef", 4), "abcd");
+        assert_eq!(take_width("abc", 4), "abc");
+        assert_eq!(take_width("abc", 0), "");
+        assert_eq!(take_width("日本語", 5), "日本");
+        assert_eq!(take_width("a日本", 2), "a");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("a long host name", 10), "a long ...");
+        assert_eq!(truncate("日本語日本語", 7), "日本...");
+    }
+
+    #[test]
+    fn test_display_width_combining_marks() {
+        // "e" followed by a combining acute accent occupies one column.
+        assert_eq!(display_width("e\u{301}"), 1);
+    }
+}
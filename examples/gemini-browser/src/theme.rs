@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::paths;
+
+const THEME_FILE: &str = "theme.toml";
+
+/// ANSI styles used by `render`, each a complete SGR escape sequence such as
+/// `"\x1b[1;96m"`. An empty string leaves that element unstyled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub h1: String,
+    pub h2: String,
+    pub h3: String,
+    pub link: String,
+    pub visited_link: String,
+    /// The `(url)` after a link label when link URLs are shown.
+    pub link_url: String,
+    pub quote: String,
+    /// The `│` bar in front of each quote line.
+    pub quote_bar: String,
+    pub bullet: String,
+    /// Caption above a preformatted block with alt text.
+    pub caption: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            h1: sgr("1;96"),        // bold bright cyan
+            h2: sgr("1;36"),        // bold cyan
+            h3: sgr("1"),           // bold
+            link: sgr("36"),        // cyan
+            visited_link: sgr("2"), // dim
+            link_url: sgr("2"),     // dim
+            quote: sgr("2;3"),      // dim italic
+            quote_bar: sgr("2"),    // dim
+            bullet: String::new(),
+            caption: sgr("2"), // dim
+        }
+    }
+}
+
+/// `theme.toml` as written by the user. Each value is a list of SGR
+/// parameters, e.g. `h1 = "1;96"`; omitted keys keep their default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    h1: Option<String>,
+    h2: Option<String>,
+    h3: Option<String>,
+    link: Option<String>,
+    visited_link: Option<String>,
+    link_url: Option<String>,
+    quote: Option<String>,
+    quote_bar: Option<String>,
+    bullet: Option<String>,
+    caption: Option<String>,
+}
+
+impl Theme {
+    /// Load `theme.toml` from the config directory. A missing file gives the
+    /// default theme; an invalid one is an error saying what is wrong.
+    pub fn load_default() -> Result<Self, String> {
+        match paths::config_dir() {
+            Some(dir) => Self::load(&dir.join(THEME_FILE)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load a theme file from `path`, as `load_default` does.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let Ok(contents) = fs::read_to_string(path) else {
+            return Ok(Self::default());
+        };
+        Self::from_toml(&contents).map_err(|e| format!("invalid theme {}: {e}", path.display()))
+    }
+
+    /// Parse theme file contents, starting from the default theme.
+    pub fn from_toml(contents: &str) -> Result<Self, String> {
+        let file: ThemeFile = toml::from_str(contents).map_err(|e| e.message().to_string())?;
+        let mut theme = Self::default();
+        for (slot, value) in [
+            (&mut theme.h1, file.h1),
+            (&mut theme.h2, file.h2),
+            (&mut theme.h3, file.h3),
+            (&mut theme.link, file.link),
+            (&mut theme.visited_link, file.visited_link),
+            (&mut theme.link_url, file.link_url),
+            (&mut theme.quote, file.quote),
+            (&mut theme.quote_bar, file.quote_bar),
+            (&mut theme.bullet, file.bullet),
+            (&mut theme.caption, file.caption),
+        ] {
+            if let Some(params) = value {
+                if !params.chars().all(|c| c.is_ascii_digit() || c == ';') {
+                    return Err(format!("invalid SGR parameters: {params:?}"));
+                }
+                *slot = sgr(&params);
+            }
+        }
+        Ok(theme)
+    }
+}
+
+/// Build the escape sequence for SGR `params`; empty params mean no style.
+fn sgr(params: &str) -> String {
+    if params.is_empty() {
+        String::new()
+    } else {
+        format!("\x1b[{params}m")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml_overrides_some_fields() {
+        let theme = Theme::from_toml("h1 = \"1;35\"\nbullet = \"33\"\n").unwrap();
+        assert_eq!(theme.h1, "\x1b[1;35m");
+        assert_eq!(theme.bullet, "\x1b[33m");
+        assert_eq!(theme.link, Theme::default().link);
+    }
+
+    #[test]
+    fn test_from_toml_empty_value_disables_style() {
+        let theme = Theme::from_toml("quote = \"\"").unwrap();
+        assert_eq!(theme.quote, "");
+    }
+
+    #[test]
+    fn test_from_toml_rejects_bad_input() {
+        assert!(Theme::from_toml("h1 = \"bold\"").is_err());
+        assert!(Theme::from_toml("heading = \"1\"").is_err());
+        assert!(Theme::from_toml("h1 = 1").is_err());
+        assert!(Theme::from_toml("not toml").is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use std::path::Path;
+
+use url::Url;
+
+use crate::gemini::{GeminiClient, GeminiError, GeminiResponse};
+
+/// Parse a titan:// URL string. Like `parse_gemini_url`, an empty path
+/// becomes "/".
+pub fn parse_titan_url(raw: &str) -> Result<Url, GeminiError> {
+    let mut url = Url::parse(raw).map_err(|e| GeminiError::InvalidUrl(format!("{raw}: {e}")))?;
+    if url.scheme() != "titan" {
+        return Err(GeminiError::InvalidUrl(format!(
+            "expected titan:// scheme, got {}://",
+            url.scheme()
+        )));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(GeminiError::InvalidUrl("missing host in URL".to_string()));
+    }
+    if url.path().is_empty() {
+        url.set_path("/");
+    }
+    Ok(url)
+}
+
+/// The gemini:// URL for the same resource, used to resolve the server's
+/// response (typically a redirect to the uploaded page).
+pub fn gemini_url(url: &Url) -> Url {
+    let mut gemini = url.clone();
+    // Both schemes are non-special, so the change cannot fail
+    let _ = gemini.set_scheme("gemini");
+    gemini
+}
+
+/// Build the Titan request line: the URL with `;size=`, `;mime=`, and an
+/// optional `;token=` appended to its path.
+fn request_line(url: &Url, size: usize, mime: &str, token: Option<&str>) -> String {
+    let mut path = format!("{};size={size};mime={mime}", url.path());
+    if let Some(token) = token {
+        path.push_str(&format!(";token={token}"));
+    }
+    let mut target = url.clone();
+    target.set_path(&path);
+    // As with gemini requests, the fragment stays on the client
+    target.set_fragment(None);
+    format!("{target}\r\n")
+}
+
+/// Upload `data` to a titan:// URL, connecting exactly as `fetch` does.
+/// The response is returned as-is; redirects are not followed.
+pub fn upload(
+    client: &GeminiClient,
+    url: &Url,
+    data: &[u8],
+    mime: &str,
+    token: Option<&str>,
+) -> Result<GeminiResponse, GeminiError> {
+    client.exchange(url, &request_line(url, data.len(), mime, token), data)
+}
+
+/// Guess the MIME type of a file from its extension.
+pub fn guess_mime(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gmi" | "gemini") => "text/gemini",
+        Some("txt") => "text/plain",
+        Some("md") => "text/markdown",
+        Some("html" | "htm") => "text/html",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_line() {
+        let url = parse_titan_url("titan://example.com/notes/new.gmi").unwrap();
+        assert_eq!(
+            request_line(&url, 12, "text/gemini", None),
+            "titan://example.com/notes/new.gmi;size=12;mime=text/gemini\r\n"
+        );
+        assert_eq!(
+            request_line(&url, 0, "text/plain", Some("secret")),
+            "titan://example.com/notes/new.gmi;size=0;mime=text/plain;token=secret\r\n"
+        );
+        let url = parse_titan_url("titan://example.com/new.gmi#section").unwrap();
+        assert_eq!(
+            request_line(&url, 1, "text/plain", None),
+            "titan://example.com/new.gmi;size=1;mime=text/plain\r\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_titan_url() {
+        assert_eq!(
+            parse_titan_url("titan://example.com").unwrap().as_str(),
+            "titan://example.com/"
+        );
+        assert!(parse_titan_url("gemini://example.com/").is_err());
+        assert!(parse_titan_url("not a url").is_err());
+    }
+
+    #[test]
+    fn test_gemini_url() {
+        let url = parse_titan_url("titan://example.com:1966/a.gmi").unwrap();
+        assert_eq!(gemini_url(&url).as_str(), "gemini://example.com:1966/a.gmi");
+    }
+
+    #[test]
+    fn test_guess_mime() {
+        assert_eq!(guess_mime(Path::new("post.GMI")), "text/gemini");
+        assert_eq!(guess_mime(Path::new("cat.png")), "image/png");
+        assert_eq!(guess_mime(Path::new("README")), "application/octet-stream");
+    }
+}
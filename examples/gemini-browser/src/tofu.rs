@@ -0,0 +1,468 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, OtherError, SignatureScheme};
+
+use time::OffsetDateTime;
+
+use crate::cert::CertificateInfo;
+use crate::paths;
+
+const KNOWN_HOSTS_FILE: &str = "known_hosts";
+
+/// Compute the SHA-256 fingerprint of a DER-encoded certificate as lowercase hex.
+pub fn fingerprint(cert_der: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, cert_der);
+    digest.as_ref().iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The outcome of checking a presented certificate against the pinned set.
+#[derive(Debug, PartialEq)]
+pub enum TrustDecision {
+    /// The host has never been seen before.
+    FirstUse,
+    /// The presented fingerprint matches the pinned one.
+    Trusted,
+    /// The host is pinned to a different fingerprint.
+    Changed { pinned: String },
+}
+
+/// Pinned certificate fingerprints keyed by `host:port`.
+///
+/// Backed by a `known_hosts` file with one `host:port fingerprint` entry per
+/// line. The default set, and the one loaded when there is no home
+/// directory, keeps pins in memory only.
+#[derive(Debug, Default)]
+pub struct KnownHosts {
+    path: Option<PathBuf>,
+    entries: BTreeMap<String, String>,
+}
+
+impl KnownHosts {
+    /// Load the `known_hosts` file from the config directory.
+    pub fn load_default() -> Self {
+        match paths::config_dir() {
+            Some(dir) => Self::load(dir.join(KNOWN_HOSTS_FILE)),
+            None => Self::default(),
+        }
+    }
+
+    /// Load pins from `path`. A missing or unreadable file yields an empty set.
+    pub fn load(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .map(|contents| parse_known_hosts(&contents))
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            entries,
+        }
+    }
+
+    /// Check a fingerprint for `host:port` against the pinned one, if any.
+    pub fn check(&self, key: &str, fingerprint: &str) -> TrustDecision {
+        match self.entries.get(key) {
+            None => TrustDecision::FirstUse,
+            Some(pinned) if pinned == fingerprint => TrustDecision::Trusted,
+            Some(pinned) => TrustDecision::Changed {
+                pinned: pinned.clone(),
+            },
+        }
+    }
+
+    /// Pin `fingerprint` for `host:port` and persist the file, if there is
+    /// one.
+    pub fn pin(&mut self, key: &str, fingerprint: &str) -> io::Result<()> {
+        self.entries
+            .insert(key.to_string(), fingerprint.to_string());
+        self.save()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, format_known_hosts(&self.entries))
+    }
+}
+
+/// Parse `known_hosts` contents. Blank lines and `#` comments are skipped.
+fn parse_known_hosts(contents: &str) -> BTreeMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let key = parts.next()?;
+            let fingerprint = parts.next()?;
+            Some((key.to_string(), fingerprint.to_string()))
+        })
+        .collect()
+}
+
+fn format_known_hosts(entries: &BTreeMap<String, String>) -> String {
+    entries
+        .iter()
+        .map(|(key, fingerprint)| format!("{key} {fingerprint}\n"))
+        .collect()
+}
+
+/// Raised by `TofuVerifier` when a pinned host presents a different certificate.
+///
+/// Travels inside `rustls::Error` so `fetch` can recover it after the handshake
+/// fails and report it as `GeminiError::CertificateChanged`.
+#[derive(Debug)]
+pub struct CertificateMismatch {
+    pub host: String,
+    pub pinned: String,
+    pub presented: String,
+}
+
+impl fmt::Display for CertificateMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "certificate for {} changed (pinned {}, presented {})",
+            self.host, self.pinned, self.presented
+        )
+    }
+}
+
+impl std::error::Error for CertificateMismatch {}
+
+/// Raised by `TofuVerifier` when a certificate is used outside its validity
+/// period. `fetch` reports it as a `GeminiError::TlsError`.
+#[derive(Debug)]
+pub struct CertificateOutOfDate {
+    pub host: String,
+    /// What is wrong, with the validity dates, from
+    /// `CertificateInfo::validity_error`.
+    pub problem: String,
+}
+
+impl fmt::Display for CertificateOutOfDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "certificate for {} {}", self.host, self.problem)
+    }
+}
+
+impl std::error::Error for CertificateOutOfDate {}
+
+/// Told the `host:port` of a certificate that was pinned but couldn't be
+/// saved to `known_hosts`, and why.
+pub type SaveErrorHandler = Arc<dyn Fn(&str, &io::Error) + Send + Sync>;
+
+/// A ServerCertVerifier implementing Gemini's trust-on-first-use model.
+///
+/// The first certificate seen for a `host:port` is pinned; later connections
+/// must present the same certificate or the handshake fails with a
+/// `CertificateMismatch`. Certificates that have expired or are not yet
+/// valid are refused with a `CertificateOutOfDate` before any pinning.
+pub struct TofuVerifier {
+    port: u16,
+    known_hosts: Arc<Mutex<KnownHosts>>,
+    /// A changed certificate with this fingerprint is let through once,
+    /// without updating the pin.
+    accept_once: Option<String>,
+    on_save_error: Option<SaveErrorHandler>,
+}
+
+impl fmt::Debug for TofuVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TofuVerifier")
+            .field("port", &self.port)
+            .field("known_hosts", &self.known_hosts)
+            .field("accept_once", &self.accept_once)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TofuVerifier {
+    pub fn new(known_hosts: Arc<Mutex<KnownHosts>>, port: u16) -> Self {
+        Self {
+            port,
+            known_hosts,
+            accept_once: None,
+            on_save_error: None,
+        }
+    }
+
+    /// Call `handler` when a first-seen certificate is pinned but
+    /// `known_hosts` can't be saved. The connection goes ahead either way.
+    pub fn on_save_error(mut self, handler: SaveErrorHandler) -> Self {
+        self.on_save_error = Some(handler);
+        self
+    }
+
+    /// Also accept a certificate with `fingerprint` where the pin says
+    /// otherwise, leaving `known_hosts` as it is.
+    pub fn accepting(mut self, fingerprint: &str) -> Self {
+        self.accept_once = Some(fingerprint.to_string());
+        self
+    }
+}
+
+impl ServerCertVerifier for TofuVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let key = format!("{}:{}", server_name.to_str(), self.port);
+
+        let now = i64::try_from(now.as_secs())
+            .ok()
+            .and_then(|secs| OffsetDateTime::from_unix_timestamp(secs).ok())
+            .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+        if let Some(problem) = CertificateInfo::from_der(end_entity.as_ref()).validity_error(now) {
+            let out_of_date = CertificateOutOfDate { host: key, problem };
+            return Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                OtherError(Arc::new(out_of_date)),
+            )));
+        }
+
+        let presented = fingerprint(end_entity.as_ref());
+
+        let mut known_hosts = self
+            .known_hosts
+            .lock()
+            .map_err(|_| rustls::Error::General("known_hosts lock poisoned".to_string()))?;
+
+        match known_hosts.check(&key, &presented) {
+            TrustDecision::Trusted => Ok(ServerCertVerified::assertion()),
+            TrustDecision::FirstUse => {
+                if let Err(e) = known_hosts.pin(&key, &presented) {
+                    if let Some(handler) = &self.on_save_error {
+                        handler(&key, &e);
+                    }
+                }
+                Ok(ServerCertVerified::assertion())
+            }
+            TrustDecision::Changed { .. }
+                if self.accept_once.as_deref() == Some(presented.as_str()) =>
+            {
+                Ok(ServerCertVerified::assertion())
+            }
+            TrustDecision::Changed { pinned } => {
+                let mismatch = CertificateMismatch {
+                    host: key,
+                    pinned,
+                    presented,
+                };
+                Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                    OtherError(Arc::new(mismatch)),
+                )))
+            }
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &rustls::crypto::ring::default_provider().signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &rustls::crypto::ring::default_provider().signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        rustls::crypto::ring::default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_sha256_hex() {
+        assert_eq!(
+            fingerprint(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_check_first_use_then_trusted() {
+        let mut hosts = KnownHosts::default();
+        assert_eq!(
+            hosts.check("example.com:1965", "aa"),
+            TrustDecision::FirstUse
+        );
+        hosts.pin("example.com:1965", "aa").unwrap();
+        assert_eq!(
+            hosts.check("example.com:1965", "aa"),
+            TrustDecision::Trusted
+        );
+    }
+
+    #[test]
+    fn test_check_changed() {
+        let mut hosts = KnownHosts::default();
+        hosts.pin("example.com:1965", "aa").unwrap();
+        assert_eq!(
+            hosts.check("example.com:1965", "bb"),
+            TrustDecision::Changed {
+                pinned: "aa".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_pins_are_per_port() {
+        let mut hosts = KnownHosts::default();
+        hosts.pin("example.com:1965", "aa").unwrap();
+        assert_eq!(
+            hosts.check("example.com:1966", "bb"),
+            TrustDecision::FirstUse
+        );
+    }
+
+    #[test]
+    fn test_parse_known_hosts_skips_comments() {
+        let entries = parse_known_hosts("# pinned hosts\n\nexample.com:1965 aa\nbad-line\n");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries["example.com:1965"], "aa");
+    }
+
+    #[test]
+    fn test_known_hosts_roundtrip() {
+        let path =
+            std::env::temp_dir().join(format!("gemini-browser-known-hosts-{}", std::process::id()));
+        let mut hosts = KnownHosts::load(path.clone());
+        hosts.pin("a.example:1965", "aa").unwrap();
+        hosts.pin("b.example:1966", "bb").unwrap();
+
+        let reloaded = KnownHosts::load(path.clone());
+        assert_eq!(
+            reloaded.check("a.example:1965", "aa"),
+            TrustDecision::Trusted
+        );
+        assert_eq!(
+            reloaded.check("b.example:1966", "bb"),
+            TrustDecision::Trusted
+        );
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_verifier_rejects_changed_certificate() {
+        let hosts = Arc::new(Mutex::new(KnownHosts::default()));
+        let verifier = TofuVerifier::new(hosts, 1965);
+        let name = ServerName::try_from("example.com").unwrap();
+        let first = CertificateDer::from(vec![1u8, 2, 3]);
+        let second = CertificateDer::from(vec![4u8, 5, 6]);
+
+        assert!(verifier
+            .verify_server_cert(&first, &[], &name, &[], UnixTime::now())
+            .is_ok());
+        assert!(verifier
+            .verify_server_cert(&first, &[], &name, &[], UnixTime::now())
+            .is_ok());
+
+        let err = verifier
+            .verify_server_cert(&second, &[], &name, &[], UnixTime::now())
+            .unwrap_err();
+        match err {
+            rustls::Error::InvalidCertificate(CertificateError::Other(other)) => {
+                let mismatch = other.0.downcast_ref::<CertificateMismatch>().unwrap();
+                assert_eq!(mismatch.host, "example.com:1965");
+                assert_eq!(mismatch.pinned, fingerprint(&[1, 2, 3]));
+                assert_eq!(mismatch.presented, fingerprint(&[4, 5, 6]));
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_verifier_accepts_fingerprint_once_without_pinning() {
+        let mut known_hosts = KnownHosts::default();
+        known_hosts
+            .pin("example.com:1965", &fingerprint(&[1, 2, 3]))
+            .unwrap();
+        let hosts = Arc::new(Mutex::new(known_hosts));
+        let verifier = TofuVerifier::new(hosts.clone(), 1965).accepting(&fingerprint(&[4, 5, 6]));
+        let name = ServerName::try_from("example.com").unwrap();
+
+        let accepted = CertificateDer::from(vec![4u8, 5, 6]);
+        assert!(verifier
+            .verify_server_cert(&accepted, &[], &name, &[], UnixTime::now())
+            .is_ok());
+        let other = CertificateDer::from(vec![7u8, 8, 9]);
+        assert!(verifier
+            .verify_server_cert(&other, &[], &name, &[], UnixTime::now())
+            .is_err());
+        assert_eq!(
+            hosts
+                .lock()
+                .unwrap()
+                .check("example.com:1965", &fingerprint(&[1, 2, 3])),
+            TrustDecision::Trusted
+        );
+    }
+
+    #[test]
+    fn test_verifier_rejects_expired_certificate() {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(vec!["example.com".to_string()]).unwrap();
+        params.not_before = rcgen::date_time_ymd(2020, 1, 1);
+        params.not_after = rcgen::date_time_ymd(2021, 1, 1);
+        let cert = params.self_signed(&key_pair).unwrap();
+
+        let hosts = Arc::new(Mutex::new(KnownHosts::default()));
+        let verifier = TofuVerifier::new(hosts.clone(), 1965);
+        let name = ServerName::try_from("example.com").unwrap();
+        let err = verifier
+            .verify_server_cert(cert.der(), &[], &name, &[], UnixTime::now())
+            .unwrap_err();
+        match err {
+            rustls::Error::InvalidCertificate(CertificateError::Other(other)) => {
+                let out_of_date = other.0.downcast_ref::<CertificateOutOfDate>().unwrap();
+                assert_eq!(
+                    out_of_date.to_string(),
+                    "certificate for example.com:1965 expired on 2021-01-01 00:00:00 UTC \
+                     (valid from 2020-01-01 00:00:00 UTC until 2021-01-01 00:00:00 UTC)"
+                );
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        // Never pinned
+        assert_eq!(
+            hosts.lock().unwrap().check("example.com:1965", "x"),
+            TrustDecision::FirstUse
+        );
+    }
+}
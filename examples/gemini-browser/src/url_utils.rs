@@ -1,10 +1,27 @@
+use percent_encoding::percent_decode_str;
 use url::Url;
 
 use crate::gemini::GeminiError;
 
-/// Parse a gemini:// URL string into a `url::Url`.
-/// Ensures scheme is "gemini", default port 1965, default path "/".
+/// Port assumed when a gemini:// URL doesn't give one.
+const DEFAULT_PORT: u16 = 1965;
+
+/// Longest request URL the Gemini spec allows, in bytes.
+const MAX_URL_LEN: usize = 1024;
+
+/// Parse a gemini:// URL string into a `url::Url`. Input without a scheme,
+/// such as `example.com/path`, is taken as a gemini:// URL.
+/// Ensures scheme is "gemini", default port 1965, default path "/", an
+/// ASCII (punycode) host, and that the serialized URL fits in `MAX_URL_LEN`
+/// bytes.
 pub fn parse_gemini_url(raw: &str) -> Result<Url, GeminiError> {
+    let with_scheme;
+    let raw = if raw.contains("://") {
+        raw
+    } else {
+        with_scheme = format!("gemini://{raw}");
+        &with_scheme
+    };
     let parsed = Url::parse(raw).map_err(|e| GeminiError::InvalidUrl(format!("{raw}: {e}")))?;
 
     if parsed.scheme() != "gemini" {
@@ -25,7 +42,7 @@ pub fn parse_gemini_url(raw: &str) -> Result<Url, GeminiError> {
         result.set_path("/");
     }
 
-    Ok(result)
+    check_length(ascii_host(result)?)
 }
 
 /// Resolve a potentially-relative URL against a base URL.
@@ -50,7 +67,124 @@ pub fn resolve_url(base: &Url, reference: &str) -> Result<Url, GeminiError> {
         )));
     }
 
-    Ok(resolved)
+    check_length(ascii_host(resolved)?)
+}
+
+/// Punycode an internationalized host so it can be used for DNS and SNI,
+/// and lowercase an ASCII one so `Example.COM` and `example.com` are the
+/// same host.
+///
+/// `url::Url` only applies IDNA to special schemes like http; for gemini://
+/// it keeps the host opaque, as typed, and percent-encodes non-ASCII bytes.
+fn ascii_host(mut url: Url) -> Result<Url, GeminiError> {
+    let Some(host) = url.host_str().map(str::to_string) else {
+        return Ok(url);
+    };
+    if host.is_ascii() && !host.contains('%') {
+        if host.bytes().any(|b| b.is_ascii_uppercase()) {
+            url.set_host(Some(&host.to_ascii_lowercase()))
+                .map_err(|_| GeminiError::InvalidUrl(format!("invalid host name: {host}")))?;
+        }
+        return Ok(url);
+    }
+    let invalid = || GeminiError::InvalidUrl(format!("invalid host name: {host}"));
+    let decoded = percent_decode_str(&host)
+        .decode_utf8()
+        .map_err(|_| invalid())?;
+    let ascii = idna::domain_to_ascii(&decoded).map_err(|_| invalid())?;
+    url.set_host(Some(&ascii)).map_err(|_| invalid())?;
+    Ok(url)
+}
+
+/// The URL's host in Unicode form for display, e.g. `café.example` for
+/// `xn--caf-dma.example`. Empty when the URL has no host.
+pub fn display_host(url: &Url) -> String {
+    url.host_str()
+        .map(|host| idna::domain_to_unicode(host).0)
+        .unwrap_or_default()
+}
+
+/// The host to show for a URL, with `:port` appended only when it isn't
+/// the Gemini default, e.g. `example.com` or `example.com:1966`.
+pub fn display_authority(url: &Url) -> String {
+    let host = display_host(url);
+    match url.port().filter(|&port| port != DEFAULT_PORT) {
+        Some(port) => format!("{host}:{port}"),
+        None => host,
+    }
+}
+
+/// The form of `url` used to tell whether two URLs name the same resource:
+/// host lowercased, the default port dropped, and an empty path written as
+/// `/`. `url::Url` already resolves `.` and `..` segments when parsing.
+pub fn normalize(url: &Url) -> Url {
+    let mut url = url.clone();
+    if let Some(host) = url.host_str().map(str::to_ascii_lowercase) {
+        let _ = url.set_host(Some(&host));
+    }
+    if url.scheme() == "gemini" && url.port() == Some(DEFAULT_PORT) {
+        let _ = url.set_port(None);
+    }
+    if url.path().is_empty() {
+        url.set_path("/");
+    }
+    url
+}
+
+/// `normalize`, plus percent-encoding in its minimal form: escapes of
+/// unreserved characters (letters, digits, `-._~`) decoded and the rest
+/// written with uppercase hex, so `/%7euser` and `/~user` compare equal.
+/// The scheme is lowercased by `url::Url` already.
+pub fn canonicalize(url: &Url) -> Url {
+    let mut url = normalize(url);
+    let path = minimal_escapes(url.path());
+    url.set_path(&path);
+    if let Some(query) = url.query().map(minimal_escapes) {
+        url.set_query(Some(&query));
+    }
+    url
+}
+
+/// Rewrite each `%XY` escape in `s`: decoded if it stands for an
+/// unreserved character, uppercased otherwise.
+fn minimal_escapes(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('%') {
+        out.push_str(&rest[..i]);
+        let escape = &rest[i..];
+        let byte = escape
+            .get(1..3)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match byte {
+            Some(b) if b.is_ascii_alphanumeric() || b"-._~".contains(&b) => {
+                out.push(char::from(b));
+                rest = &escape[3..];
+            }
+            Some(_) => {
+                out.push_str(&escape[..3].to_ascii_uppercase());
+                rest = &escape[3..];
+            }
+            // A stray `%`, left as it is
+            None => {
+                out.push('%');
+                rest = &escape[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Reject URLs the server would refuse for being too long.
+fn check_length(url: Url) -> Result<Url, GeminiError> {
+    if url.as_str().len() > MAX_URL_LEN {
+        return Err(GeminiError::InvalidUrl(format!(
+            "URL exceeds {MAX_URL_LEN} bytes"
+        )));
+    }
+    Ok(url)
 }
 
 #[cfg(test)]
@@ -64,6 +198,16 @@ mod tests {
         assert_eq!(url.path(), "/path");
     }
 
+    #[test]
+    fn test_parse_lowercases_scheme_and_host_only() {
+        let url = parse_gemini_url("GEMINI://Example.COM/Path").unwrap();
+        assert_eq!(url.host_str(), Some("example.com"));
+        assert_eq!(url.path(), "/Path");
+        assert_eq!(url.as_str(), "gemini://example.com/Path");
+        let url = resolve_url(&url, "//Other.Example/Q").unwrap();
+        assert_eq!(url.as_str(), "gemini://other.example/Q");
+    }
+
     #[test]
     fn test_parse_default_port() {
         let url = parse_gemini_url("gemini://example.com/").unwrap();
@@ -84,6 +228,51 @@ mod tests {
         assert_eq!(url.port(), Some(1966));
     }
 
+    #[test]
+    fn test_parse_bare_hostname() {
+        let url = parse_gemini_url("example.com").unwrap();
+        assert_eq!(url.as_str(), "gemini://example.com/");
+        let url = parse_gemini_url("example.com:1965/").unwrap();
+        assert_eq!(url.host_str(), Some("example.com"));
+        assert_eq!(url.path(), "/");
+        let url = parse_gemini_url("example.com/path").unwrap();
+        assert_eq!(url.as_str(), "gemini://example.com/path");
+        assert!(parse_gemini_url("http://x").is_err());
+    }
+
+    #[test]
+    fn test_idn_host() {
+        let url = parse_gemini_url("gemini://café.example/menu").unwrap();
+        assert_eq!(url.as_str(), "gemini://xn--caf-dma.example/menu");
+        assert_eq!(display_host(&url), "café.example");
+
+        let base = Url::parse("gemini://example.com/").unwrap();
+        let resolved = resolve_url(&base, "//café.example/").unwrap();
+        assert_eq!(resolved.host_str(), Some("xn--caf-dma.example"));
+        assert_eq!(display_host(&base), "example.com");
+    }
+
+    #[test]
+    fn test_display_authority() {
+        let url = |raw| Url::parse(raw).unwrap();
+        assert_eq!(
+            display_authority(&url("gemini://example.com/")),
+            "example.com"
+        );
+        assert_eq!(
+            display_authority(&url("gemini://example.com:1965/")),
+            "example.com"
+        );
+        assert_eq!(
+            display_authority(&url("gemini://example.com:1966/")),
+            "example.com:1966"
+        );
+        assert_eq!(
+            display_authority(&url("gemini://xn--caf-dma.example:300/")),
+            "café.example:300"
+        );
+    }
+
     #[test]
     fn test_reject_non_gemini_scheme() {
         let result = parse_gemini_url("https://example.com");
@@ -100,6 +289,22 @@ mod tests {
         assert!(matches!(err, GeminiError::InvalidUrl(_)));
     }
 
+    #[test]
+    fn test_url_length_limit() {
+        let prefix = "gemini://example.com/";
+        let at_limit = format!("{prefix}{}", "a".repeat(MAX_URL_LEN - prefix.len()));
+        assert_eq!(parse_gemini_url(&at_limit).unwrap().as_str().len(), 1024);
+
+        let over = format!("{at_limit}a");
+        match parse_gemini_url(&over) {
+            Err(GeminiError::InvalidUrl(msg)) => assert_eq!(msg, "URL exceeds 1024 bytes"),
+            other => panic!("expected InvalidUrl, got {other:?}"),
+        }
+
+        let base = Url::parse(prefix).unwrap();
+        assert!(resolve_url(&base, &"a".repeat(MAX_URL_LEN)).is_err());
+    }
+
     #[test]
     fn test_resolve_absolute_url() {
         let base = Url::parse("gemini://base.com/dir/page").unwrap();
@@ -131,4 +336,89 @@ mod tests {
         assert_eq!(resolved.host_str(), Some("host"));
         assert_eq!(resolved.path(), "/a/up");
     }
+
+    #[test]
+    fn test_normalize_equivalent_urls() {
+        let canonical = Url::parse("gemini://h.example/a/").unwrap();
+        for raw in [
+            "gemini://h.example/a/",
+            "gemini://H.Example/a/",
+            "gemini://h.example:1965/a/",
+            "gemini://h.example/a/../a/",
+            "gemini://h.example/a/./b/../",
+        ] {
+            assert_eq!(normalize(&Url::parse(raw).unwrap()), canonical, "{raw}");
+        }
+        assert_eq!(
+            normalize(&Url::parse("gemini://h.example").unwrap()).as_str(),
+            "gemini://h.example/"
+        );
+        // Other ports, paths, and queries still tell URLs apart
+        assert_ne!(
+            normalize(&Url::parse("gemini://h.example:1966/a/").unwrap()),
+            canonical
+        );
+        assert_ne!(
+            normalize(&Url::parse("gemini://h.example/a").unwrap()),
+            canonical
+        );
+        assert_ne!(
+            normalize(&Url::parse("gemini://h.example/a/?q").unwrap()),
+            canonical
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_percent_encoding() {
+        let canonical = |raw: &str| canonicalize(&Url::parse(raw).unwrap()).to_string();
+        assert_eq!(
+            canonical("gemini://h.example/%7euser/"),
+            canonical("gemini://h.example/~user/")
+        );
+        assert_eq!(
+            canonical("gemini://h.example/%7Euser/"),
+            "gemini://h.example/~user/"
+        );
+        assert_eq!(
+            canonical("gemini://H.Example/%41%2d%5F"),
+            "gemini://h.example/A-_"
+        );
+        // Reserved and non-ASCII bytes stay escaped, with uppercase hex
+        assert_eq!(
+            canonical("gemini://h.example/a%2fb"),
+            "gemini://h.example/a%2Fb"
+        );
+        assert_eq!(
+            canonical("gemini://h.example/caf%c3%a9"),
+            "gemini://h.example/caf%C3%A9"
+        );
+        assert_eq!(
+            canonical("gemini://h.example/?q%3d%61"),
+            "gemini://h.example/?q%3Da"
+        );
+        assert_eq!(
+            canonical("gemini://h.example/100%"),
+            "gemini://h.example/100%"
+        );
+    }
+
+    #[test]
+    fn test_resolve_query_only() {
+        let base = Url::parse("gemini://h/path").unwrap();
+        let resolved = resolve_url(&base, "?search").unwrap();
+        assert_eq!(resolved.as_str(), "gemini://h/path?search");
+
+        // A new query replaces the old one
+        let base = Url::parse("gemini://h/path?old#frag").unwrap();
+        let resolved = resolve_url(&base, "?new").unwrap();
+        assert_eq!(resolved.as_str(), "gemini://h/path?new");
+    }
+
+    #[test]
+    fn test_resolve_fragment_only() {
+        // The query is the page's input, so a fragment link keeps it
+        let base = Url::parse("gemini://h/path?q").unwrap();
+        let resolved = resolve_url(&base, "#section").unwrap();
+        assert_eq!(resolved.as_str(), "gemini://h/path?q#section");
+    }
 }
@@ -0,0 +1,202 @@
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, RootCertStore, SignatureScheme};
+
+/// How server certificates are checked, as chosen with `--verify`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerifyMode {
+    /// Pin each host's certificate on first use (`tofu::TofuVerifier`).
+    Tofu,
+    /// Require a chain to a well-known certificate authority.
+    Ca,
+    /// Accept any certificate.
+    Insecure,
+}
+
+impl VerifyMode {
+    /// Parse a `--verify` value: `tofu`, `ca`, or `insecure`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "tofu" => Some(VerifyMode::Tofu),
+            "ca" => Some(VerifyMode::Ca),
+            "insecure" => Some(VerifyMode::Insecure),
+            _ => None,
+        }
+    }
+
+    /// The verifier to install on the client. None leaves the client's
+    /// default TOFU verification in place.
+    pub fn verifier(self) -> Result<Option<Arc<dyn ServerCertVerifier>>, rustls::Error> {
+        Ok(match self {
+            VerifyMode::Tofu => None,
+            VerifyMode::Ca => Some(Arc::new(CaVerifier::new()?)),
+            VerifyMode::Insecure => Some(Arc::new(InsecureVerifier)),
+        })
+    }
+}
+
+/// Accepts every server certificate without checking it. Handshake
+/// signatures are still verified.
+#[derive(Debug)]
+pub struct InsecureVerifier;
+
+impl ServerCertVerifier for InsecureVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &rustls::crypto::ring::default_provider().signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &rustls::crypto::ring::default_provider().signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        rustls::crypto::ring::default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Standard WebPKI verification against the platform's root store, as
+/// loaded by `rustls-native-certs`. Most capsules use self-signed certificates, so this is
+/// only useful for hosts known to have CA-issued ones.
+#[derive(Debug)]
+pub struct CaVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+}
+
+impl CaVerifier {
+    /// Fails when no usable root certificate can be found on this system.
+    pub fn new() -> Result<Self, rustls::Error> {
+        let mut roots = RootCertStore::empty();
+        let native = rustls_native_certs::load_native_certs();
+        roots.add_parsable_certificates(native.certs);
+        if roots.is_empty() {
+            let reason = match native.errors.first() {
+                Some(e) => format!("no root certificates found: {e}"),
+                None => "no root certificates found".to_string(),
+            };
+            return Err(rustls::Error::General(reason));
+        }
+        Self::with_roots(roots)
+    }
+
+    /// Verify against `roots` instead of the platform's root store.
+    pub fn with_roots(roots: RootCertStore) -> Result<Self, rustls::Error> {
+        let inner = WebPkiServerVerifier::builder_with_provider(
+            Arc::new(roots),
+            Arc::new(rustls::crypto::ring::default_provider()),
+        )
+        .build()
+        .map_err(|e| rustls::Error::General(e.to_string()))?;
+        Ok(Self { inner })
+    }
+}
+
+impl ServerCertVerifier for CaVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini::{GeminiClient, GeminiError};
+    use crate::test_support::MockServer;
+
+    #[test]
+    fn test_parse_verify_mode() {
+        assert_eq!(VerifyMode::parse("tofu"), Some(VerifyMode::Tofu));
+        assert_eq!(VerifyMode::parse("ca"), Some(VerifyMode::Ca));
+        assert_eq!(VerifyMode::parse("insecure"), Some(VerifyMode::Insecure));
+        assert_eq!(VerifyMode::parse("none"), None);
+    }
+
+    /// A root store holding one freshly made CA that signed nothing.
+    fn unrelated_roots() -> RootCertStore {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca = params.self_signed(&key_pair).unwrap();
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.der().clone()).unwrap();
+        roots
+    }
+
+    #[test]
+    fn test_ca_verifier_rejects_self_signed() {
+        let server = MockServer::new().route("/", "20 text/gemini\r\n").start();
+        let verifier = CaVerifier::with_roots(unrelated_roots()).unwrap();
+        let client = GeminiClient::builder()
+            .cert_verifier(Arc::new(verifier))
+            .build();
+        assert!(matches!(
+            client.fetch(&server.url("/")),
+            Err(GeminiError::TlsError(_))
+        ));
+    }
+}
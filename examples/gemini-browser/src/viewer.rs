@@ -0,0 +1,208 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::mime::MimeType;
+
+/// Numbers temp files uniquely within this process.
+static NEXT_FILE: AtomicUsize = AtomicUsize::new(1);
+
+/// Opens media bodies (images, audio, video, PDFs) in an external program.
+///
+/// Each body is written to its own new file in a private directory under the
+/// system temp directory, named with an extension matching its MIME type so
+/// the viewer can tell what it is. The directory is removed when the
+/// `Viewer` is dropped.
+#[derive(Debug)]
+pub struct Viewer {
+    /// Program and arguments to run; the file path is appended.
+    command: Vec<String>,
+    /// Created on first use, readable only by this user.
+    dir: Option<PathBuf>,
+}
+
+impl Viewer {
+    /// A viewer running `command` (split on whitespace), or the system
+    /// opener (`xdg-open`, or `open` on macOS) when none is given.
+    pub fn new(command: Option<&str>) -> Self {
+        let command = match command {
+            Some(command) => command.split_whitespace().map(String::from).collect(),
+            None => vec![default_opener().to_string()],
+        };
+        Self { command, dir: None }
+    }
+
+    /// Whether a body of this type should go to the viewer rather than be
+    /// rendered or skipped. Only common image, audio, and video formats and
+    /// PDFs qualify, so a capsule can't hand the system opener a script or
+    /// an installer.
+    pub fn handles(mime: &MimeType) -> bool {
+        extension(mime).is_some()
+    }
+
+    /// Write `body` to a new temp file and run the viewer on it.
+    pub fn open(&mut self, mime: &MimeType, body: &[u8]) -> io::Result<PathBuf> {
+        let Some(extension) = extension(mime) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a viewable type", mime.essence),
+            ));
+        };
+        let path = self.write_temp(extension, body)?;
+        let Some((program, args)) = self.command.split_first() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "empty viewer command",
+            ));
+        };
+        let status = Command::new(program).args(args).arg(&path).status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("{program} exited with {status}")));
+        }
+        Ok(path)
+    }
+
+    fn write_temp(&mut self, extension: &str, body: &[u8]) -> io::Result<PathBuf> {
+        let path = self.dir()?.join(format!(
+            "{}.{extension}",
+            NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        file.write_all(body)?;
+        Ok(path)
+    }
+
+    /// The private directory for temp files, created if need be. Creation
+    /// fails rather than reusing a directory that already exists, since
+    /// someone else may control it.
+    fn dir(&mut self) -> io::Result<&Path> {
+        let dir = match self.dir.take() {
+            Some(dir) => dir,
+            None => {
+                let path = std::env::temp_dir().join(format!(
+                    "gemini-browser-{}-{}",
+                    std::process::id(),
+                    NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+                ));
+                create_private_dir(&path)?;
+                path
+            }
+        };
+        Ok(self.dir.insert(dir))
+    }
+}
+
+impl Drop for Viewer {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new().mode(0o700).create(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    fs::create_dir(path)
+}
+
+fn default_opener() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    }
+}
+
+/// File extension for a media type the viewer handles, or None for any
+/// other type.
+fn extension(mime: &MimeType) -> Option<&'static str> {
+    let extension = match mime.essence.as_str() {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/bmp" => "bmp",
+        "audio/mpeg" => "mp3",
+        "audio/ogg" => "ogg",
+        "audio/flac" => "flac",
+        "audio/wav" | "audio/x-wav" => "wav",
+        "video/mp4" => "mp4",
+        "video/webm" => "webm",
+        "application/pdf" => "pdf",
+        _ => return None,
+    };
+    Some(extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mime;
+
+    #[test]
+    fn test_extension_from_mime() {
+        assert_eq!(extension(&mime::parse("image/png")), Some("png"));
+        assert_eq!(extension(&mime::parse("Image/JPEG; q=1")), Some("jpg"));
+        assert_eq!(extension(&mime::parse("application/pdf")), Some("pdf"));
+        assert_eq!(extension(&mime::parse("application/x-unknown")), None);
+    }
+
+    #[test]
+    fn test_handles_media_types_only() {
+        assert!(Viewer::handles(&mime::parse("image/png")));
+        assert!(Viewer::handles(&mime::parse("audio/ogg")));
+        assert!(Viewer::handles(&mime::parse("application/pdf")));
+        assert!(!Viewer::handles(&mime::parse("text/plain")));
+        assert!(!Viewer::handles(&mime::parse("text/gemini")));
+        assert!(!Viewer::handles(&mime::parse("image/svg+xml")));
+        assert!(!Viewer::handles(&mime::parse("application/x-sh")));
+        assert!(!Viewer::handles(&mime::parse("application/octet-stream")));
+    }
+
+    #[test]
+    fn test_open_runs_command_and_drop_removes_files() {
+        let mut viewer = Viewer::new(Some("true"));
+        let path = viewer.open(&mime::parse("image/png"), b"\x89PNG").unwrap();
+        assert_eq!(path.extension().unwrap(), "png");
+        assert_eq!(fs::read(&path).unwrap(), b"\x89PNG");
+        let dir = path.parent().unwrap().to_path_buf();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        drop(viewer);
+        assert!(!path.exists());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_failed_viewer_still_cleans_up() {
+        let mut viewer = Viewer::new(Some("false"));
+        assert!(viewer.open(&mime::parse("audio/mpeg"), b"id3").is_err());
+        let dir = viewer.dir.clone().unwrap();
+        drop(viewer);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_open_refuses_other_types() {
+        let mut viewer = Viewer::new(Some("true"));
+        assert!(viewer
+            .open(&mime::parse("application/x-sh"), b"rm")
+            .is_err());
+        assert!(viewer.dir.is_none());
+    }
+}